@@ -0,0 +1,19 @@
+use std::process::ExitCode;
+
+use clap::Parser;
+
+use app_lib::Options;
+
+
+/* Run the Dill server without a window, e.g. on CI or in Docker, taking the same options as Dill */
+fn main() -> ExitCode {
+    let options: Options = Options { headless: true, ..Options::parse() };
+
+    match app_lib::run_headless(options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Dill server failed: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -1,7 +1,12 @@
+mod error;
 mod fetch;
 pub mod save;
 mod breakpoint;
 mod source;
+mod timeline;
+mod rules;
+mod watchpoints;
+mod server;
 
 /* Expose command handlers for Tauri setup */
 pub fn handlers() -> impl Fn(tauri::ipc::Invoke) -> bool {
@@ -16,9 +21,32 @@ pub fn handlers() -> impl Fn(tauri::ipc::Invoke) -> bool {
         save::update_refs,
         save::reset_refs,
         save::delete_saved_trees,
+        save::open_startup_files,
         breakpoint::skip_breakpoints,
         breakpoint::skip_all_breakpoints,
         breakpoint::terminate_debugging,
-        source::request_source_file
+        breakpoint::continue_until_parser,
+        breakpoint::continue_until_failure,
+        breakpoint::continue_until_offset,
+        breakpoint::resume_with_refs,
+        breakpoint::set_breakpoint_timeout,
+        breakpoint::get_breakpoint_timeout,
+        breakpoint::list_breakpoint_queue,
+        breakpoint::set_focus_policy,
+        breakpoint::get_focus_policy,
+        source::request_source_file,
+        timeline::list_timeline,
+        timeline::load_snapshot,
+        timeline::diff_snapshot,
+        rules::create_rule,
+        rules::list_rules,
+        rules::enable_rule,
+        rules::delete_rule,
+        watchpoints::add_watchpoint,
+        watchpoints::list_watchpoints,
+        watchpoints::remove_watchpoint,
+        server::server_status,
+        server::server_token,
+        server::restart_server
     ]
 }
@@ -1,31 +1,105 @@
-use crate::state::{StateError, StateManager, state_manager::BreakpointCode};
+use super::error::CommandError;
+use crate::state::{StateManager, state_manager::BreakpointCode};
+use crate::state::breakpoint_timeout::TimeoutPolicy;
+use crate::state::breakpoint_queue::FocusPolicy;
+use crate::trees::{ref_metadata, DebugTree, SessionId};
 use crate::AppState;
 
+/* Action taken at a breakpoint, as sent by the frontend */
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum BreakpointAction {
+    Skip { skips: i32 },
+    SkipAll,
+    Terminate,
+    UntilParser { name: String },
+    UntilFailure,
+    UntilOffset { offset: u32 },
+}
+
+impl From<BreakpointAction> for BreakpointCode {
+    fn from(action: BreakpointAction) -> Self {
+        match action {
+            BreakpointAction::Skip { skips } => BreakpointCode::Skip(skips),
+            BreakpointAction::SkipAll => BreakpointCode::SkipAll,
+            BreakpointAction::Terminate => BreakpointCode::Terminate,
+            BreakpointAction::UntilParser { name } => BreakpointCode::UntilParser(name),
+            BreakpointAction::UntilFailure => BreakpointCode::UntilFailure,
+            BreakpointAction::UntilOffset { offset } => BreakpointCode::UntilOffset(offset),
+        }
+    }
+}
+
+/* Resume a session's breakpoint with an action and the edited references in one step */
+/* The references are checked against their declared types before anything is sent */
 #[tauri::command]
-pub fn skip_breakpoints(state: tauri::State<'_, AppState>, session_id: i32, skips: i32) -> Result<(), SkipBreakpointError> {
-    state.transmit_breakpoint_skips(session_id, BreakpointCode::Skip(skips)).map_err(SkipBreakpointError::from)
+pub fn resume_with_refs(state: tauri::State<'_, AppState>, session_id: SessionId, action: BreakpointAction, refs: Vec<(i32, String)>) -> Result<(), CommandError> {
+    let tree: DebugTree = state.get_tree(session_id).map_err(|err| CommandError::from(err).for_session(session_id))?;
+    ref_metadata::validate_refs(tree.ref_metadata(), &refs).map_err(|errors| CommandError::invalid_refs(errors).for_session(session_id))?;
+
+    state.resume_breakpoint(session_id, BreakpointCode::from(action), refs).map_err(|err| CommandError::from(err).for_session(session_id))
 }
 
 #[tauri::command]
-pub fn skip_all_breakpoints(state: tauri::State<'_, AppState>, session_id: i32) -> Result<(), SkipBreakpointError> {
-    state.transmit_breakpoint_skips(session_id, BreakpointCode::SkipAll).map_err(SkipBreakpointError::from)
+pub fn skip_breakpoints(state: tauri::State<'_, AppState>, session_id: SessionId, skips: i32) -> Result<(), CommandError> {
+    state.transmit_breakpoint_skips(session_id, BreakpointCode::Skip(skips)).map_err(|err| CommandError::from(err).for_session(session_id))
 }
 
 #[tauri::command]
-pub fn terminate_debugging(state: tauri::State<'_, AppState>, session_id: i32) -> Result<(), SkipBreakpointError> {
-    state.transmit_breakpoint_skips(session_id, BreakpointCode::Terminate).map_err(SkipBreakpointError::from)
+pub fn skip_all_breakpoints(state: tauri::State<'_, AppState>, session_id: SessionId) -> Result<(), CommandError> {
+    state.transmit_breakpoint_skips(session_id, BreakpointCode::SkipAll).map_err(|err| CommandError::from(err).for_session(session_id))
 }
 
-#[derive(Debug, serde::Serialize)]
-pub enum SkipBreakpointError {
-    ChannelError,
+#[tauri::command]
+pub fn terminate_debugging(state: tauri::State<'_, AppState>, session_id: SessionId) -> Result<(), CommandError> {
+    state.transmit_breakpoint_skips(session_id, BreakpointCode::Terminate).map_err(|err| CommandError::from(err).for_session(session_id))
 }
 
-impl From<StateError> for SkipBreakpointError {
-    fn from(err: StateError) -> Self {
-        match err {
-            StateError::ChannelError => Self::ChannelError,
-            e => panic!("Unexpected error on skip_breakpoints: {:?}", e),
-        }
-    }
-}
\ No newline at end of file
+/* Continue until a parser with the given name is entered */
+#[tauri::command]
+pub fn continue_until_parser(state: tauri::State<'_, AppState>, session_id: SessionId, name: String) -> Result<(), CommandError> {
+    state.transmit_breakpoint_skips(session_id, BreakpointCode::UntilParser(name)).map_err(|err| CommandError::from(err).for_session(session_id))
+}
+
+/* Continue until the next parser failure */
+#[tauri::command]
+pub fn continue_until_failure(state: tauri::State<'_, AppState>, session_id: SessionId) -> Result<(), CommandError> {
+    state.transmit_breakpoint_skips(session_id, BreakpointCode::UntilFailure).map_err(|err| CommandError::from(err).for_session(session_id))
+}
+
+/* Continue until the parse reaches the given input offset */
+#[tauri::command]
+pub fn continue_until_offset(state: tauri::State<'_, AppState>, session_id: SessionId, offset: u32) -> Result<(), CommandError> {
+    state.transmit_breakpoint_skips(session_id, BreakpointCode::UntilOffset(offset)).map_err(|err| CommandError::from(err).for_session(session_id))
+}
+
+/* Set how long a session's breakpoints are waited at before its timeout action, or every session's if none is given */
+#[tauri::command]
+pub fn set_breakpoint_timeout(state: tauri::State<'_, AppState>, session_id: Option<SessionId>, policy: TimeoutPolicy) -> Result<(), CommandError> {
+    state.set_timeout_policy(session_id, policy).map_err(CommandError::from)
+}
+
+#[tauri::command]
+pub fn get_breakpoint_timeout(state: tauri::State<'_, AppState>, session_id: Option<SessionId>) -> Result<String, CommandError> {
+    serde_json::to_string(&state.timeout_policy(session_id)?)
+        .map_err(|_| CommandError::serialise_failed("the timeout policy"))
+}
+
+/* Sessions waiting at a breakpoint, with the parser each paused at and when */
+#[tauri::command]
+pub fn list_breakpoint_queue(state: tauri::State<'_, AppState>) -> Result<String, CommandError> {
+    serde_json::to_string(&state.breakpoint_queue()?)
+        .map_err(|_| CommandError::serialise_failed("the breakpoint queue"))
+}
+
+/* Choose which of several paused sessions is shown */
+#[tauri::command]
+pub fn set_focus_policy(state: tauri::State<'_, AppState>, policy: FocusPolicy) -> Result<(), CommandError> {
+    state.set_focus_policy(policy).map_err(CommandError::from)
+}
+
+#[tauri::command]
+pub fn get_focus_policy(state: tauri::State<'_, AppState>) -> Result<String, CommandError> {
+    serde_json::to_string(&state.focus_policy()?)
+        .map_err(|_| CommandError::serialise_failed("the focus policy"))
+}
@@ -0,0 +1,199 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::state::StateError;
+use crate::state::state_manager::UpdateTreeError;
+use crate::trees::ref_metadata::RefFieldError;
+use crate::trees::SessionId;
+
+
+/* Stable codes identifying why a command failed, which the frontend matches on */
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorCode {
+    LockFailed,                 /* Dill's state could not be locked */
+    TreeNotFound,               /* No tree is held for the session */
+    NodeNotFound,               /* The session's tree has no node with the id */
+    ParentNotFound,             /* A tree delta could not be attached to the session's tree */
+    TabNotFound,                /* No tab is open at the index */
+    SnapshotNotFound,           /* The session has no breakpoint snapshot at the index */
+    RuleNotFound,               /* No breakpoint rule has the id */
+    WatchpointExists,           /* The reference is already watched */
+    WatchpointNotFound,         /* The reference is not watched */
+    BreakpointClosed,           /* The parser is no longer waiting at the breakpoint */
+    EventEmitFailed,            /* The frontend could not be notified */
+    PathUnavailable,            /* A directory Dill uses could not be found */
+    ReadFileFailed,
+    WriteFileFailed,
+    SerialiseFailed,
+    DeserialiseFailed,
+    InvalidRefs,                /* Edited references do not match their declared types */
+}
+
+
+/* What a failed command was acting on, so the frontend can say more than the message */
+#[derive(Debug, Default, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorContext {
+    #[serde(skip_serializing_if = "Option::is_none")] session_id: Option<SessionId>,
+    #[serde(skip_serializing_if = "Option::is_none")] node_id: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")] index: Option<usize>,         /* Index of a tab or snapshot */
+    #[serde(skip_serializing_if = "Option::is_none")] rule_id: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")] address: Option<i32>,         /* Address of a reference */
+    #[serde(skip_serializing_if = "Option::is_none")] path: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")] invalid_refs: Option<Vec<RefFieldError>>,
+}
+
+/* Error returned by every command, serialised for the frontend as { code, message, context } */
+#[derive(Debug, PartialEq, serde::Serialize)]
+pub struct CommandError {
+    code: ErrorCode,
+    message: String,
+    context: Box<ErrorContext>,       /* Boxed to keep command results small */
+}
+
+impl CommandError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        CommandError { code, message: message.into(), context: Box::default() }
+    }
+
+    /* A file could not be read or written */
+    pub fn file(code: ErrorCode, path: &Path, err: io::Error) -> Self {
+        let action: &str = if code == ErrorCode::ReadFileFailed { "read" } else { "write" };
+        CommandError::new(code, format!("Could not {action} {}: {err}", path.display())).for_path(path)
+    }
+
+    pub fn serialise_failed(what: &str) -> Self {
+        CommandError::new(ErrorCode::SerialiseFailed, format!("Could not serialise {what}"))
+    }
+
+    /* Edited references did not match their declared types */
+    pub fn invalid_refs(errors: Vec<RefFieldError>) -> Self {
+        let mut error = CommandError::new(ErrorCode::InvalidRefs, format!("{} edited references do not match their types", errors.len()));
+        error.context.invalid_refs = Some(errors);
+        error
+    }
+
+    /* Name the session the failed command was acting on */
+    pub fn for_session(mut self, session_id: SessionId) -> Self {
+        self.context.session_id = Some(session_id);
+        self
+    }
+
+    pub fn for_index(mut self, index: usize) -> Self {
+        self.context.index = Some(index);
+        self
+    }
+
+    pub fn for_path(mut self, path: &Path) -> Self {
+        self.context.path = Some(path.to_path_buf());
+        self
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/* Every state error maps to a command error, so no command can panic on an unexpected one */
+impl From<StateError> for CommandError {
+    fn from(err: StateError) -> Self {
+        let mut context = ErrorContext::default();
+
+        let (code, message): (ErrorCode, String) = match err {
+            StateError::LockFailed => (ErrorCode::LockFailed, String::from("Could not lock Dill's state, try restarting Dill")),
+            StateError::TreeNotFound => (ErrorCode::TreeNotFound, String::from("No tree is held for this session")),
+            StateError::NodeNotFound(node_id) => {
+                context.node_id = Some(node_id);
+                (ErrorCode::NodeNotFound, format!("No node with id {node_id} in this tree"))
+            },
+            StateError::ParentNotFound(node_id) => {
+                context.node_id = Some(node_id);
+                (ErrorCode::ParentNotFound, format!("No node with child id {node_id} to attach nodes to"))
+            },
+            StateError::EventEmitFailed => (ErrorCode::EventEmitFailed, String::from("Could not notify the frontend")),
+            StateError::AbsolutePathNotAllowed => (ErrorCode::PathUnavailable, String::from("Expected a path relative to Dill's directories")),
+            StateError::GetTempdirPathFail => (ErrorCode::PathUnavailable, String::from("Could not find the saved trees directory")),
+            StateError::GetDownloadPathFail => (ErrorCode::PathUnavailable, String::from("Could not find the downloads directory")),
+            StateError::ChannelError => (ErrorCode::BreakpointClosed, String::from("The parser is no longer waiting at this breakpoint")),
+            StateError::TabOutOfBounds => (ErrorCode::TabNotFound, String::from("No tab is open at this position")),
+            StateError::SnapshotNotFound(index) => {
+                context.index = Some(index);
+                (ErrorCode::SnapshotNotFound, format!("No breakpoint snapshot {index} in this session"))
+            },
+            StateError::RuleNotFound(rule_id) => {
+                context.rule_id = Some(rule_id);
+                (ErrorCode::RuleNotFound, format!("No breakpoint rule with id {rule_id}"))
+            },
+            StateError::WatchpointExists(address) => {
+                context.address = Some(address);
+                (ErrorCode::WatchpointExists, format!("Reference {address} is already watched"))
+            },
+            StateError::WatchpointNotFound(address) => {
+                context.address = Some(address);
+                (ErrorCode::WatchpointNotFound, format!("Reference {address} is not watched"))
+            },
+        };
+
+        CommandError { code, message, context: Box::new(context) }
+    }
+}
+
+impl From<UpdateTreeError> for CommandError {
+    fn from(err: UpdateTreeError) -> Self {
+        match err {
+            UpdateTreeError::LockFailed => CommandError::from(StateError::LockFailed),
+            UpdateTreeError::TreeNotFound => CommandError::from(StateError::TreeNotFound),
+            UpdateTreeError::SerialiseFailed => CommandError::serialise_failed("the tree"),
+            UpdateTreeError::OpenFileFailed => CommandError::new(ErrorCode::WriteFileFailed, "Could not open the tree's save file"),
+            UpdateTreeError::WriteTreeFailed => CommandError::new(ErrorCode::WriteFileFailed, "Could not write the tree's save file"),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+
+    /* Command error unit testing */
+
+    use std::path::Path;
+
+    use super::{CommandError, ErrorCode};
+    use crate::state::StateError;
+    use crate::trees::session_id::test::id;
+
+    #[test]
+    fn error_serialises_with_code_message_and_context() {
+        let error: CommandError = CommandError::from(StateError::NodeNotFound(7)).for_session(id(2));
+
+        assert_eq!(
+            serde_json::to_value(&error).expect("Could not serialise error"),
+            serde_json::json!({
+                "code": "nodeNotFound",
+                "message": "No node with id 7 in this tree",
+                "context": { "sessionId": id(2).to_string(), "nodeId": 7 }
+            })
+        );
+    }
+
+    #[test]
+    fn unexpected_state_errors_do_not_panic() {
+        assert_eq!(CommandError::from(StateError::GetDownloadPathFail).code, ErrorCode::PathUnavailable);
+        assert_eq!(CommandError::from(StateError::ChannelError).code, ErrorCode::BreakpointClosed);
+    }
+
+    #[test]
+    fn file_error_names_path() {
+        let error: CommandError = CommandError::file(ErrorCode::ReadFileFailed, Path::new("tree.json"), std::io::ErrorKind::NotFound.into());
+
+        let json: serde_json::Value = serde_json::to_value(&error).expect("Could not serialise error");
+        assert_eq!(json["context"]["path"], "tree.json");
+        assert!(error.to_string().starts_with("Could not read tree.json"));
+    }
+}
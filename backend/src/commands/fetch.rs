@@ -1,57 +1,26 @@
-use crate::state::{StateError, StateManager};
+use super::error::CommandError;
+use crate::state::StateManager;
 use crate::AppState;
-use crate::trees::DebugNode;
+use crate::trees::{DebugNode, SessionId};
 
 
 /* Frontend-accessible debug render */
 #[tauri::command]
-pub fn fetch_debug_tree(state: tauri::State<AppState>) -> Result<String, FetchTreeError> {
-    serde_json::to_string_pretty(&state.get_tree()?)
-        .map_err(|_| FetchTreeError::SerialiseFailed)
-}
-
-#[derive(Debug, serde::Serialize)]
-pub enum FetchTreeError {
-    LockFailed,
-    TreeNotFound,
-    SerialiseFailed,
-}
+pub fn fetch_debug_tree(state: tauri::State<AppState>, session_id: SessionId) -> Result<String, CommandError> {
+    let tree = state.get_tree(session_id).map_err(|err| CommandError::from(err).for_session(session_id))?;
 
-impl From<StateError> for FetchTreeError {
-    fn from(state_error: StateError) -> Self {
-        match state_error {
-            StateError::LockFailed => FetchTreeError::LockFailed,
-            StateError::TreeNotFound => FetchTreeError::TreeNotFound,
-            e => panic!("Unexpected error on fetch_debug_tree: {:?}", e),
-        }
-    }
+    serde_json::to_string_pretty(&tree)
+        .map_err(|_| CommandError::serialise_failed("the tree").for_session(session_id))
 }
 
 
 /* Backend reactive fetch children */
 #[tauri::command]
-pub fn fetch_node_children(state: tauri::State<AppState>, node_id: u32) -> Result<String, FetchChildrenError> {
-    /* Find node with corresponding node id */
-    let node: DebugNode = state.get_node(node_id)?;
+pub fn fetch_node_children(state: tauri::State<AppState>, session_id: SessionId, node_id: u32) -> Result<String, CommandError> {
+    /* Find children of node with corresponding node id in the session's tree */
+    let children: Vec<DebugNode> = state.get_children(session_id, node_id).map_err(|err| CommandError::from(err).for_session(session_id))?;
 
     /* Serialise children */
-    serde_json::to_string_pretty(&node.children)
-        .map_err(|_| FetchChildrenError::SerialiseFailed)
-}
-
-#[derive(Debug, serde::Serialize)]
-pub enum FetchChildrenError {
-    LockFailed,
-    NodeNotFound(u32),
-    SerialiseFailed,
-}
-
-impl From<StateError> for FetchChildrenError {
-    fn from(state_error: StateError) -> Self {
-        match state_error {
-            StateError::LockFailed => FetchChildrenError::LockFailed,
-            StateError::NodeNotFound(id) => FetchChildrenError::NodeNotFound(id),
-            e => panic!("Unexpected error on fetch_node_children: {:?}", e),
-        }
-    }
+    serde_json::to_string_pretty(&children)
+        .map_err(|_| CommandError::serialise_failed("the node's children").for_session(session_id))
 }
@@ -0,0 +1,28 @@
+use super::error::CommandError;
+use crate::state::StateManager;
+use crate::state::breakpoint_rules::RuleCondition;
+use crate::AppState;
+
+
+/* Add an auto-continue rule, returning it with its allocated id */
+#[tauri::command]
+pub fn create_rule(state: tauri::State<AppState>, condition: RuleCondition) -> Result<String, CommandError> {
+    serde_json::to_string(&state.add_rule(condition)?)
+        .map_err(|_| CommandError::serialise_failed("the rule"))
+}
+
+#[tauri::command]
+pub fn list_rules(state: tauri::State<AppState>) -> Result<String, CommandError> {
+    serde_json::to_string(&state.rules()?)
+        .map_err(|_| CommandError::serialise_failed("the rules"))
+}
+
+#[tauri::command]
+pub fn enable_rule(state: tauri::State<AppState>, rule_id: u32, enabled: bool) -> Result<(), CommandError> {
+    state.set_rule_enabled(rule_id, enabled).map_err(CommandError::from)
+}
+
+#[tauri::command]
+pub fn delete_rule(state: tauri::State<AppState>, rule_id: u32) -> Result<(), CommandError> {
+    state.remove_rule(rule_id).map_err(CommandError::from)
+}
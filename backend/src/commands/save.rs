@@ -1,107 +1,76 @@
-use std::ffi::OsString;
 use std::fs::{self, File};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use super::error::{CommandError, ErrorCode};
 use crate::state::state_manager::DirectoryKind;
 use crate::AppState;
 use crate::events::Event;
 use crate::state::{StateError, StateManager};
-use crate::trees::{DebugTree, SavedTree};
+use crate::trees::{deep_json, DebugTree, SavedTree, SessionId};
+use crate::trees::ref_metadata;
 
 /* Generates the full path to a tree file in the form `APPDATA/SAVED_TREE_DIR/file_name` */
-fn format_filepath(state: &tauri::State<AppState>, session_id: i32) -> Result<OsString, StateError> {
-    state.system_path_to(DirectoryKind::SavedTrees, PathBuf::from(format!("{session_id}.json"))).map(OsString::from)
+fn format_filepath<S: StateManager>(state: &S, session_id: SessionId) -> Result<PathBuf, StateError> {
+    state.system_path_to(DirectoryKind::SavedTrees, PathBuf::from(format!("{session_id}.json")))
 }
 
 /* Downloads the tree into Downloads folder */
 #[tauri::command]
-pub fn download_tree(state: tauri::State<AppState>, index: usize) -> Result<(), DownloadTreeError> {
-    let (session_id, tree_name) = state.get_tab(index)?;
+pub fn download_tree(state: tauri::State<AppState>, index: usize) -> Result<(), CommandError> {
+    let (session_id, tree_name) = state.get_tab(index).map_err(|err| CommandError::from(err).for_index(index))?;
 
     /* Path to the json file used to store the tree */
-    let file_path: OsString = format_filepath(&state, session_id)?;
+    let file_path: PathBuf = format_filepath(state.inner(), session_id)?;
 
     /* Resolves to /path/to/downloads/tree_name.json */
     let download_path: PathBuf = state.system_path_to(DirectoryKind::Downloads, PathBuf::from(format!("{}.json", tree_name)))?;
 
     /* Creates a file in Downloads and copies data into it */
-    File::create(&download_path).map_err(|_| DownloadTreeError::CreateDirFailed)?;
-    fs::copy(file_path, download_path).map_err(|_| DownloadTreeError::WriteToFileFailed)?;
+    File::create(&download_path).map_err(|err| CommandError::file(ErrorCode::WriteFileFailed, &download_path, err))?;
+    fs::copy(&file_path, &download_path).map_err(|err| CommandError::file(ErrorCode::WriteFileFailed, &download_path, err).for_session(session_id))?;
 
     Ok(())
 }
 
-#[derive(Debug, serde::Serialize)]
-pub enum DownloadTreeError {
-    DownloadPathNotFound,
-    CreateDirFailed,
-    WriteToFileFailed,
+
+/* Imports JSON file to display a tree */
+#[tauri::command]
+pub fn import_tree(contents: String, state: tauri::State<AppState>) -> Result<(), CommandError> {
+    import_contents(&contents, state.inner())
 }
 
-impl From<StateError> for DownloadTreeError {
-    fn from(state_error: StateError) -> Self {
-        match state_error {
-            StateError::LockFailed => DownloadTreeError::DownloadPathNotFound,
-            StateError::TreeNotFound => DownloadTreeError::WriteToFileFailed,
-            e => panic!("Unexpected error on save_tree: {:?}", e),
-        }
+/* Imports the saved trees Dill was opened with, once the frontend is ready to show them */
+#[tauri::command]
+pub fn open_startup_files(state: tauri::State<AppState>) -> Result<(), CommandError> {
+    for file_path in state.take_startup_files()? {
+        let contents: String = fs::read_to_string(&file_path).map_err(|err| CommandError::file(ErrorCode::ReadFileFailed, &file_path, err))?;
+        import_contents(&contents, state.inner()).map_err(|err| err.for_path(&file_path))?;
     }
-}
 
+    Ok(())
+}
 
-/* Imports JSON file to display a tree */
-#[tauri::command]
-pub fn import_tree(contents: String, state: tauri::State<AppState>) -> Result<(), ImportTreeError> {
-    let assigned_session_id: i32 = state.next_session_id()?;
+/* Imports the contents of a saved tree into any state, such as the files opened on startup */
+pub fn import_contents<S: StateManager>(contents: &str, state: &S) -> Result<(), CommandError> {
+    let assigned_session_id: SessionId = state.next_session_id()?;
 
     /* Path to the json file used to store the tree */
-    let file_path: OsString = format_filepath(&state, assigned_session_id)?;
+    let file_path: PathBuf = format_filepath(state, assigned_session_id)?;
 
     /* Creates a file in apps local saved tree folders and writes data from external json it */
-    let mut imported_tree: File = File::create(&file_path).map_err(|_| ImportTreeError::CreateDirFailed)?;
-    imported_tree.write(contents.as_bytes()).map_err(|_| ImportTreeError::WriteToFileFailed)?;
-
-    /* Load tree in the state and emit an event to frontend, passing the new tree */
-    load_path(file_path, true, &state)?;
-    state.emit(Event::NewTree).map_err(ImportTreeError::from)
-}
+    File::create(&file_path)
+        .and_then(|mut imported_tree| imported_tree.write_all(contents.as_bytes()))
+        .map_err(|err| CommandError::file(ErrorCode::WriteFileFailed, &file_path, err))?;
 
-#[derive(Debug, serde::Serialize)]
-pub enum ImportTreeError {
-    CreateDirFailed,
-    WriteToFileFailed,
-    DeserialiseFailed,
-    EventEmitFailed,
-    LockFailed,
-    ReadFileFailed,
-}
-
-impl From<StateError> for ImportTreeError {
-    fn from(state_error: StateError) -> Self {
-        match state_error {
-            StateError::LockFailed => ImportTreeError::LockFailed,
-            StateError::EventEmitFailed => ImportTreeError::EventEmitFailed,
-            StateError::TreeNotFound => ImportTreeError::WriteToFileFailed,
-            e => panic!("Unexpected error on save_tree: {:?}", e),
-        }
-    }
-}
-
-impl From<LoadTreeError> for ImportTreeError {
-    fn from(load_error: LoadTreeError) -> Self {
-        match load_error {
-            LoadTreeError::LockFailed => ImportTreeError::LockFailed,
-            LoadTreeError::ReadFileFailed => ImportTreeError::ReadFileFailed,
-            LoadTreeError::DeserialiseFailed => ImportTreeError::DeserialiseFailed,
-            LoadTreeError::EventEmitFailed => ImportTreeError::EventEmitFailed,
-        }
-    }
+    /* Load tree in the state under the id its file is named by, and emit an event to frontend */
+    load_path(&file_path, state, assigned_session_id)?;
+    state.emit(Event::NewTree).map_err(CommandError::from)
 }
 
 /* Delete file associated with where tree is saved */
 #[tauri::command]
-pub fn delete_tree(state: tauri::State<AppState>, index: usize) -> Result<String, DeleteTreeError> {
+pub fn delete_tree(state: tauri::State<AppState>, index: usize) -> Result<String, CommandError> {
     // TODO idk yet
     /* Get the tree name given the index */
     // let tree_name: String = state.get_tree_name(index).map_err(|_| DeleteTreeError::NameRetrievalFail)?;
@@ -113,136 +82,102 @@ pub fn delete_tree(state: tauri::State<AppState>, index: usize) -> Result<String
     // fs::remove_file(file_path).map_err(|_| DeleteTreeError::TreeFileRemoveFail)?;
 
     /* Will remove entries inside internal state and returns a list of remaining tree names */
-    let tree_names: Vec<String> = state.rmv_tab(index).map_err(|_| DeleteTreeError::SessionIdRemovalFail)?;
+    let tree_names: Vec<String> = state.rmv_tab(index).map_err(|err| CommandError::from(err).for_index(index))?;
 
     serde_json::to_string_pretty(&tree_names)
-        .map_err(|_| DeleteTreeError::SerialiseFailed)
+        .map_err(|_| CommandError::serialise_failed("the remaining tabs"))
 }
 
-/* Deletes all saved trees */
+/* Deletes all saved trees, only forgetting them if they are in a directory chosen with --saved-trees-dir */
 #[tauri::command]
-pub fn delete_saved_trees(state: tauri::State<AppState>) -> Result<(), DeleteTreeError> {
+pub fn delete_saved_trees(state: tauri::State<AppState>) -> Result<(), CommandError> {
     state.reset_trees()?;
 
-    let path_to_saved_trees: PathBuf = state.system_path(DirectoryKind::SavedTrees)?;
+    /* A chosen directory keeps trees from earlier runs and may hold other files, including those being opened */
+    if state.keeps_saved_trees()? {
+        return Ok(());
+    }
 
-    fs::remove_dir_all(path_to_saved_trees.clone()).map_err(|_| DeleteTreeError::TreeFileRemoveFail)?;
-    fs::create_dir(path_to_saved_trees).map_err(|_| DeleteTreeError::FolderCreationFail)
-}
+    let path_to_saved_trees: PathBuf = state.system_path(DirectoryKind::SavedTrees)?;
+    let write_failed = |err: std::io::Error| CommandError::file(ErrorCode::WriteFileFailed, &path_to_saved_trees, err);
+    fs::create_dir_all(&path_to_saved_trees).map_err(write_failed)?;
 
-#[derive(Debug, serde::Serialize)]
-pub enum DeleteTreeError {
-    TreeFileRemoveFail,
-    SerialiseFailed,
-    SessionIdRemovalFail,
-    FolderCreationFail,
-    TreesResetFailed,
-}
+    for entry in fs::read_dir(&path_to_saved_trees).map_err(write_failed)? {
+        let path: PathBuf = entry.map_err(write_failed)?.path();
 
-impl From<StateError> for DeleteTreeError {
-    fn from(_: StateError) -> Self {
-        DeleteTreeError::TreesResetFailed
+        if path.extension().is_some_and(|ext| ext == "json") {
+            fs::remove_file(&path).map_err(|err| CommandError::file(ErrorCode::WriteFileFailed, &path, err))?;
+        }
     }
+
+    Ok(())
 }
 
 
-/* Fetches a tree from saved_trees and resets the tree in the tauri state */
+/* Shows the tree of the session in the given tab, which is already held in memory */
 #[tauri::command]
-pub fn load_saved_tree(index: usize, state: tauri::State<AppState>) -> Result<(), LoadTreeError>  {
-    /* Get the tree name given the index */
-    let (session_id, _) = state.get_tab(index)?;
+pub fn load_saved_tree(index: usize, state: tauri::State<AppState>) -> Result<(), CommandError>  {
+    /* Get the session id given the index */
+    let (session_id, _) = state.get_tab(index).map_err(|err| CommandError::from(err).for_index(index))?;
 
-    /* Get the file path of the tree to be reloaded */
-    let file_path: OsString = format_filepath(&state, session_id)?;
-    load_path(file_path, false, &state)
+    state.display_tree(session_id).map_err(|err| CommandError::from(err).for_session(session_id))?;
+
+    Ok(())
 }
 
-/* Loads a tree from the specified file path */
-fn load_path(file_path: OsString, is_import: bool, state: &tauri::State<AppState>) -> Result<(), LoadTreeError> {
+/* Loads an imported tree from the specified file path into the state */
+fn load_path<S: StateManager>(file_path: &Path, state: &S, session_id: SessionId) -> Result<(), CommandError> {
     /* Read the contents of the file as a string */
     let contents: String = fs::read_to_string(file_path)
-        .map_err(|_| LoadTreeError::ReadFileFailed)?;
+        .map_err(|err| CommandError::file(ErrorCode::ReadFileFailed, file_path, err))?;
 
 
     /* Deserialize the tree into SavedTree, then convert to DebugTree */
-    let saved_tree: SavedTree = serde_json::from_str(&contents).map_err(|_| LoadTreeError::DeserialiseFailed)?;
+    let saved_tree: SavedTree = deep_json::from_str(&contents)
+        .map_err(|err| CommandError::new(ErrorCode::DeserialiseFailed, format!("Not a saved Dill tree: {err}")).for_path(file_path))?;
 
     let mut tree: DebugTree = DebugTree::from(saved_tree);
 
-    /* If we are importing the tree, we must turn debugging off and give it a new session_id */
-    if is_import {
-        tree.set_is_debugging(false);
-        let session_id: i32 = state.next_session_id()?;
-        tree.set_session_id(session_id);
-    }
+    /* Imported trees cannot be debugged and take the session id they were saved under */
+    tree.set_is_debugging(false);
+    tree.set_session_id(session_id);
 
-    /* Update the global tauri state with the reloaded tree */
+    /* Store the imported tree in the global tauri state */
     state.set_tree(tree)?;
 
     Ok(())
 }
 
-#[derive(Debug, serde::Serialize)]
-#[allow(clippy::enum_variant_names)]
-pub enum LoadTreeError {
-    LockFailed,
-    ReadFileFailed,
-    DeserialiseFailed,
-    EventEmitFailed,
-}
-
-impl From<StateError> for LoadTreeError {
-    fn from(state_error: StateError) -> Self {
-        match state_error {
-            StateError::LockFailed => LoadTreeError::LockFailed,
-            StateError::EventEmitFailed => LoadTreeError::EventEmitFailed,
-            e => panic!("Unexpected error on load_saved_tree: {:?}", e),
-        }
-    }
-}
-
-/* Updates local changed references for a tree */
+/* Updates local changed references for a tree, rejecting values that do not match their declared types */
 #[tauri::command]
-pub fn update_refs(new_refs: Vec<(i32, String)>, state: tauri::State<AppState>) -> Result<(), RefError>  {
-    let session_id: i32 = state.get_tree()?.get_session_id();
+pub fn update_refs(new_refs: Vec<(i32, String)>, state: tauri::State<AppState>) -> Result<(), CommandError>  {
+    let session_id: SessionId = state.displayed_session_id()?;
+    let debug_tree: DebugTree = state.get_tree(session_id).map_err(|err| CommandError::from(err).for_session(session_id))?;
+
+    ref_metadata::validate_refs(debug_tree.ref_metadata(), &new_refs).map_err(|errors| CommandError::invalid_refs(errors).for_session(session_id))?;
 
-    Ok(state.update_refs(session_id, new_refs)?)
+    state.update_refs(session_id, new_refs).map_err(|err| CommandError::from(err).for_session(session_id))
 }
 
 /* Retrieves local changed references for a tree */
 #[tauri::command]
-pub fn get_refs(session_id: i32, state: tauri::State<AppState>) -> Result<String, RefError>  {
-    let refs: Vec<(i32, String)> = state.get_refs(session_id)?;
+pub fn get_refs(session_id: SessionId, state: tauri::State<AppState>) -> Result<String, CommandError>  {
+    let refs: Vec<(i32, String)> = state.get_refs(session_id).map_err(|err| CommandError::from(err).for_session(session_id))?;
 
     serde_json::to_string_pretty(&refs)
-        .map_err(|_| RefError::RefMapFail)
+        .map_err(|_| CommandError::serialise_failed("the references").for_session(session_id))
 }
 
 /* Resets local changes to default for a tree's refs */
 #[tauri::command]
-pub fn reset_refs(state: tauri::State<AppState>) -> Result<String, RefError>  {
-    let debug_tree: DebugTree = state.get_tree()?;
-
-    let session_id: i32 = debug_tree.get_session_id();
+pub fn reset_refs(state: tauri::State<AppState>) -> Result<String, CommandError>  {
+    let session_id: SessionId = state.displayed_session_id()?;
+    let debug_tree: DebugTree = state.get_tree(session_id).map_err(|err| CommandError::from(err).for_session(session_id))?;
 
     let default_refs: Vec<(i32, String)> = debug_tree.refs();
-    state.reset_refs(session_id, default_refs.clone())?;
+    state.reset_refs(session_id, default_refs.clone()).map_err(|err| CommandError::from(err).for_session(session_id))?;
 
     serde_json::to_string_pretty(&default_refs)
-        .map_err(|_| RefError::RefMapFail)
+        .map_err(|_| CommandError::serialise_failed("the references").for_session(session_id))
 }
 
-#[derive(Debug, serde::Serialize)]
-#[allow(clippy::enum_variant_names)]
-pub enum RefError {
-    RefMapFail,
-}
-
-impl From<StateError> for RefError {
-    fn from(state_error: StateError) -> Self {
-        match state_error {
-            StateError::LockFailed => RefError::RefMapFail,
-            e => panic!("Unexpected error on load_saved_tree: {:?}", e),
-        }
-    }
-}
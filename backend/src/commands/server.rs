@@ -0,0 +1,33 @@
+use super::error::CommandError;
+use crate::server::ServerControl;
+use crate::state::state_manager::BreakpointCode;
+use crate::state::StateManager;
+
+
+/* Get the server's most recent status, which may have been sent before the frontend was listening */
+#[tauri::command]
+pub fn server_status(control: tauri::State<ServerControl>) -> Result<String, CommandError> {
+    serde_json::to_string(&control.status())
+        .map_err(|_| CommandError::serialise_failed("the server status"))
+}
+
+/* Get the token remote clients must send, or null if authentication is disabled */
+#[tauri::command]
+pub fn server_token(control: tauri::State<ServerControl>) -> Result<String, CommandError> {
+    serde_json::to_string(&control.options().token)
+        .map_err(|_| CommandError::serialise_failed("the token"))
+}
+
+/* Stop the server and launch it again, on a different port if one is given */
+#[tauri::command]
+pub fn restart_server(port: Option<u16>, app: tauri::AppHandle, control: tauri::State<ServerControl>) -> Result<(), CommandError> {
+    /* Parsers waiting at a breakpoint would lose their connection, so stop debugging them */
+    for session_id in app.debuggable_session_ids()? {
+        app.transmit_breakpoint_skips(session_id, BreakpointCode::Terminate)
+            .map_err(|err| CommandError::from(err).for_session(session_id))?;
+    }
+
+    control.stop(port);
+    crate::spawn_server(&app, control.inner().clone());
+    Ok(())
+}
@@ -0,0 +1,29 @@
+use super::error::CommandError;
+use crate::state::StateManager;
+use crate::AppState;
+use crate::trees::SessionId;
+
+
+/* List every breakpoint snapshot recorded for a session */
+#[tauri::command]
+pub fn list_timeline(state: tauri::State<AppState>, session_id: SessionId) -> Result<String, CommandError> {
+    let timeline = state.timeline(session_id).map_err(|err| CommandError::from(err).for_session(session_id))?;
+
+    serde_json::to_string(&timeline)
+        .map_err(|_| CommandError::serialise_failed("the timeline").for_session(session_id))
+}
+
+/* Show breakpoint snapshot at index of a session in the frontend */
+#[tauri::command]
+pub fn load_snapshot(state: tauri::State<AppState>, session_id: SessionId, index: usize) -> Result<(), CommandError> {
+    state.view_snapshot(session_id, index).map_err(|err| CommandError::from(err).for_session(session_id))
+}
+
+/* Compare breakpoint snapshot at index of a session against the snapshot before it */
+#[tauri::command]
+pub fn diff_snapshot(state: tauri::State<AppState>, session_id: SessionId, index: usize) -> Result<String, CommandError> {
+    let diff = state.diff_snapshot(session_id, index).map_err(|err| CommandError::from(err).for_session(session_id))?;
+
+    serde_json::to_string(&diff)
+        .map_err(|_| CommandError::serialise_failed("the snapshot diff").for_session(session_id).for_index(index))
+}
@@ -0,0 +1,21 @@
+use super::error::CommandError;
+use crate::state::StateManager;
+use crate::AppState;
+
+
+/* Stop at any breakpoint where the reference at address changed */
+#[tauri::command]
+pub fn add_watchpoint(state: tauri::State<AppState>, address: i32) -> Result<(), CommandError> {
+    state.add_watchpoint(address).map_err(CommandError::from)
+}
+
+#[tauri::command]
+pub fn list_watchpoints(state: tauri::State<AppState>) -> Result<String, CommandError> {
+    serde_json::to_string(&state.watchpoints()?)
+        .map_err(|_| CommandError::serialise_failed("the watchpoints"))
+}
+
+#[tauri::command]
+pub fn remove_watchpoint(state: tauri::State<AppState>, address: i32) -> Result<(), CommandError> {
+    state.remove_watchpoint(address).map_err(CommandError::from)
+}
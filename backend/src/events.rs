@@ -1,11 +1,21 @@
-use crate::{state::StateError, trees::DebugTree};
+use crate::server::{ServerStatus, SessionInfo};
+use crate::state::{StateError, breakpoint_queue::QueuedBreakpoint, breakpoint_timeout::TimeoutWarning, watchpoints::WatchpointHit};
+use crate::trees::{DebugTree, TreeDelta};
 
 /* Event enum representing an event fired and managed by State */
 #[derive(Debug, PartialEq)]
 pub enum Event<'a> {
     TreeReady(&'a DebugTree),   /* Tree is ready for loading in frontend */
     NewTree,                    /* New tree is sent from RemoteView */
+    TreeDelta(&'a TreeDelta),   /* Nodes were merged into a stored tree from RemoteView */
+    WatchpointHit(&'a WatchpointHit), /* Watched references changed at a breakpoint */
     SourceFile(&'a String),     /* Source file requested is sent */
+    ServerStatus(&'a ServerStatus), /* Server started, is listening or failed to launch */
+    SessionPaused(&'a SessionInfo), /* A session is waiting at a breakpoint */
+    SessionResumed(&'a SessionInfo), /* A session was continued from its breakpoint */
+    SessionClosed(&'a SessionInfo), /* A debugging session finished or its client disconnected */
+    BreakpointTimeoutWarning(&'a TimeoutWarning), /* A breakpoint will soon be continued by its timeout policy */
+    BreakpointQueued(&'a QueuedBreakpoint), /* A session paused at a breakpoint without taking focus */
 }
 
 impl Event<'_> {
@@ -15,7 +25,15 @@ impl Event<'_> {
         match self {
             Event::TreeReady(_) => "tree-ready",
             Event::NewTree => "new-tree",
-            Event::SourceFile(_) => "upload-code-file"
+            Event::TreeDelta(_) => "tree-delta",
+            Event::WatchpointHit(_) => "watchpoint-hit",
+            Event::SourceFile(_) => "upload-code-file",
+            Event::ServerStatus(_) => "server-status",
+            Event::SessionPaused(_) => "session-paused",
+            Event::SessionResumed(_) => "session-resumed",
+            Event::SessionClosed(_) => "session-closed",
+            Event::BreakpointTimeoutWarning(_) => "breakpoint-timeout-warning",
+            Event::BreakpointQueued(_) => "breakpoint-queued",
         }.to_string()
     }
 
@@ -24,7 +42,13 @@ impl Event<'_> {
         match self {
             Event::TreeReady(tree) => serde_json::to_string(tree),
             Event::NewTree => serde_json::to_string(&()),
+            Event::TreeDelta(delta) => serde_json::to_string(delta),
+            Event::WatchpointHit(hit) => serde_json::to_string(hit),
             Event::SourceFile(contents) => serde_json::to_string(contents),
+            Event::ServerStatus(status) => serde_json::to_string(status),
+            Event::SessionPaused(info) | Event::SessionResumed(info) | Event::SessionClosed(info) => serde_json::to_string(info),
+            Event::BreakpointTimeoutWarning(warning) => serde_json::to_string(warning),
+            Event::BreakpointQueued(queued) => serde_json::to_string(queued),
         }.map_err(EventError::from)
     }
 
@@ -13,6 +13,34 @@ pub fn delete_saved_trees_dir(path_to_dir: &Path) -> Result<(), FileError> {
 }
 
 
+/* Removes the Unix domain socket the server listened on, if it is still there */
+/* Any other file at the path is left alone, as it was not created by Dill */
+pub fn delete_socket(path_to_socket: &Path) -> Result<(), FileError> {
+    match fs::symlink_metadata(path_to_socket) {
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(_) => return Err(FileError::DeleteSocketFailed),
+        Ok(metadata) if !is_socket(&metadata) => return Err(FileError::NotASocket),
+        Ok(_) => (),
+    }
+
+    match fs::remove_file(path_to_socket) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(FileError::DeleteSocketFailed),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(unix)]
+fn is_socket(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    metadata.file_type().is_socket()
+}
+
+#[cfg(not(unix))]
+fn is_socket(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+
 pub fn create_saved_trees_dir(path_to_dir: &Path) -> Result<(), FileError> {
     /* If the folder for saved_trees does not exist, create it. */
     if !path_to_dir.exists() {
@@ -24,7 +52,10 @@ pub fn create_saved_trees_dir(path_to_dir: &Path) -> Result<(), FileError> {
 }
 
 #[derive (Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum FileError  {
     DeleteDirFailed,
     CreateDirFailed,
+    DeleteSocketFailed,
+    NotASocket,
 }
\ No newline at end of file
@@ -1,5 +1,6 @@
 use std::path::PathBuf;
 
+use clap::Parser;
 use state::state_manager::{BreakpointCode, DirectoryKind};
 use state::StateManager;
 use tauri::Manager;
@@ -11,13 +12,18 @@ mod commands;
 mod events;
 mod trees;
 mod files;
+mod options;
 
-use state::AppState;
-use server::ServerState;
+use state::{AppState, HeadlessState};
+use server::{ServerControl, ServerState, ServerStatus};
+use events::Event;
+
+pub use state::BreakpointPolicy;
+pub use options::Options;
 
 
 /* Setup Tauri app */
-fn setup(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>> {
+fn setup(app: &mut tauri::App, mut options: Options) -> Result<(), Box<dyn std::error::Error>> {
     if cfg!(debug_assertions) {
         app.handle().plugin(
             tauri_plugin_log::Builder::default()
@@ -26,36 +32,64 @@ fn setup(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>> {
         )?;
     }
 
+    /* Read or generate the token remote clients must send, if authentication is enabled */
+    server::resolve_token(&mut options)?;
+
     /* Manage the app state using Tauri */
-    let app_state: AppState = AppState::new(app.app_handle().clone());
+    let app_state: AppState = AppState::new(app.app_handle().clone(), &options);
     let path_to_saved_trees: PathBuf = app_state.system_path(DirectoryKind::SavedTrees).map_err(|_| tauri::Error::UnknownPath)?;
 
     app.manage(app_state);
 
     files::create_saved_trees_dir(&path_to_saved_trees).map_err(|_| tauri::Error::UnknownPath)?;
     
+    /* Report the server's status to the frontend as it starts, listens or fails */
+    let handle: tauri::AppHandle = app.handle().clone();
+    let control: ServerControl = ServerControl::new(options, move |status| {
+        if handle.emit(Event::ServerStatus(status)).is_err() {
+            log::warn!("Could not emit server status {status:?}");
+        }
+    });
+
+    app.manage(control.clone());
+    spawn_server(app.handle(), control);
+
+    Ok(())
+}
+
+/* Mount the Rocket server to the running instance of Tauri, once any previous server has stopped */
+fn spawn_server(app: &tauri::AppHandle, control: ServerControl) {
     /* Clone the app handle and use to create a ServerState */
-    let server_state: ServerState = ServerState::new(app.handle().clone());
+    let server_state: ServerState = ServerState::new(app.clone());
 
-    /* Mount the Rocket server to the running instance of Tauri */
     tauri::async_runtime::spawn(async move {
-        server::launch(server_state)
-            .await
-            .expect("Rocket failed to initialise")
+        /* Failures are reported through the server-status event */
+        let _ = server::launch(server_state, &control).await;
     });
-
-    Ok(())
 }
 
-/* Run the Tauri app */
+/* Run the Tauri app, or only the server if launched with --headless */
 pub fn run() {
+    let options: Options = Options::parse();
+
+    if options.headless {
+        if let Err(err) = run_headless(options) {
+            eprintln!("Dill server failed: {err}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    /* A saved trees directory chosen by the user is kept on exit */
+    let keep_saved_trees: bool = options.saved_trees_dir.is_some();
+
     /* Fix for NVidia graphics cards */
     std::env::set_var("WEBKIT_DISABLE_DMABUF_RENDERER", "1");
 
     /* Build app using Tauri builders */
     let app = tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())     /* Allow shell access from app */
-        .setup(setup)                           /* Run app setup */
+        .setup(move |app| setup(app, options))  /* Run app setup */
         .invoke_handler(commands::handlers())   /* Expose Tauri commands to frontend */
         .build(tauri::generate_context!())      /* Build the app */
         .expect("Error building Dill");
@@ -71,12 +105,54 @@ pub fn run() {
                 handle.transmit_breakpoint_skips(session_id, BreakpointCode::Terminate).expect("Error occured when terminating debugging");
             }
 
-            let path_to_saved_trees: PathBuf = handle.system_path(DirectoryKind::SavedTrees).expect("Error occured whilst trying to find saved trees");
-            files::delete_saved_trees_dir(&path_to_saved_trees).expect("Error occured cleaning saved trees");
+            if !keep_saved_trees {
+                let path_to_saved_trees: PathBuf = handle.system_path(DirectoryKind::SavedTrees).expect("Error occured whilst trying to find saved trees");
+                files::delete_saved_trees_dir(&path_to_saved_trees).expect("Error occured cleaning saved trees");
+            }
+
+            /* Remove the socket now, as the server may not get to shut down before the process exits */
+            if let Some(socket) = handle.state::<ServerControl>().options().socket {
+                /* A file that is not a socket was never bound to, so is left where it is */
+                match files::delete_socket(&socket) {
+                    Err(files::FileError::NotASocket) => (),
+                    removed => removed.expect("Error occured removing the server socket"),
+                }
+            }
         }
     })
 }
 
+/* Run the Rocket server alone, writing received trees to a directory instead of showing them */
+pub fn run_headless(mut options: Options) -> Result<(), Box<dyn std::error::Error>> {
+    server::resolve_token(&mut options)?;
+
+    let trees_dir: PathBuf = options.saved_trees_dir.clone().unwrap_or_else(|| PathBuf::from(files::SAVED_TREE_DIR));
+    files::create_saved_trees_dir(&trees_dir).map_err(|err| format!("Could not create {}: {err:?}", trees_dir.display()))?;
+
+    let headless_state: HeadlessState = HeadlessState::new(trees_dir);
+    let policy: BreakpointPolicy = options.policy;
+
+    /* With no frontend to wait for, the opened files are imported straight away */
+    for file_path in &options.open {
+        let contents: String = std::fs::read_to_string(file_path).map_err(|err| format!("Could not open {}: {err}", file_path.display()))?;
+        commands::save::import_contents(&contents, &headless_state).map_err(|err| format!("Could not import {}: {err}", file_path.display()))?;
+    }
+
+    /* With no frontend, tell whoever started the server where to find it */
+    let control: ServerControl = ServerControl::new(options, |status| {
+        if let ServerStatus::Listening { address, port, socket } = status {
+            println!("Dill server listening on {address}:{port}");
+            if let Some(socket) = socket {
+                println!("Dill server listening on {}", socket.display());
+            }
+        }
+    });
+
+    /* Without Tauri there is no async runtime, so let Rocket start its own */
+    rocket::execute(server::launch(ServerState::headless(headless_state, policy), &control))?;
+    Ok(())
+}
+
 
 #[cfg(test)]
 mod test {
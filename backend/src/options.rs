@@ -0,0 +1,173 @@
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+use clap::Parser;
+use rocket::data::ByteUnit;
+
+use crate::state::BreakpointPolicy;
+use crate::state::breakpoint_timeout::TimeoutAction;
+use crate::state::breakpoint_queue::FocusPolicy;
+
+
+/* Options Dill is launched with, each of which can also be set with a DILL_* environment variable */
+#[derive(Debug, Clone, Default, Parser)]
+#[command(name = "dill", version, about = "A debugging UI for parsley-debug")]
+pub struct Options {
+    #[arg(long, env = "DILL_PORT", help = "Port the server listens on, overriding Rocket.toml")]
+    pub port: Option<u16>,
+
+    #[arg(long, env = "DILL_ADDRESS", help = "Address the server binds to, overriding Rocket.toml")]
+    pub address: Option<IpAddr>,
+
+    #[arg(long, env = "DILL_PORT_FALLBACK", help = "Use the next free port if the port is already in use")]
+    pub port_fallback: bool,
+
+    #[arg(long, env = "DILL_DISCOVERY_FILE", help = "File the address and port being listened on are written to [default: dill-server-<pid>.json in Dill's data directory]")]
+    pub discovery_file: Option<PathBuf>,
+
+    #[arg(long, value_parser = parse_byte_unit, env = "DILL_BODY_LIMIT", help = "Largest tree the server accepts, as sent and once decompressed, e.g. 256MiB [default: Rocket.toml's tree limit]")]
+    pub body_limit: Option<ByteUnit>,
+
+    #[arg(long, value_name = "SECONDS", env = "DILL_UPLOAD_EXPIRY", help = "Time after its last chunk that an abandoned chunked upload is discarded [default: 300]")]
+    pub upload_expiry: Option<u64>,
+
+    #[arg(long, value_name = "SECONDS", env = "DILL_HEARTBEAT_TIMEOUT", help = "Time a client that sends heartbeats may go without one before it is taken to have disconnected [default: 15]")]
+    pub heartbeat_timeout: Option<u64>,
+
+    #[arg(long, env = "DILL_SOCKET", help = "Unix domain socket the server also listens on, removed on exit")]
+    pub socket: Option<PathBuf>,
+
+    #[arg(long, requires = "socket", env = "DILL_SOCKET_ONLY", help = "Route clients through the socket, moving the TCP listener to a free loopback port that local processes can still reach")]
+    pub socket_only: bool,
+
+    #[arg(long, env = "DILL_AUTH", help = "Require remote clients to send a token, read from or written to the token file")]
+    pub auth: bool,
+
+    #[arg(long, env = "DILL_TOKEN", hide_env_values = true, help = "Token remote clients must send, enabling --auth")]
+    pub token: Option<String>,
+
+    #[arg(long, env = "DILL_TOKEN_FILE", help = "File the token is read from and written to [default: dill-token in Dill's data directory]")]
+    pub token_file: Option<PathBuf>,
+
+    #[arg(long, num_args = 1.., value_delimiter = ',', env = "DILL_OPEN", help = "Saved trees to import on startup")]
+    pub open: Vec<PathBuf>,
+
+    #[arg(long, env = "DILL_SAVED_TREES_DIR", help = "Directory trees are saved to, whose files are kept across runs unlike the default temporary directory")]
+    pub saved_trees_dir: Option<PathBuf>,
+
+    #[arg(long, env = "DILL_HEADLESS", help = "Run the server without a window, writing received trees to the saved trees directory")]
+    pub headless: bool,
+
+    #[arg(long, default_value = "skip-all", env = "DILL_POLICY", help = "Action taken at every breakpoint when running headless: step, skip-all or terminate")]
+    pub policy: BreakpointPolicy,
+
+    #[arg(long, value_name = "SECONDS", env = "DILL_BREAKPOINT_TIMEOUT", help = "Time a breakpoint is waited at before the timeout action is applied [default: wait forever]")]
+    pub breakpoint_timeout: Option<u64>,
+
+    #[arg(long, default_value = "continue", env = "DILL_TIMEOUT_ACTION", help = "Action applied to a breakpoint that timed out: continue, skip-all or terminate")]
+    pub timeout_action: TimeoutAction,
+
+    #[arg(long, default_value = "most-recent", env = "DILL_FOCUS_POLICY", help = "Which of several paused sessions is shown: most-recent, first-come or never")]
+    pub focus_policy: FocusPolicy,
+
+    #[arg(long, env = "DILL_MAX_SNAPSHOTS", help = "Most breakpoint snapshots kept per session to look back through, the oldest are dropped past it [default: 64]")]
+    pub max_snapshots: Option<usize>,
+}
+
+/* ByteUnit's parse error is not a std::error::Error, so clap cannot use its FromStr directly */
+fn parse_byte_unit(size: &str) -> Result<ByteUnit, String> {
+    size.parse().map_err(|err| format!("{err}"))
+}
+
+
+#[cfg(test)]
+mod test {
+
+    /* Options unit testing */
+
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::path::PathBuf;
+    use std::sync::{Mutex, MutexGuard, PoisonError};
+
+    use clap::Parser;
+    use rocket::data::ByteUnit;
+
+    use super::Options;
+    use crate::state::BreakpointPolicy;
+    use crate::state::breakpoint_timeout::TimeoutAction;
+    use crate::state::breakpoint_queue::FocusPolicy;
+
+    /* Options are read from the environment too, so tests that set it must not run alongside a parse */
+    static ENV: Mutex<()> = Mutex::new(());
+
+    fn env_lock() -> MutexGuard<'static, ()> {
+        ENV.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn parse<const N: usize>(args: [&str; N]) -> Result<Options, clap::Error> {
+        let _env = env_lock();
+        Options::try_parse_from(args)
+    }
+
+    #[test]
+    fn options_parse_from_args() {
+        let options: Options = parse([
+            "dill", "--port", "8000", "--address", "0.0.0.0", "--open", "a.json", "b.json", "--headless", "--policy", "step",
+            "--focus-policy", "first-come",
+        ]).expect("Options should parse");
+
+        assert_eq!(options.port, Some(8000));
+        assert_eq!(options.address, Some(IpAddr::V4(Ipv4Addr::UNSPECIFIED)));
+        assert_eq!(options.open, vec![PathBuf::from("a.json"), PathBuf::from("b.json")]);
+        assert_eq!(options.saved_trees_dir, None);
+        assert!(options.headless);
+        assert_eq!(options.policy, BreakpointPolicy::Step);
+        assert_eq!(options.focus_policy, FocusPolicy::FirstCome);
+    }
+
+    #[test]
+    fn options_read_from_env() {
+        let options: Options = {
+            let _env = env_lock();
+
+            std::env::set_var("DILL_SAVED_TREES_DIR", "/tmp/dill-trees");
+            let parsed = Options::try_parse_from(["dill"]);
+            std::env::remove_var("DILL_SAVED_TREES_DIR");
+
+            parsed.expect("Options should parse")
+        };
+
+        assert_eq!(options.saved_trees_dir, Some(PathBuf::from("/tmp/dill-trees")));
+    }
+
+    #[test]
+    fn socket_only_requires_socket() {
+        assert!(parse(["dill", "--socket-only"]).is_err());
+
+        let options: Options = parse(["dill", "--socket", "/tmp/dill.sock", "--socket-only"]).expect("Options should parse");
+        assert_eq!(options.socket, Some(PathBuf::from("/tmp/dill.sock")));
+        assert!(options.socket_only);
+    }
+
+    #[test]
+    fn body_limit_parses_units() {
+        let options: Options = parse(["dill", "--body-limit", "256MiB"]).expect("Options should parse");
+        assert_eq!(options.body_limit, Some(ByteUnit::Mebibyte(256)));
+
+        assert!(parse(["dill", "--body-limit", "large"]).is_err());
+    }
+
+    #[test]
+    fn breakpoint_timeout_parses_with_action() {
+        let options: Options = parse(["dill"]).expect("Options should parse");
+        assert_eq!((options.breakpoint_timeout, options.timeout_action), (None, TimeoutAction::Continue));
+
+        let options: Options = parse(["dill", "--breakpoint-timeout", "30", "--timeout-action", "terminate"]).expect("Options should parse");
+        assert_eq!((options.breakpoint_timeout, options.timeout_action), (Some(30), TimeoutAction::Terminate));
+    }
+
+    #[test]
+    fn invalid_port_is_rejected() {
+        assert!(parse(["dill", "--port", "port"]).is_err());
+    }
+}
@@ -1,9 +1,21 @@
+mod auth;
+mod data;
+mod error;
 mod launch;
 mod request;
 mod server_state;
-
+mod sessions;
+mod status;
+mod upload;
+#[cfg(unix)]
+mod unix_socket;
+mod websocket;
+
+pub use auth::resolve_token;
 pub use launch::launch;
 pub use server_state::{ServerState, SkipsSender};
+pub use sessions::SessionInfo;
+pub use status::{ServerControl, ServerStatus};
 
 pub type TokioMutex<T> = rocket::tokio::sync::Mutex<T>;
 
@@ -15,8 +27,10 @@ pub mod test {
 
     use super::{launch, ServerState};
     use crate::events::Event;
+    use crate::options::Options;
     use crate::state::MockStateManager;
     use crate::trees::{debug_tree, parsley_tree};
+    use crate::trees::session_id::test::id;
 
     /* Server integration testing */
 
@@ -24,8 +38,23 @@ pub mod test {
     /* Start a blocking, tracked client for rocket
     The mock should already be set with expectations */
     pub fn tracked_client(mock: MockStateManager) -> blocking::Client {
+        tracked_client_with(mock, &Options::default())
+    }
+
+    /* Start a blocking, tracked client for rocket launched with the given options */
+    pub fn tracked_client_with(mock: MockStateManager, options: &Options) -> blocking::Client {
         let state = ServerState::new(mock);
-        blocking::Client::tracked(launch::build(state)).expect("Could not launch rocket")
+        blocking::Client::tracked(launch::build(state, options)).expect("Could not launch rocket")
+    }
+
+    /* Mock accepting a single posted tree */
+    pub fn posting_mock() -> MockStateManager {
+        let mut mock = MockStateManager::new();
+        mock.expect_next_session_id().returning(|| Ok(id(0)));
+        mock.expect_set_tree().with(predicate::eq(debug_tree::test::tree())).times(1).returning(|_| Ok(()));
+        mock.expect_update_tree().returning(|_| Ok(()));
+        mock.expect_emit().returning(|_| Ok(()));
+        mock
     }
 
     #[test]
@@ -42,13 +71,17 @@ pub mod test {
         mock.expect_emit().withf(|expected| &Event::NewTree == expected)
             .returning(|_| Ok(()));
 
+        mock.expect_displayed_session_id()
+            .times(NUM_REPEATS)
+            .returning(|| Ok(id(0)));
+
         mock.expect_get_tree()
             .times(NUM_REPEATS)
-            .returning(|| Ok(debug_tree::test::tree()));
+            .returning(|_| Ok(debug_tree::test::tree()));
 
-        mock.expect_next_session_id().returning(|| Ok(-1));
+        mock.expect_next_session_id().returning(|| Ok(id(0)));
 
-        mock.expect_update_tree().times(NUM_REPEATS).returning(|_, _| Ok(()));
+        mock.expect_update_tree().times(NUM_REPEATS).returning(|_| Ok(()));
 
         let client: blocking::Client = tracked_client(mock);
 
@@ -0,0 +1,255 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::{catch, Request};
+
+use super::error::{ApiError, ErrorCode};
+use crate::options::Options;
+
+
+/* Secret remote clients must send when authentication is enabled, managed by Rocket */
+pub struct ApiToken(pub Option<String>);
+
+/* Request guard passing only requests with the secret, or every request when authentication is off */
+pub struct Authorised;
+
+#[derive(Debug)]
+pub enum AuthError {
+    MissingToken,
+    InvalidToken,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Authorised {
+    type Error = AuthError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(ApiToken(Some(expected))) = req.rocket().state::<ApiToken>() else {
+            return Outcome::Success(Authorised);
+        };
+
+        match req.headers().get_one("Authorization").and_then(|value| value.strip_prefix("Bearer ")) {
+            None => Outcome::Error((Status::Unauthorized, AuthError::MissingToken)),
+            Some(token) if tokens_match(token.as_bytes(), expected.as_bytes()) => Outcome::Success(Authorised),
+            Some(_) => Outcome::Error((Status::Unauthorized, AuthError::InvalidToken)),
+        }
+    }
+}
+
+/* Compare every byte, so the time taken does not reveal how much of the token was right */
+pub(super) fn tokens_match(given: &[u8], expected: &[u8]) -> bool {
+    given.len() == expected.len() && given.iter().zip(expected).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+
+#[catch(401)]
+pub fn unauthorised() -> ApiError {
+    ApiError::new(ErrorCode::Unauthorised, "Send the Dill token as 'Authorization: Bearer <token>', see the token file or Dill's settings")
+}
+
+
+/* Directory Dill keeps its data in, named after the app's identifier as Tauri's data directory is */
+pub(super) const APP_IDENTIFIER: &str = "com.dill.dev";
+
+/* Path of the file the token is read from and written to */
+/* The default is in the user's own data directory, where other users cannot place or read it */
+fn token_path(options: &Options) -> io::Result<PathBuf> {
+    if let Some(path) = &options.token_file {
+        return Ok(path.clone());
+    }
+
+    let dir: PathBuf = dirs::data_dir()
+        .map(|dir| dir.join(APP_IDENTIFIER))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Could not find a data directory for the token, set --token-file"))?;
+
+    create_private_dir(&dir)?;
+    Ok(dir.join("dill-token"))
+}
+
+/* Fill in the token when authentication is enabled: the given token, the token file's, or a new one */
+/* The token is always written to the token file, so local clients can read it */
+pub fn resolve_token(options: &mut Options) -> io::Result<()> {
+    if !options.auth && options.token.is_none() {
+        return Ok(());
+    }
+
+    let path: PathBuf = token_path(options)?;
+    check_owner(&path)?;
+
+    let token: String = match options.token.take() {
+        Some(token) => token,
+        None => fs::read_to_string(&path).ok()
+            .map(|contents| contents.trim().to_string())
+            .filter(|token| !token.is_empty())
+            .unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string()),
+    };
+
+    create_private_file(&path)?.write_all(token.as_bytes())?;
+
+    options.token = Some(token);
+    Ok(())
+}
+
+/* A token file another user owns could have been planted to hand out a token they know */
+#[cfg(unix)]
+fn check_owner(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata: fs::Metadata = match fs::symlink_metadata(path) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        metadata => metadata?,
+    };
+
+    /* Safety: geteuid has no preconditions and cannot fail */
+    let user: u32 = unsafe { libc::geteuid() };
+
+    match metadata.uid() == user && metadata.is_file() {
+        true => Ok(()),
+        false => Err(io::Error::new(io::ErrorKind::PermissionDenied, format!("Token file {} is not a file owned by this user", path.display()))),
+    }
+}
+
+#[cfg(not(unix))]
+fn check_owner(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/* Only the user running Dill should be able to read the token */
+#[cfg(unix)]
+fn create_private_file(path: &Path) -> io::Result<fs::File> {
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+    let file: fs::File = fs::OpenOptions::new().write(true).create(true).truncate(true).mode(0o600).open(path)?;
+
+    /* The mode only applies to a new file, so an existing one is restricted too */
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    Ok(file)
+}
+
+#[cfg(not(unix))]
+fn create_private_file(path: &Path) -> io::Result<fs::File> {
+    fs::File::create(path)
+}
+
+#[cfg(unix)]
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)
+}
+
+
+#[cfg(test)]
+mod test {
+
+    /* Authentication unit testing */
+
+    use std::path::PathBuf;
+
+    use rocket::http::{self, Header};
+    use rocket::local::blocking;
+
+    use super::resolve_token;
+    use crate::options::Options;
+    use crate::server::test::tracked_client_with;
+    use crate::state::MockStateManager;
+    use crate::trees::session_id::test::id;
+
+    fn client_with_token(mock: MockStateManager) -> blocking::Client {
+        tracked_client_with(mock, &Options { token: Some(String::from("secret")), ..Options::default() })
+    }
+
+    #[test]
+    fn request_without_token_is_rejected() {
+        let client: blocking::Client = client_with_token(MockStateManager::new());
+
+        let response = client.post("/api/remote/newSession")
+            .header(http::ContentType::JSON)
+            .dispatch();
+
+        assert_eq!(response.status(), http::Status::Unauthorized);
+
+        let body: serde_json::Value = response.into_json().expect("Rejection should be JSON");
+        assert_eq!(body["code"], "unauthorised");
+    }
+
+    #[test]
+    fn request_with_wrong_token_is_rejected() {
+        let client: blocking::Client = client_with_token(MockStateManager::new());
+
+        let response = client.post("/api/remote/newSession")
+            .header(http::ContentType::JSON)
+            .header(Header::new("Authorization", "Bearer guess"))
+            .dispatch();
+
+        assert_eq!(response.status(), http::Status::Unauthorized);
+    }
+
+    #[test]
+    fn request_with_token_is_accepted() {
+        let mut mock = MockStateManager::new();
+        mock.expect_next_session_id().returning(|| Ok(id(0)));
+
+        let client: blocking::Client = client_with_token(mock);
+
+        let response = client.post("/api/remote/newSession")
+            .header(http::ContentType::JSON)
+            .header(Header::new("Authorization", "Bearer secret"))
+            .dispatch();
+
+        assert_eq!(response.status(), http::Status::Ok);
+    }
+
+    #[test]
+    fn generated_token_is_written_and_reused() {
+        let path: PathBuf = std::env::temp_dir().join(format!("dill-token-test-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut options = Options { auth: true, token_file: Some(path.clone()), ..Options::default() };
+        resolve_token(&mut options).expect("Token should be written");
+
+        let token: String = options.token.clone().expect("Token should be generated");
+        assert_eq!(std::fs::read_to_string(&path).ok(), Some(token.clone()));
+
+        /* A later launch reads the same token back, so clients stay configured */
+        let mut relaunched = Options { auth: true, token_file: Some(path.clone()), ..Options::default() };
+        resolve_token(&mut relaunched).expect("Token should be read");
+        assert_eq!(relaunched.token, Some(token));
+
+        std::fs::remove_file(path).expect("Could not remove token file");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn token_file_is_private_and_not_followed() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path: PathBuf = std::env::temp_dir().join(format!("dill-token-private-{}", std::process::id()));
+        std::fs::write(&path, "old").expect("Could not write token file");
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).expect("Could not open up token file");
+
+        let mut options = Options { auth: true, token_file: Some(path.clone()), ..Options::default() };
+        resolve_token(&mut options).expect("Token should be written");
+
+        let mode: u32 = std::fs::metadata(&path).expect("Token file should exist").permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        /* A link could point the token anywhere, so it is refused */
+        let link: PathBuf = path.with_extension("link");
+        let _ = std::fs::remove_file(&link);
+        std::os::unix::fs::symlink(&path, &link).expect("Could not link token file");
+
+        let mut linked = Options { auth: true, token_file: Some(link.clone()), ..Options::default() };
+        assert_eq!(resolve_token(&mut linked).map_err(|err| err.kind()), Err(std::io::ErrorKind::PermissionDenied));
+
+        std::fs::remove_file(link).expect("Could not remove link");
+        std::fs::remove_file(path).expect("Could not remove token file");
+    }
+}
@@ -0,0 +1,325 @@
+use std::io::Read;
+
+use rocket::data::{self, ByteUnit, Data, FromData, Limits};
+use rocket::http::ContentType;
+use rocket::outcome::Outcome;
+use rocket::Request;
+use serde::de::DeserializeOwned;
+
+use super::error::ApiError;
+use crate::trees::deep_json;
+
+
+/* Name of the limit on tree bodies, applied both as sent and once decompressed */
+pub const TREE_LIMIT: &str = "tree";
+
+/* Deepest nesting rmp_serde decodes, as it counts depth in a u16, unlike JSON which may nest to any depth */
+/* Each level of a tree is two levels of MessagePack, its node and its children */
+pub const MSGPACK_MAX_DEPTH: usize = u16::MAX as usize;
+
+/* Data guard for posted trees, which may be JSON or MessagePack, compressed with gzip or zstd */
+/* Like rocket's Json, but accepts documents of any nesting depth */
+pub struct TreeBody<T>(pub T);
+
+impl<T> TreeBody<T> {
+    /* Consume the guard, returning the wrapped value */
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TreeBodyError {
+    ReadFailed,                     /* The request body could not be read */
+    TooLarge,                       /* The request body, or its decompressed contents, exceeds the tree limit */
+    UnsupportedEncoding,            /* The Content-Encoding is not gzip or zstd */
+    UnsupportedFormat,              /* The Content-Type is not JSON or MessagePack */
+    DecompressFailed,               /* The request body is not valid for its Content-Encoding */
+    ParseFailed,                    /* The request body is not valid for the type */
+    TooDeep,                        /* The MessagePack body nests deeper than MSGPACK_MAX_DEPTH */
+}
+
+/* Compression applied to a request body */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum BodyEncoding {
+    Identity,
+    Gzip,
+    Zstd,
+}
+
+impl BodyEncoding {
+    fn from_request(req: &Request<'_>) -> Option<BodyEncoding> {
+        BodyEncoding::from_name(req.headers().get_one("Content-Encoding"))
+    }
+
+    /* Encoding named by a Content-Encoding, where none means the body is uncompressed */
+    pub(super) fn from_name(name: Option<&str>) -> Option<BodyEncoding> {
+        match name.map(str::trim) {
+            None | Some("identity") => Some(BodyEncoding::Identity),
+            Some("gzip" | "x-gzip") => Some(BodyEncoding::Gzip),
+            Some("zstd") => Some(BodyEncoding::Zstd),
+            Some(_) => None,
+        }
+    }
+
+    /* Decompress a body, failing with TooLarge as soon as it grows past the limit */
+    fn decode(self, body: Vec<u8>, limit: ByteUnit) -> Result<Vec<u8>, TreeBodyError> {
+        let decoder: Box<dyn Read> = match self {
+            BodyEncoding::Identity => return Ok(body),
+            BodyEncoding::Gzip => Box::new(flate2::read::GzDecoder::new(body.as_slice())),
+            BodyEncoding::Zstd => Box::new(zstd::Decoder::new(body.as_slice()).map_err(|_| TreeBodyError::DecompressFailed)?),
+        };
+
+        /* Read one byte past the limit, to tell a body exactly at the limit from one over it */
+        let mut decoded: Vec<u8> = Vec::new();
+        decoder.take(limit.as_u64().saturating_add(1)).read_to_end(&mut decoded).map_err(|_| TreeBodyError::DecompressFailed)?;
+
+        match decoded.len() as u64 > limit.as_u64() {
+            true => Err(TreeBodyError::TooLarge),
+            false => Ok(decoded),
+        }
+    }
+}
+
+/* Serialisation of a request body */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum BodyFormat {
+    Json,
+    MessagePack,
+}
+
+impl BodyFormat {
+    fn from_request(req: &Request<'_>) -> Option<BodyFormat> {
+        BodyFormat::from_content_type(req.content_type())
+    }
+
+    pub(super) fn from_content_type(content_type: Option<&ContentType>) -> Option<BodyFormat> {
+        match content_type {
+            Some(content_type) if content_type.is_json() => Some(BodyFormat::Json),
+            Some(content_type) if content_type.is_msgpack() || is_msgpack_alias(content_type) => Some(BodyFormat::MessagePack),
+            _ => None,
+        }
+    }
+
+    /* Deep trees are deserialised on a growable stack, so must be run on a blocking thread */
+    fn parse<T: DeserializeOwned>(self, body: &[u8]) -> Result<T, TreeBodyError> {
+        match self {
+            BodyFormat::Json => std::str::from_utf8(body).ok()
+                .and_then(|json| deep_json::from_str(json).ok())
+                .ok_or(TreeBodyError::ParseFailed),
+            BodyFormat::MessagePack => {
+                /* Lift the default limit as far as rmp_serde allows */
+                let mut deserializer = rmp_serde::Deserializer::from_read_ref(body);
+                deserializer.set_max_depth(MSGPACK_MAX_DEPTH);
+
+                T::deserialize(serde_stacker::Deserializer::new(&mut deserializer)).map_err(|err| match err {
+                    rmp_serde::decode::Error::DepthLimitExceeded => TreeBodyError::TooDeep,
+                    _ => TreeBodyError::ParseFailed,
+                })
+            },
+        }
+    }
+}
+
+/* MessagePack has no registered media type, so clients also send these */
+fn is_msgpack_alias(content_type: &ContentType) -> bool {
+    content_type.top() == "application" && (content_type.sub() == "x-msgpack" || content_type.sub() == "vnd.msgpack")
+}
+
+/* Limit on tree bodies, defaulting to the JSON limit if none is configured */
+pub(super) fn tree_limit(limits: &Limits) -> ByteUnit {
+    limits.get(TREE_LIMIT)
+        .or_else(|| limits.get("json"))
+        .unwrap_or(Limits::JSON)
+}
+
+/* Decompress and deserialise a body, on a blocking thread as trees may be deep */
+pub(super) async fn decode_body<T: DeserializeOwned + Send + 'static>(format: BodyFormat, encoding: BodyEncoding, body: Vec<u8>, limit: ByteUnit) -> Result<T, TreeBodyError> {
+    rocket::tokio::task::spawn_blocking(move || encoding.decode(body, limit).and_then(|body| format.parse::<T>(&body)))
+        .await
+        .unwrap_or(Err(TreeBodyError::ReadFailed))
+}
+
+/* Why a request's tree body was rejected, cached on the request for the error catchers */
+struct Rejection(Option<TreeBodyError>);
+
+/* Reject a tree body, recording why for the error catchers */
+fn reject<'r, T>(req: &'r Request<'_>, err: TreeBodyError) -> data::Outcome<'r, T, TreeBodyError> {
+    req.local_cache(|| Rejection(Some(err)));
+    Outcome::Error((ApiError::from(err).code().status(), err))
+}
+
+/* Why the request's tree body was rejected, if it was */
+pub(super) fn rejection(req: &Request<'_>) -> Option<TreeBodyError> {
+    req.local_cache(|| Rejection(None)).0
+}
+
+/* Read a whole body, failing with TooLarge if it exceeds the limit */
+pub(super) async fn read_limited(data: Data<'_>, limit: ByteUnit) -> Result<Vec<u8>, TreeBodyError> {
+    match data.open(limit).into_bytes().await {
+        Ok(body) if body.is_complete() => Ok(body.into_inner()),
+        Ok(_) => Err(TreeBodyError::TooLarge),
+        Err(_) => Err(TreeBodyError::ReadFailed),
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, T: DeserializeOwned + Send + 'static> FromData<'r> for TreeBody<T> {
+    type Error = TreeBodyError;
+
+    async fn from_data(req: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let Some(format) = BodyFormat::from_request(req) else {
+            return reject(req, TreeBodyError::UnsupportedFormat);
+        };
+        let Some(encoding) = BodyEncoding::from_request(req) else {
+            return reject(req, TreeBodyError::UnsupportedEncoding);
+        };
+
+        let limit: ByteUnit = tree_limit(req.limits());
+        let body: Vec<u8> = match read_limited(data, limit).await {
+            Ok(body) => body,
+            Err(err) => return reject(req, err),
+        };
+
+        match decode_body(format, encoding, body, limit).await {
+            Ok(value) => Outcome::Success(TreeBody(value)),
+            Err(err) => reject(req, err),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+
+    /* Tree body unit testing */
+
+    use std::io::Write;
+
+    use rocket::data::ByteUnit;
+    use rocket::http::{self, Header};
+    use rocket::local::blocking;
+
+    use super::MSGPACK_MAX_DEPTH;
+    use crate::options::Options;
+    use crate::server::test::{posting_mock, tracked_client, tracked_client_with};
+    use crate::state::MockStateManager;
+    use crate::trees::parsley_tree;
+
+    fn gzip(body: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(body).expect("Could not compress body");
+        encoder.finish().expect("Could not compress body")
+    }
+
+    fn msgpack(json: &str) -> Vec<u8> {
+        let value: serde_json::Value = serde_json::from_str(json).expect("Invalid test JSON");
+        rmp_serde::to_vec_named(&value).expect("Could not encode MessagePack")
+    }
+
+    fn post(client: &blocking::Client, content_type: http::ContentType, encoding: Option<&'static str>, body: Vec<u8>) -> http::Status {
+        let mut request = client.post("/api/remote/tree").header(content_type).body(body);
+        if let Some(encoding) = encoding {
+            request = request.header(Header::new("Content-Encoding", encoding));
+        }
+
+        request.dispatch().status()
+    }
+
+    #[test]
+    fn gzip_json_post_succeeds() {
+        let client: blocking::Client = tracked_client(posting_mock());
+        let body: Vec<u8> = gzip(parsley_tree::test::json().as_bytes());
+
+        assert_eq!(post(&client, http::ContentType::JSON, Some("gzip"), body), http::Status::Ok);
+    }
+
+    #[test]
+    fn zstd_msgpack_post_succeeds() {
+        let client: blocking::Client = tracked_client(posting_mock());
+        let body: Vec<u8> = zstd::encode_all(msgpack(&parsley_tree::test::json()).as_slice(), 0).expect("Could not compress body");
+
+        assert_eq!(post(&client, http::ContentType::MsgPack, Some("zstd"), body), http::Status::Ok);
+    }
+
+    #[test]
+    fn msgpack_alias_post_succeeds() {
+        let client: blocking::Client = tracked_client(posting_mock());
+        let content_type = http::ContentType::new("application", "x-msgpack");
+
+        assert_eq!(post(&client, content_type, None, msgpack(&parsley_tree::test::json())), http::Status::Ok);
+    }
+
+    /* MessagePack for a tree whose root is nested this many children deep */
+    fn deep_msgpack(depth: usize) -> Vec<u8> {
+        let mut body: Vec<u8> = vec![0x81, 0xa4];
+        body.extend_from_slice(b"root");
+
+        /* Each node is a map holding only its one child */
+        for _ in 0..depth {
+            body.push(0x81);
+            body.push(0xa8);
+            body.extend_from_slice(b"children");
+            body.push(0x91);
+        }
+
+        body.push(0x80);
+        body
+    }
+
+    #[test]
+    fn too_deep_msgpack_names_limit() {
+        let client: blocking::Client = tracked_client(MockStateManager::new());
+        let response = client.post("/api/remote/tree").header(http::ContentType::MsgPack).body(deep_msgpack(MSGPACK_MAX_DEPTH)).dispatch();
+
+        assert_eq!(response.status(), http::Status::UnprocessableEntity);
+
+        let error: serde_json::Value = response.into_json().expect("Error should be JSON");
+        assert_eq!(error["details"]["maxDepth"], MSGPACK_MAX_DEPTH);
+    }
+
+    #[test]
+    fn unknown_encoding_is_unsupported() {
+        let client: blocking::Client = tracked_client(MockStateManager::new());
+        let body: Vec<u8> = parsley_tree::test::json().into_bytes();
+
+        assert_eq!(post(&client, http::ContentType::JSON, Some("br"), body), http::Status::UnsupportedMediaType);
+    }
+
+    #[test]
+    fn corrupt_compressed_body_is_rejected() {
+        let client: blocking::Client = tracked_client(MockStateManager::new());
+        let body: Vec<u8> = parsley_tree::test::json().into_bytes();
+
+        assert_eq!(post(&client, http::ContentType::JSON, Some("gzip"), body), http::Status::BadRequest);
+    }
+
+    #[test]
+    fn oversized_post_explains_limit() {
+        let options = Options { body_limit: Some(ByteUnit::Byte(64)), ..Options::default() };
+        let client: blocking::Client = tracked_client_with(MockStateManager::new(), &options);
+
+        let response = client.post("/api/remote/tree")
+            .header(http::ContentType::JSON)
+            .body(parsley_tree::test::json())
+            .dispatch();
+
+        assert_eq!(response.status(), http::Status::PayloadTooLarge);
+
+        let body: serde_json::Value = response.into_json().expect("Rejection should be JSON");
+        assert_eq!(body["code"], "payloadTooLarge");
+        assert_eq!(body["details"]["limit"], 64);
+    }
+
+    #[test]
+    fn oversized_decompressed_post_is_rejected() {
+        let options = Options { body_limit: Some(ByteUnit::Kibibyte(1)), ..Options::default() };
+        let client: blocking::Client = tracked_client_with(MockStateManager::new(), &options);
+
+        /* Compresses to well under the limit, but expands far beyond it */
+        let body: Vec<u8> = gzip(format!("[{}]", "0,".repeat(100_000) + "0").as_bytes());
+        assert!(body.len() < 1024);
+
+        assert_eq!(post(&client, http::ContentType::JSON, Some("gzip"), body), http::Status::PayloadTooLarge);
+    }
+}
@@ -0,0 +1,258 @@
+use rocket::http::Status;
+use rocket::response::{self, Responder};
+use rocket::serde::json::Json;
+use rocket::{catch, Request};
+
+use super::data::{self, TreeBodyError};
+use crate::state::StateError;
+use crate::state::state_manager::UpdateTreeError;
+use crate::trees::SessionId;
+
+
+/* Stable codes identifying why a request to the remote API failed, which clients can match on */
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorCode {
+    BadRequest,                 /* The request body could not be read or decompressed */
+    Unauthorised,               /* The request did not send the token */
+    SessionTokenMismatch,       /* The request sent a session token other than the one issued to its session */
+    NotFound,                   /* No route matches the request */
+    SessionNotFound,            /* No tree has been posted for the session */
+    NodeNotFound,               /* The session's tree has no node with the id */
+    UploadNotFound,             /* No chunked upload is in progress with the id, or it expired */
+    PayloadTooLarge,            /* The request body exceeds a size limit */
+    UnsupportedMediaType,       /* The request body's Content-Type or Content-Encoding is not accepted */
+    InvalidTree,                /* The request body is not a valid tree, or cannot be attached to the session's tree */
+    UploadIncomplete,           /* A chunked upload was committed without all of its chunks */
+    TooManyUploads,             /* The most chunked uploads Dill holds at once are already in progress */
+    BreakpointConflict,         /* The session is already waiting at a breakpoint */
+    BreakpointClosed,           /* The breakpoint was closed before the user continued, e.g. by closing its session */
+    StateUnavailable,           /* Dill's state could not be locked */
+    StoreFailed,                /* The tree could not be saved or shown */
+    Internal,                   /* Any other failure within Dill */
+}
+
+impl ErrorCode {
+    pub fn status(self) -> Status {
+        match self {
+            ErrorCode::BadRequest => Status::BadRequest,
+            ErrorCode::Unauthorised => Status::Unauthorized,
+            ErrorCode::SessionTokenMismatch => Status::Forbidden,
+            ErrorCode::NotFound | ErrorCode::SessionNotFound | ErrorCode::NodeNotFound | ErrorCode::UploadNotFound => Status::NotFound,
+            ErrorCode::PayloadTooLarge => Status::PayloadTooLarge,
+            ErrorCode::UnsupportedMediaType => Status::UnsupportedMediaType,
+            ErrorCode::InvalidTree | ErrorCode::UploadIncomplete => Status::UnprocessableEntity,
+            ErrorCode::BreakpointConflict => Status::Conflict,
+            ErrorCode::TooManyUploads => Status::TooManyRequests,
+            ErrorCode::BreakpointClosed => Status::ServiceUnavailable,
+            ErrorCode::StateUnavailable | ErrorCode::StoreFailed | ErrorCode::Internal => Status::InternalServerError,
+        }
+    }
+
+    /* Code for a status Rocket rejected a request with, before any route handled it */
+    fn from_status(status: Status) -> ErrorCode {
+        match status.code {
+            400 => ErrorCode::BadRequest,
+            401 => ErrorCode::Unauthorised,
+            404 => ErrorCode::NotFound,
+            413 => ErrorCode::PayloadTooLarge,
+            415 => ErrorCode::UnsupportedMediaType,
+            422 => ErrorCode::InvalidTree,
+            _ => ErrorCode::Internal,
+        }
+    }
+}
+
+
+/* Body of every failed request to the remote API */
+#[derive(Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiError {
+    code: ErrorCode,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")] session_id: Option<SessionId>,
+    #[serde(skip_serializing_if = "Option::is_none")] details: Option<serde_json::Value>,   /* Code-specific fields, e.g. the limit exceeded */
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        ApiError { code, message: message.into(), session_id: None, details: None }
+    }
+
+    /* Name the session the failed request was about */
+    pub fn for_session(mut self, session_id: SessionId) -> Self {
+        self.session_id = Some(session_id);
+        self
+    }
+
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        self.code
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl From<StateError> for ApiError {
+    fn from(err: StateError) -> Self {
+        match err {
+            StateError::LockFailed => ApiError::new(ErrorCode::StateUnavailable, "Could not lock Dill's state"),
+            StateError::TreeNotFound => ApiError::new(ErrorCode::SessionNotFound, "No tree has been posted for this session"),
+            StateError::NodeNotFound(node_id) => ApiError::new(ErrorCode::NodeNotFound, format!("No node with id {node_id} in this session's tree")),
+            StateError::ParentNotFound(child_id) => ApiError::new(ErrorCode::InvalidTree, format!("No node with child id {child_id} to attach nodes to")),
+            StateError::EventEmitFailed => ApiError::new(ErrorCode::StoreFailed, "Could not show the tree in Dill"),
+            StateError::ChannelError => ApiError::new(ErrorCode::BreakpointClosed, "The breakpoint was closed before the user continued"),
+            err => ApiError::new(ErrorCode::Internal, format!("Dill failed with {err:?}")),
+        }
+    }
+}
+
+impl From<UpdateTreeError> for ApiError {
+    fn from(err: UpdateTreeError) -> Self {
+        let reason: &str = match err {
+            UpdateTreeError::LockFailed => return ApiError::from(StateError::LockFailed),
+            UpdateTreeError::TreeNotFound => return ApiError::from(StateError::TreeNotFound),
+            UpdateTreeError::SerialiseFailed => "could not be serialised",
+            UpdateTreeError::OpenFileFailed => "could not be opened",
+            UpdateTreeError::WriteTreeFailed => "could not be written",
+        };
+
+        ApiError::new(ErrorCode::StoreFailed, format!("The tree's save file {reason}"))
+    }
+}
+
+impl From<TreeBodyError> for ApiError {
+    fn from(err: TreeBodyError) -> Self {
+        match err {
+            TreeBodyError::ReadFailed => ApiError::new(ErrorCode::BadRequest, "The request body could not be read"),
+            TreeBodyError::TooLarge => ApiError::new(ErrorCode::PayloadTooLarge, "The tree is larger than the body limit"),
+            TreeBodyError::UnsupportedEncoding => ApiError::new(ErrorCode::UnsupportedMediaType, "Trees can only be compressed with gzip or zstd"),
+            TreeBodyError::UnsupportedFormat => ApiError::new(ErrorCode::UnsupportedMediaType, "Trees can only be sent as JSON or MessagePack"),
+            TreeBodyError::DecompressFailed => ApiError::new(ErrorCode::BadRequest, "The request body is not valid for its Content-Encoding"),
+            TreeBodyError::ParseFailed => ApiError::new(ErrorCode::InvalidTree, "The request body is not a valid tree"),
+            TreeBodyError::TooDeep => ApiError::new(ErrorCode::InvalidTree, format!("MessagePack trees can nest at most {} levels, so deeper trees must be sent as JSON", data::MSGPACK_MAX_DEPTH))
+                .with_details(serde_json::json!({ "maxDepth": data::MSGPACK_MAX_DEPTH })),
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        (self.code.status(), Json(self)).respond_to(req)
+    }
+}
+
+
+/* Catchers rendering requests Rocket rejected before a route handled them as ApiErrors */
+
+#[catch(404)]
+pub fn not_found(req: &Request<'_>) -> ApiError {
+    ApiError::new(ErrorCode::NotFound, format!("No route for {} {}", req.method(), req.uri()))
+}
+
+/* Rejected tree bodies record why, so the catchers can say more than the status */
+#[catch(422)]
+pub fn unprocessable_entity(req: &Request<'_>) -> ApiError {
+    data::rejection(req).map_or_else(
+        || ApiError::new(ErrorCode::InvalidTree, "The request body is not valid for this route"),
+        ApiError::from,
+    )
+}
+
+#[catch(413)]
+pub fn payload_too_large(req: &Request<'_>) -> ApiError {
+    let limit = data::tree_limit(req.limits());
+
+    ApiError::new(ErrorCode::PayloadTooLarge, format!("Tree is larger than the {limit} limit, compress it, send it as MessagePack, or raise the limit with --body-limit"))
+        .with_details(serde_json::json!({ "limit": limit.as_u64() }))
+}
+
+#[catch(default)]
+pub fn default(status: Status, req: &Request<'_>) -> ApiError {
+    data::rejection(req).map_or_else(
+        || ApiError::new(ErrorCode::from_status(status), status.reason_lossy()),
+        ApiError::from,
+    )
+}
+
+
+#[cfg(test)]
+mod test {
+
+    /* API error unit testing */
+
+    use rocket::http::{self, Status};
+    use rocket::local::blocking;
+
+    use super::{ApiError, ErrorCode};
+    use crate::server::test::tracked_client;
+    use crate::state::{MockStateManager, StateError};
+    use crate::trees::session_id::test::id;
+
+    #[test]
+    fn error_serialises_with_code_and_session() {
+        let error: ApiError = ApiError::from(StateError::TreeNotFound).for_session(id(3));
+
+        assert_eq!(
+            serde_json::to_value(&error).expect("Could not serialise error"),
+            serde_json::json!({ "code": "sessionNotFound", "message": "No tree has been posted for this session", "sessionId": id(3).to_string() })
+        );
+    }
+
+    #[test]
+    fn state_errors_map_to_statuses() {
+        assert_eq!(ApiError::from(StateError::TreeNotFound).code().status(), Status::NotFound);
+        assert_eq!(ApiError::from(StateError::ParentNotFound(4)).code().status(), Status::UnprocessableEntity);
+        assert_eq!(ApiError::from(StateError::ChannelError).code().status(), Status::ServiceUnavailable);
+        assert_eq!(ApiError::from(StateError::LockFailed).code().status(), Status::InternalServerError);
+    }
+
+    #[test]
+    fn unrouted_request_is_json_error() {
+        let client: blocking::Client = tracked_client(MockStateManager::new());
+
+        let response = client.get("/hello").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        assert_eq!(response.content_type(), Some(http::ContentType::JSON));
+
+        let body: serde_json::Value = response.into_json().expect("Error should be JSON");
+        assert_eq!(body["code"], "notFound");
+    }
+
+    #[test]
+    fn invalid_tree_is_json_error() {
+        let client: blocking::Client = tracked_client(MockStateManager::new());
+
+        let response = client.post("/api/remote/tree")
+            .header(http::ContentType::JSON)
+            .body("{}")
+            .dispatch();
+
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+
+        let body: serde_json::Value = response.into_json().expect("Error should be JSON");
+        assert_eq!(body["code"], serde_json::to_value(ErrorCode::InvalidTree).expect("Could not serialise code"));
+        assert_eq!(body["message"], "The request body is not a valid tree");
+    }
+
+    #[test]
+    fn unsupported_media_type_is_json_error() {
+        let client: blocking::Client = tracked_client(MockStateManager::new());
+
+        let response = client.post("/api/remote/tree")
+            .header(http::ContentType::Text)
+            .body("Hello world")
+            .dispatch();
+
+        assert_eq!(response.status(), Status::UnsupportedMediaType);
+
+        let body: serde_json::Value = response.into_json().expect("Error should be JSON");
+        assert_eq!(body["code"], "unsupportedMediaType");
+    }
+}
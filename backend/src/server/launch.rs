@@ -1,41 +1,144 @@
-use rocket::{Build, Config, Ignite, Rocket};
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use rocket::{Build, Config, Rocket};
 use rocket::figment::Figment;
 use rocket::figment::providers::{Format, Toml};
+use rocket::fairing::AdHoc;
 
+use super::auth::ApiToken;
+use super::data;
+use super::sessions;
+use super::upload::{self, UploadStore};
+use super::status::{self, LaunchError, ServerControl, ServerStatus};
 use super::ServerState;
+use crate::files;
+use crate::options::Options;
 
 
 /* Embed Rocket.toml as a string to allow post-compilation access */
 const ROCKET_CONFIG: &str = include_str!("Rocket.toml");
 
 
-/* Build the Rocket server */
-pub fn build(server_state: ServerState) -> Rocket<Build> {
+/* Config from Rocket.toml, overridden by the options Dill was launched with */
+fn figment(options: &Options) -> Figment {
     /* Override the default config with values from Rocket.toml */
-    let figment: Figment =
+    let mut figment: Figment =
         Figment::from(Config::default()).merge(Toml::string(ROCKET_CONFIG).nested());
 
+    /* Override Rocket.toml with the port, address and body limit Dill was launched with */
+    if let Some(port) = options.port {
+        figment = figment.merge((Config::PORT, port));
+    }
+    if let Some(address) = options.address {
+        figment = figment.merge((Config::ADDRESS, address));
+    }
+    if let Some(limit) = options.body_limit {
+        figment = figment.merge((format!("{}.{}", Config::LIMITS, data::TREE_LIMIT), limit.as_u64()));
+    }
+
+    figment
+}
+
+/* Build the Rocket server */
+pub fn build(server_state: ServerState, options: &Options) -> Rocket<Build> {
+    let upload_expiry: Duration = options.upload_expiry.map_or(upload::DEFAULT_UPLOAD_EXPIRY, Duration::from_secs);
+    server_state.set_heartbeat_timeout(options.heartbeat_timeout.map_or(sessions::DEFAULT_HEARTBEAT_TIMEOUT, Duration::from_secs));
+
     /* Build the rocket server */
-    rocket::custom(figment) /* Install our custom config */
+    rocket::custom(figment(options)) /* Install our custom config */
         .mount("/", super::request::routes()) /* Mount routes to the base path '/' */
+        .mount("/", super::websocket::routes()) /* Mount WebSocket protocol alongside HTTP routes */
+        .mount("/", super::upload::routes()) /* Mount chunked uploads for trees too large for one request */
         .manage(server_state) /* Manage the server state using Rocket state management */
+        .manage(ApiToken(options.token.clone())) /* Secret checked by the Authorised request guard */
+        .manage(UploadStore::new(upload_expiry)) /* Chunked uploads in progress */
+        .attach(super::upload::expire_uploads()) /* Discard abandoned uploads while none are being sent */
+        .register("/", rocket::catchers![ /* Explain rejected requests as JSON ApiErrors */
+            super::auth::unauthorised,
+            super::error::not_found,
+            super::error::unprocessable_entity,
+            super::error::payload_too_large,
+            super::error::default,
+        ])
+}
+
+/* Launch the Rocket server, reporting its status until it is shut down */
+/* Sessions are tracked by the control, so clients of a restarted server keep their tokens and ids */
+pub async fn launch(server_state: ServerState, control: &ServerControl) -> Result<(), LaunchError> {
+    let _running = control.running().await;
+    control.set_status(ServerStatus::Starting);
+
+    let launched: Result<(), LaunchError> = serve(server_state.with_registry(control.sessions()), control).await;
+    if let Err(err) = &launched {
+        control.set_status(ServerStatus::Failed { reason: err.to_string() });
+    }
+
+    launched
 }
 
-/* Launch the Rocket server */
-pub async fn launch(server_state: ServerState) -> Result<Rocket<Ignite>, rocket::Error> {
-    build(server_state).launch().await
+async fn serve(server_state: ServerState, control: &ServerControl) -> Result<(), LaunchError> {
+    let mut options: Options = control.options();
+
+    /* Listening only on the socket still needs a TCP listener, so hide it on any free loopback port */
+    if options.socket_only {
+        options.address = Some(IpAddr::V4(Ipv4Addr::LOCALHOST));
+        options.port = Some(0);
+    }
+
+    /* Check the port is free before launching, so a taken port can fall back to the next */
+    let config: Config = figment(&options).extract().map_err(|_| LaunchError::ConfigInvalid)?;
+    let port: u16 = status::free_port(config.address, config.port, options.port_fallback)?;
+    let socket: Option<PathBuf> = options.socket.clone();
+
+    /* Report the server as listening once it has bound to the port, or stop it if clients could not reach it */
+    let failed: Arc<Mutex<Option<LaunchError>>> = Arc::default();
+    let liftoff_failed: Arc<Mutex<Option<LaunchError>>> = Arc::clone(&failed);
+    let liftoff_control: ServerControl = control.clone();
+    let liftoff = AdHoc::on_liftoff("Server status", move |rocket| Box::pin(async move {
+        if let Err(err) = liftoff_control.listening(rocket) {
+            *liftoff_failed.lock().unwrap_or_else(PoisonError::into_inner) = Some(err);
+            rocket.shutdown().notify();
+        }
+    }));
+
+    let served = build(server_state, &Options { port: Some(port), ..options })
+        .attach(liftoff)
+        .launch()
+        .await;
+
+    /* Clients should not find a server that has stopped */
+    let _ = std::fs::remove_file(control.discovery_path());
+    if let Some(socket) = socket {
+        let _ = files::delete_socket(&socket);
+    }
+
+    if let Some(err) = failed.lock().unwrap_or_else(PoisonError::into_inner).take() {
+        return Err(err);
+    }
+    served.map(|_| ()).map_err(LaunchError::from)
 }
 
 #[cfg(test)]
 mod test {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use rocket::data::ByteUnit;
     use rocket::figment::providers::{self, Format, Toml};
     use rocket::figment::{Figment, Provider};
     use rocket::local::blocking;
     use rocket::{Build, Config, Rocket};
 
     use super::ROCKET_CONFIG;
-    use crate::server::{self, ServerState};
+    use crate::options::Options;
+    use crate::server::data::TREE_LIMIT;
+    use crate::server::status::LaunchError;
+    use crate::server::{self, ServerControl, ServerState, ServerStatus};
+    use crate::server::sessions;
     use crate::state::MockStateManager;
+    use crate::trees::session_id::test::id;
 
     /* Launch unit testing */
 
@@ -44,7 +147,7 @@ mod test {
         let mock = MockStateManager::new();
         let state = ServerState::new(mock);
 
-        let rocket: Rocket<Build> = super::build(state);
+        let rocket: Rocket<Build> = super::build(state, &Options::default());
 
         /* Fails if launching rocket would fail */
         assert!(blocking::Client::tracked(rocket).is_ok())
@@ -80,13 +183,130 @@ mod test {
         }
     }
 
+    #[test]
+    fn launch_options_override_config() {
+        let options = Options { port: Some(8000), address: Some(Ipv4Addr::UNSPECIFIED.into()), ..Options::default() };
+        let rocket: Rocket<Build> = super::build(ServerState::new(MockStateManager::new()), &options);
+
+        let config: Config = rocket.figment().extract().expect("Failed to extract config");
+        assert_eq!((config.port, config.address), (8000, IpAddr::from(Ipv4Addr::UNSPECIFIED)));
+    }
+
+    #[test]
+    fn body_limit_option_overrides_config() {
+        let options = Options { body_limit: Some(ByteUnit::Mebibyte(256)), ..Options::default() };
+        let rocket: Rocket<Build> = super::build(ServerState::new(MockStateManager::new()), &options);
+
+        let config: Config = rocket.figment().extract().expect("Failed to extract config");
+        assert_eq!(config.limits.get(TREE_LIMIT), Some(ByteUnit::Mebibyte(256)));
+    }
+
+    #[cfg(unix)]
+    #[rocket::async_test]
+    async fn unbound_socket_is_not_reported() {
+        let dir: std::path::PathBuf = std::env::temp_dir().join(format!("dill-unbound-socket-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("Could not create directory");
+
+        /* A file that is not a socket is never replaced, so the socket cannot be bound */
+        let path: std::path::PathBuf = dir.join("dill.sock");
+        std::fs::write(&path, "not a socket").expect("Could not write file");
+
+        let options = Options {
+            address: Some(Ipv4Addr::LOCALHOST.into()),
+            port: Some(0),
+            socket: Some(path.clone()),
+            discovery_file: Some(dir.join("dill-server.json")),
+            ..Options::default()
+        };
+
+        /* Still reachable on TCP, the server is reported listening without the socket */
+        let control = ServerControl::new(options.clone(), |_| ());
+        let launched_control: ServerControl = control.clone();
+        let launched = rocket::tokio::spawn(async move {
+            super::launch(ServerState::new(MockStateManager::new()), &launched_control).await
+        });
+
+        while !matches!(control.status(), ServerStatus::Listening { .. }) {
+            rocket::tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(matches!(control.status(), ServerStatus::Listening { socket: None, .. }));
+
+        control.stop(None);
+        launched.await.expect("Launch panicked").expect("Server should stop cleanly");
+
+        /* Only reachable on the socket, the server fails instead */
+        let control = ServerControl::new(Options { socket_only: true, ..options }, |_| ());
+        let launched = super::launch(ServerState::new(MockStateManager::new()), &control).await;
+
+        assert!(matches!(launched, Err(LaunchError::SocketUnavailable(..))));
+        assert!(matches!(control.status(), ServerStatus::Failed { .. }));
+        assert!(!dir.join("dill-server.json").exists());
+
+        std::fs::remove_dir_all(dir).expect("Could not remove directory");
+    }
+
+    /* Port a launched server is listening on, once it is */
+    async fn listening_port(control: &ServerControl) -> u16 {
+        loop {
+            match control.status() {
+                ServerStatus::Listening { port, .. } => return port,
+                _ => rocket::tokio::time::sleep(std::time::Duration::from_millis(10)).await,
+            }
+        }
+    }
+
+    #[rocket::async_test]
+    async fn restarted_server_keeps_sessions() {
+        let dir: std::path::PathBuf = std::env::temp_dir().join(format!("dill-restart-{}", std::process::id()));
+        let options = Options {
+            address: Some(Ipv4Addr::LOCALHOST.into()),
+            port: Some(0),
+            discovery_file: Some(dir.join("dill-server.json")),
+            ..Options::default()
+        };
+        let control = ServerControl::new(options, |_| ());
+
+        /* Every server is built with a state of its own, as the app does on restart */
+        let relaunch = |control: ServerControl| rocket::tokio::spawn(async move {
+            let mut mock = MockStateManager::new();
+            mock.expect_next_session_id().returning(|| Ok(id(4)));
+            super::launch(ServerState::new(mock), &control).await
+        });
+
+        let launched = relaunch(control.clone());
+        let port: u16 = listening_port(&control).await;
+        assert!(sessions::test::response(port, "POST /api/remote/newSession", "").await.starts_with("HTTP/1.1 200"));
+
+        control.stop(None);
+        launched.await.expect("Launch panicked").expect("Server should stop cleanly");
+        control.set_status(ServerStatus::Starting);
+
+        /* The restarted server still knows the session, so still checks its token */
+        let launched = relaunch(control.clone());
+        let port: u16 = listening_port(&control).await;
+        let listed: String = sessions::test::response(port, "GET /api/remote/sessions", "").await;
+        assert!(listed.contains(&id(4).to_string()));
+
+        control.stop(None);
+        launched.await.expect("Launch panicked").expect("Server should stop cleanly");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn default_discovery_file_is_named_after_process() {
+        let control = ServerControl::new(Options::default(), |_| ());
+        let path: std::path::PathBuf = control.discovery_path();
+
+        assert_eq!(path.file_name().and_then(|name| name.to_str()), Some(format!("dill-server-{}.json", std::process::id()).as_str()));
+    }
+
     #[test]
     fn num_routes_mounted_is_correct() {
         let mock = MockStateManager::new();
         let client: blocking::Client = server::test::tracked_client(mock);
 
         /* Assert the Rocket server was successfully built with the added routes */
-        assert_eq!(client.rocket().routes().count(), 4);
+        assert_eq!(client.rocket().routes().count(), 11);
     }
 
     #[test]
@@ -98,5 +318,10 @@ mod test {
         /* Assert the Rocket server was built with the correct routes */
         assert!(routes.contains(&"/"));
         assert!(routes.contains(&"/api/remote/tree"));
+        assert!(routes.contains(&"/api/remote/tree/delta"));
+        assert!(routes.contains(&"/api/remote/ws"));
+        assert!(routes.contains(&"/api/remote/tree/upload"));
+        assert!(routes.contains(&"/api/remote/sessions"));
+        assert!(routes.contains(&"/api/remote/heartbeat?<session_id>"));
     }
 }
@@ -1,16 +1,28 @@
-use rocket::{get, post, http, serde::json::Json};
+use rocket::{get, post, serde::json::Json};
+use rocket::response::content::RawJson;
 
+use super::auth::Authorised;
+use super::data::TreeBody;
+use super::error::{ApiError, ErrorCode};
+use super::sessions::{ClientInfo, PausedSession, SessionInfo, SessionStatus, SessionToken};
 use super::ServerState;
 use crate::events::Event;
-use crate::trees::{DebugTree, ParsleyTree};
+use crate::trees::{ClientSessionId, DebugTree, ParsleyTree, ParsleyTreeDelta, SessionId, SessionMetadata};
 use crate::state::{StateError, StateManager};
+use crate::state::state_manager::{BreakpointCode, BreakpointMessage, MergedDelta};
+use crate::state::breakpoint_timeout::TimeoutAction;
+use crate::state::timeline::RefChange;
+use crate::state::watchpoints::WatchpointHit;
 
 /* Length of input slice returned in post response */
 const RESPONSE_INPUT_LEN: usize = 16;
 
+/* First protocol version whose clients understand structured breakpoint commands */
+const STRUCTURED_COMMAND_VERSION: u32 = 2;
+
 /* Expose routes for mounting during launch */
 pub fn routes() -> Vec<rocket::Route> {
-    rocket::routes![get_index, get_tree, post_tree, new_session]
+    rocket::routes![get_index, get_tree, post_tree, post_tree_delta, new_session, heartbeat, get_sessions]
 }
 
 /* Placeholder GET request handler to print 'Hello world!' */
@@ -19,26 +31,64 @@ fn get_index() -> String {
     String::from("DILL: Debugging Interactively for the ParsLey Language")
 }
 
+/* Command telling RemoteView how to continue from a breakpoint */
+#[derive(Debug, PartialEq, serde::Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub(super) enum BreakpointCommand {
+    Skip { skips: i32 },
+    SkipAll,
+    Terminate,
+    UntilParser { name: String },
+    UntilFailure,
+    UntilOffset { offset: u32 },
+}
+
+/* Breakpoint command tagged with the version of its encoding */
+#[derive(Debug, PartialEq, serde::Serialize)]
+pub(super) struct VersionedCommand {
+    version: u32,
+    #[serde(flatten)] command: BreakpointCommand,
+}
+
+impl From<BreakpointCode> for VersionedCommand {
+    fn from(code: BreakpointCode) -> Self {
+        let command: BreakpointCommand = match code {
+            BreakpointCode::Skip(skips) => BreakpointCommand::Skip { skips },
+            BreakpointCode::SkipAll => BreakpointCommand::SkipAll,
+            BreakpointCode::Terminate => BreakpointCommand::Terminate,
+            BreakpointCode::UntilParser(name) => BreakpointCommand::UntilParser { name },
+            BreakpointCode::UntilFailure => BreakpointCommand::UntilFailure,
+            BreakpointCode::UntilOffset(offset) => BreakpointCommand::UntilOffset { offset },
+        };
+
+        VersionedCommand { version: STRUCTURED_COMMAND_VERSION, command }
+    }
+}
+
 #[derive(Debug, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
-struct PostTreeResponse {
+pub(super) struct PostTreeResponse {
     message: String,
-    session_id: i32,
+    session_id: ClientSessionId,    /* In the form the client sent it */
     #[serde(skip_serializing_if = "Option::is_none")] skip_breakpoint: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")] breakpoint_command: Option<VersionedCommand>,
     #[serde(skip_serializing_if = "Option::is_none")] new_refs: Option<Vec<(i32, String)>>,
+    #[serde(skip_serializing_if = "Option::is_none")] timeout_action: Option<TimeoutAction>, /* Applied as no one continued in time */
 }
 
 impl PostTreeResponse {
-    fn new(msg: impl Into<String>, session_id: i32, skips: Option<i32>, new_refs: Option<Vec<(i32, String)>>) -> Json<PostTreeResponse> {
+    fn new(msg: impl Into<String>, session_id: ClientSessionId, skips: Option<i32>, new_refs: Option<Vec<(i32, String)>>) -> Json<PostTreeResponse> {
         Json(PostTreeResponse {
             message: msg.into(),
             session_id,
             skip_breakpoint: skips,
+            breakpoint_command: None,
             new_refs,
+            timeout_action: None,
         })
     }
 
-    fn success_msg(input: &str) -> String {
+    pub(super) fn success_msg(input: &str) -> String {
         format!(
             "Posted parser tree handling input: \"{}{}\" to Dill",
             /* Include first few chars of input */
@@ -51,126 +101,377 @@ impl PostTreeResponse {
         )
     }
 
-    fn no_skips(message: &str, session_id: i32) -> Json<PostTreeResponse> {
+    fn no_skips(message: &str, session_id: ClientSessionId) -> Json<PostTreeResponse> {
         PostTreeResponse::new(message, session_id, None, None)
     }
 
-    fn with_refs(message: &str, session_id: i32, skips: i32, new_refs: Vec<(i32, String)>) -> Json<PostTreeResponse> {
-        PostTreeResponse::new(message, session_id, Some(skips), Some(new_refs))
+    /* Every client is sent a plain skip count, and newer clients the structured command too */
+    fn with_refs(message: &str, session_id: ClientSessionId, code: BreakpointCode, protocol_version: u32, new_refs: Vec<(i32, String)>) -> Json<PostTreeResponse> {
+        let mut response: Json<PostTreeResponse> = PostTreeResponse::new(message, session_id, Some(code.i32_code()), Some(new_refs));
+
+        if protocol_version >= STRUCTURED_COMMAND_VERSION {
+            response.breakpoint_command = Some(VersionedCommand::from(code));
+        }
+
+        response
     }
 }
 
-fn process_parsley_tree(mut parsley_tree: ParsleyTree, state: &rocket::State<ServerState>) -> Result<DebugTree, StateError> {
-    /* SETUP: Allocate id if RemoteView doesn't have one */
-    if parsley_tree.session_not_set() {
-        let allocated_id: i32 = state.inner().next_session_id()?;
-        parsley_tree.set_session_id(allocated_id);
+/* Response to a posted delta, which also reports how many nodes were merged */
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct PostTreeDeltaResponse {
+    #[serde(flatten)] response: PostTreeResponse,
+    merged_nodes: usize,
+}
+
+impl PostTreeDeltaResponse {
+    fn new(response: Json<PostTreeResponse>, merged_nodes: usize) -> Json<PostTreeDeltaResponse> {
+        Json(PostTreeDeltaResponse { response: response.into_inner(), merged_nodes })
     }
+}
+
+fn process_parsley_tree(parsley_tree: ParsleyTree, state: &ServerState) -> Result<DebugTree, StateError> {
+    /* SETUP: Allocate id if RemoteView doesn't have one, or sent an integer id not seen before */
+    let session_id: SessionId = state.resolve_session_id(parsley_tree.session_id())?;
+
+    let mut debug_tree: DebugTree = parsley_tree.into_debug_tree(session_id);
+    debug_tree.set_metadata(state.session_metadata(session_id));
+    Ok(debug_tree)
+}
 
-    Ok(parsley_tree.into())
+/* Reject a request whose session token is not the one issued to its session in the handshake */
+pub(super) fn check_session_token(state: &ServerState, session_id: SessionId, token: &SessionToken) -> Result<(), ApiError> {
+    match state.session_token_matches(session_id, token.0.as_deref()) {
+        true => Ok(()),
+        false => Err(ApiError::new(ErrorCode::SessionTokenMismatch, "The session token is not the one issued to this session").for_session(session_id)),
+    }
 }
 
 
-/* Create thread communication channels */
-fn create_breakpoint_channels(state: &rocket::State<ServerState>, session_id: i32, refs: Vec<(i32, String)>) -> Result<(), &'static str> {
-    let (tx, rx) = rocket::tokio::sync::oneshot::channel::<i32>();
+/* Create thread communication channels, released if the returned session is dropped before the user continues */
+pub(super) fn create_breakpoint_channels(state: &ServerState, session_id: SessionId, refs: Vec<(i32, String)>) -> Result<PausedSession<'_>, ApiError> {
+    let (tx, rx) = rocket::tokio::sync::oneshot::channel::<BreakpointMessage>();
 
-    match state.new_receiver(session_id, rx) {
-        Some(_) => Err("Receiver already exists for this session id")?,
-        None => if state.new_transmitter(session_id, tx).is_err() {
-            Err("Could not initialise transmitter in state")?
-        },
-    };
+    if state.new_receiver(session_id, rx).is_some() {
+        return Err(ApiError::new(ErrorCode::BreakpointConflict, "This session is already waiting at a breakpoint").for_session(session_id));
+    }
+
+    /* Another request for the session is still waiting, so only this request's receiver is dropped */
+    if let Err(err) = state.new_transmitter(session_id, tx) {
+        state.drop_receiver(session_id);
+        return Err(ApiError::from(err).for_session(session_id));
+    }
+
+    let paused = PausedSession::new(state, session_id);
 
     /* Reset references for a post tree */
-    state.inner().reset_refs(session_id, refs).map_err(|_| "Could not acquire internal lock")
+    state.reset_refs(session_id, refs).map_err(|err| ApiError::from(err).for_session(session_id))?;
+    Ok(paused)
 }
 
 /* Post request handler to accept debug tree */
-#[post("/api/remote/tree", format = "application/json", data = "<data>")]
-async fn post_tree(data: Json<ParsleyTree>, state: &rocket::State<ServerState>) -> (http::Status, Json<PostTreeResponse>) {
-    /* Deserialise and unwrap json data */
-    let parsley_tree: ParsleyTree = data.into_inner();
-
-    let debug_tree: DebugTree = match process_parsley_tree(parsley_tree, state) {
-        Ok(tree) => tree,
-        Err(_) => return (http::Status::InternalServerError, PostTreeResponse::no_skips("Could not allocate a session id", -1)),
-    };
+/* The body may be JSON or MessagePack, compressed with gzip or zstd, so its format is checked by TreeBody */
+#[post("/api/remote/tree", data = "<data>")]
+async fn post_tree(_auth: Authorised, client: ClientInfo, token: SessionToken, data: TreeBody<ParsleyTree>, state: &rocket::State<ServerState>) -> Result<Json<PostTreeResponse>, ApiError> {
+    handle_parsley_tree(data.into_inner(), state, client, token).await
+}
+
+/* Store a posted tree, waiting for the user to continue if it is a breakpoint */
+pub(super) async fn handle_parsley_tree(parsley_tree: ParsleyTree, state: &ServerState, client: ClientInfo, token: SessionToken) -> Result<Json<PostTreeResponse>, ApiError> {
+    let protocol_version: u32 = parsley_tree.protocol_version();
+    let legacy_ids: bool = parsley_tree.speaks_legacy_ids();
+
+    let debug_tree: DebugTree = process_parsley_tree(parsley_tree, state)?;
 
     /* Extract useful fields from tree */
     let is_debuggable: bool = debug_tree.is_debuggable();
-    let session_id: i32 = debug_tree.get_session_id();
+    let session_id: SessionId = debug_tree.get_session_id();
+    let reply_id: ClientSessionId = state.client_session_id(session_id, legacy_ids);
 
-    if is_debuggable {
-        if let Err(msg) = create_breakpoint_channels(state, session_id, debug_tree.refs()) {
-            return (http::Status::InternalServerError, PostTreeResponse::no_skips(msg, session_id));
-        }
+    check_session_token(state, session_id, &token)?;
+
+    let changes: Option<Vec<RefChange>> = is_debuggable.then(|| watched_changes(state, session_id, &debug_tree.refs())).flatten();
+
+    if is_debuggable && !should_pause(matched_rules(state, &debug_tree), changes.as_deref()) {
+        let success_msg: String = PostTreeResponse::success_msg(debug_tree.get_input());
+        let response: Json<PostTreeResponse> = auto_continue(&success_msg, debug_tree.refs(), reply_id, protocol_version);
+
+        /* Kept like a delta that continued, so the session's timeline has every breakpoint it passed */
+        state.record_tree(debug_tree).map_err(|err| ApiError::from(err).for_session(session_id))?;
+        state.set_session_status(session_id, SessionStatus::Running, Some(client));
+        return Ok(response);
     }
 
+    let paused: Option<PausedSession> = match is_debuggable {
+        true => Some(create_breakpoint_channels(state, session_id, debug_tree.refs())?),
+        false => None,
+    };
+
     /* Format informative response for RemoteView */
     let success_msg: String = PostTreeResponse::success_msg(debug_tree.get_input());
 
-    if let Err(_) = state.set_tree(debug_tree.clone()) {
-        todo!()
+    state.set_tree(debug_tree).map_err(|err| ApiError::from(err).for_session(session_id))?;
+    state.update_tree(session_id).map_err(|err| ApiError::from(err).for_session(session_id))?;
+    state.emit(Event::NewTree).map_err(|err| ApiError::from(err).for_session(session_id))?;
+    emit_watchpoint_hit(state, session_id, changes).map_err(|err| ApiError::from(err).for_session(session_id))?;
+
+    match paused {
+        None => {
+            state.set_session_status(session_id, SessionStatus::Finished, Some(client));
+            Ok(PostTreeResponse::no_skips(&success_msg, reply_id))
+        },
+        Some(paused) => {
+            state.set_session_status(session_id, SessionStatus::Paused, Some(client));
+            await_breakpoint(state, paused, session_id, reply_id, protocol_version, &success_msg).await
+        },
     }
+}
+
+/* Watched references changed since a session's latest snapshot, or None if nothing is watched */
+/* Must be called before the breakpoint's own tree is stored */
+pub(super) fn watched_changes(state: &ServerState, session_id: SessionId, refs: &[(i32, String)]) -> Option<Vec<RefChange>> {
+    state.watched_changes(session_id, refs).unwrap_or(None)
+}
 
-    if let Err(_) = state.update_tree(&debug_tree, session_id) {
-        todo!()
+/* Whether a tree matches an enabled rule, or None if none is, taken to match if the rules cannot be read */
+pub(super) fn matched_rules(state: &ServerState, tree: &DebugTree) -> Option<bool> {
+    state.matches_rules(tree).unwrap_or(Some(true))
+}
+
+/* Whether to show a breakpoint: if an enabled rule matches or a watched reference changed */
+/* Every breakpoint is shown when neither rules nor watchpoints are set */
+pub(super) fn should_pause(rules: Option<bool>, changes: Option<&[RefChange]>) -> bool {
+    match (rules, changes) {
+        (None, None) => true,
+        (_, Some(changes)) if !changes.is_empty() => true, /* Shown whatever the rules say */
+        (rules, _) => rules == Some(true),
     }
+}
 
-    if let Err(_) = state.emit(Event::NewTree) {
-        todo!()
+/* Name the watched references that changed to the frontend, once the breakpoint they stopped at is stored */
+pub(super) fn emit_watchpoint_hit(state: &ServerState, session_id: SessionId, changes: Option<Vec<RefChange>>) -> Result<(), StateError> {
+    match changes.filter(|changes| !changes.is_empty()) {
+        Some(changes) => state.emit(Event::WatchpointHit(&WatchpointHit::new(session_id, changes))),
+        None => Ok(()),
     }
-    
-    if !is_debuggable {
-        (http::Status::Ok, PostTreeResponse::no_skips(&success_msg, session_id))
-    } else {
-        match state.receive_breakpoint_skips(session_id).await {
-            Some(skips) => (http::Status::Ok, PostTreeResponse::with_refs(&success_msg, session_id, skips, state.get_refs(session_id).expect("Session ID should exist"))),
-            None => (http::Status::InternalServerError, PostTreeResponse::no_skips("TODO", session_id)),
-        }
+}
+
+/* Continue from a breakpoint no rule matched with the references it was posted with, without waking the frontend */
+fn auto_continue(success_msg: &str, refs: Vec<(i32, String)>, reply_id: ClientSessionId, protocol_version: u32) -> Json<PostTreeResponse> {
+    PostTreeResponse::with_refs(success_msg, reply_id, BreakpointCode::Skip(0), protocol_version, refs)
+}
+
+/* Wait for the user to continue from a breakpoint, responding with the skips and references sent together */
+/* If the session's timeout passes first, its action is applied and named in the response */
+/* If the client's heartbeats stop first, the paused session is dropped, releasing its channels */
+async fn await_breakpoint(state: &ServerState, paused: PausedSession<'_>, session_id: SessionId, reply_id: ClientSessionId, protocol_version: u32, success_msg: &str) -> Result<Json<PostTreeResponse>, ApiError> {
+    let received: Option<(BreakpointMessage, Option<TimeoutAction>)> = rocket::tokio::select! {
+        received = state.receive_breakpoint_skips(session_id) => received,
+        _ = state.heartbeats_stopped(session_id) => {
+            drop(paused);
+            return Err(ApiError::from(StateError::ChannelError).for_session(session_id));
+        },
+    };
+
+    match received {
+        Some((BreakpointMessage { code, refs }, timeout_action)) => {
+            paused.resume(code == BreakpointCode::Terminate);
+
+            let mut response: Json<PostTreeResponse> = PostTreeResponse::with_refs(success_msg, reply_id, code, protocol_version, refs);
+            response.timeout_action = timeout_action;
+            Ok(response)
+        },
+        None => {
+            paused.close();
+            Err(ApiError::from(StateError::ChannelError).for_session(session_id))
+        },
     }
 }
 
-/* Return posted DebugTree as JSON string */
-#[get("/api/remote/tree")]
-fn get_tree(state: &rocket::State<ServerState>) -> String {
-    match &state.get_tree() {
-        Ok(tree) => serde_json::to_string_pretty(tree)
-            .unwrap_or(String::from("Could not serialise tree to JSON")),
-        Err(err) => format!("{:?}", err),
+/* Post request handler to accept the nodes generated since a session's previous breakpoint */
+#[post("/api/remote/tree/delta", data = "<data>")]
+async fn post_tree_delta(_auth: Authorised, client: ClientInfo, token: SessionToken, data: TreeBody<ParsleyTreeDelta>, state: &rocket::State<ServerState>) -> Result<Json<PostTreeDeltaResponse>, ApiError> {
+    let delta: ParsleyTreeDelta = data.into_inner();
+
+    /* A delta extends a session the server already knows */
+    let session_id: SessionId = state.find_session_id(delta.session_id())?.ok_or(ApiError::from(StateError::TreeNotFound))?;
+    let reply_id: ClientSessionId = state.client_session_id(session_id, delta.session_id().is_legacy());
+    let is_debuggable: bool = delta.is_debuggable();
+    let refs: Vec<(i32, String)> = delta.refs();
+    let protocol_version: u32 = delta.protocol_version();
+
+    check_session_token(state, session_id, &token)?;
+
+    let session_error = |err: StateError| ApiError::from(err).for_session(session_id);
+
+    /* Merge before creating channels, so a rejected delta leaves the session untouched */
+    /* Rules and watchpoints are read while merging, against the latest tree rather than the one shown */
+    let MergedDelta { delta: tree_delta, input, matches_rules, changes } = state.merge_delta(session_id, delta).map_err(session_error)?;
+    let success_msg: String = PostTreeResponse::success_msg(&input);
+    let merged_nodes: usize = tree_delta.num_nodes();
+
+    /* The delta stays merged, so later deltas can attach to its nodes */
+    if is_debuggable && !should_pause(matches_rules, changes.as_deref()) {
+        state.set_session_status(session_id, SessionStatus::Running, Some(client));
+        return Ok(PostTreeDeltaResponse::new(auto_continue(&success_msg, refs, reply_id, protocol_version), merged_nodes));
+    }
+
+    let paused: Option<PausedSession> = match is_debuggable {
+        true => Some(create_breakpoint_channels(state, session_id, refs)?),
+        false => None,
+    };
+
+    /* Persist the merged tree, and send only the added nodes to the frontend */
+    state.update_tree(session_id).map_err(|err| ApiError::from(err).for_session(session_id))?;
+    state.emit(Event::TreeDelta(&tree_delta)).map_err(session_error)?;
+    if is_debuggable {
+        emit_watchpoint_hit(state, session_id, changes).map_err(session_error)?;
     }
+    state.focus_session(session_id).map_err(session_error)?;
+
+    let response: Json<PostTreeResponse> = match paused {
+        None => {
+            state.set_session_status(session_id, SessionStatus::Finished, Some(client));
+            PostTreeResponse::no_skips(&success_msg, reply_id)
+        },
+        Some(paused) => {
+            state.set_session_status(session_id, SessionStatus::Paused, Some(client));
+            await_breakpoint(state, paused, session_id, reply_id, protocol_version, &success_msg).await?
+        },
+    };
+
+    Ok(PostTreeDeltaResponse::new(response, merged_nodes))
+}
+
+/* Return a session's DebugTree as JSON, defaulting to the displayed session */
+#[get("/api/remote/tree?<session_id>")]
+fn get_tree(_auth: Authorised, session_id: Option<ClientSessionId>, state: &rocket::State<ServerState>) -> Result<RawJson<String>, ApiError> {
+    let session_id: SessionId = match session_id {
+        Some(session_id) => state.find_session_id(session_id)?.ok_or(ApiError::from(StateError::TreeNotFound))?,
+        None => state.displayed_session_id()?,
+    };
+
+    let tree: DebugTree = state.get_tree(session_id).map_err(|err| ApiError::from(err).for_session(session_id))?;
+
+    serde_json::to_string_pretty(&tree)
+        .map(RawJson)
+        .map_err(|_| ApiError::new(ErrorCode::Internal, "Could not serialise tree to JSON").for_session(session_id))
+}
+
+/* Parts of the remote protocol this server supports, so clients can avoid what it does not */
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ProtocolFeatures {
+    protocol_version: u32,
+    compression: &'static [&'static str],
+    encodings: &'static [&'static str],
+    deltas: bool,
+    chunked_uploads: bool,
+    websocket: bool,
+    heartbeats: bool,
+    breakpoint_kinds: &'static [&'static str],
+}
+
+impl ProtocolFeatures {
+    const SUPPORTED: ProtocolFeatures = ProtocolFeatures {
+        protocol_version: ParsleyTree::OPAQUE_SESSION_ID_VERSION,
+        compression: &["gzip", "zstd"],
+        encodings: &["json", "msgpack"],
+        deltas: true,
+        chunked_uploads: true,
+        websocket: true,
+        heartbeats: true,
+        breakpoint_kinds: &["skip", "skipAll", "terminate", "untilParser", "untilFailure", "untilOffset"],
+    };
 }
 
 #[derive(serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 struct NewSessionResponse {
-    session_id: i32
+    session_id: ClientSessionId,    /* An integer alias for clients older than opaque ids */
+    server_version: &'static str,
+    features: &'static ProtocolFeatures,
+    #[serde(skip_serializing_if = "Option::is_none")] session_token: Option<String>,  /* Must be sent back in the X-Dill-Session-Token header */
 }
 
-/* Post request handler to accept debug tree */
-#[post("/api/remote/newSession", format = "application/json")]
-async fn new_session(state: &rocket::State<ServerState>) -> (http::Status, Json<NewSessionResponse>) {
-    match state.next_session_id() {
-        Ok(session_id) => (http::Status::Ok, Json(NewSessionResponse { session_id })),
-        Err(_) => (http::Status::InternalServerError, Json(NewSessionResponse { session_id: -1 })),
+/* Post request handler to allocate a session, storing the metadata its client describes itself with */
+/* Older clients send no body, so have no metadata and are issued no session token */
+#[post("/api/remote/newSession", format = "application/json", data = "<handshake>")]
+async fn new_session(_auth: Authorised, client: ClientInfo, handshake: Result<Json<SessionMetadata>, rocket::serde::json::Error<'_>>, state: &rocket::State<ServerState>) -> Result<Json<NewSessionResponse>, ApiError> {
+    let metadata: Option<SessionMetadata> = match handshake {
+        Ok(metadata) => Some(metadata.into_inner()),
+        Err(rocket::serde::json::Error::Parse(body, _)) if body.trim().is_empty() => None,
+        Err(rocket::serde::json::Error::Parse(_, err)) => return Err(ApiError::new(ErrorCode::BadRequest, format!("The session metadata is not valid: {err}"))),
+        Err(rocket::serde::json::Error::Io(_)) => return Err(ApiError::new(ErrorCode::BadRequest, "The request body could not be read")),
+    };
+
+    let legacy_ids: bool = metadata.as_ref().and_then(|metadata| metadata.protocol_version).filter(|&version| version >= ParsleyTree::OPAQUE_SESSION_ID_VERSION).is_none();
+
+    let session_id: SessionId = state.next_session_id()?;
+    let session_token: Option<String> = state.open_session(session_id, client, metadata);
+
+    Ok(Json(NewSessionResponse {
+        session_id: state.client_session_id(session_id, legacy_ids),
+        server_version: env!("CARGO_PKG_VERSION"),
+        features: &ProtocolFeatures::SUPPORTED,
+        session_token,
+    }))
+}
+
+/* Post request handler to keep a session alive, so it is taken to have disconnected once heartbeats stop */
+/* A client waiting at a breakpoint over HTTP sends these on another connection while its tree is posted */
+#[post("/api/remote/heartbeat?<session_id>")]
+fn heartbeat(_auth: Authorised, token: SessionToken, session_id: ClientSessionId, state: &rocket::State<ServerState>) -> Result<rocket::http::Status, ApiError> {
+    let session_id: SessionId = state.find_session_id(session_id)?.ok_or(ApiError::from(StateError::TreeNotFound))?;
+    check_session_token(state, session_id, &token)?;
+
+    match state.session_heartbeat(session_id) {
+        true => Ok(rocket::http::Status::NoContent),
+        false => Err(ApiError::from(StateError::TreeNotFound).for_session(session_id)),
     }
 }
 
+/* Every session the server has seen, with its status, when it was last seen and its client */
+#[get("/api/remote/sessions")]
+fn get_sessions(_auth: Authorised, state: &rocket::State<ServerState>) -> Json<Vec<SessionInfo>> {
+    Json(state.sessions())
+}
+
 
 #[cfg(test)]
 pub mod test {
 
+    use std::sync::{Arc, Mutex};
+
     use mockall::predicate;
     use rocket::{http, local::blocking};
 
+    use crate::state::state_manager::{BreakpointCode, BreakpointMessage, MergedDelta, UpdateTreeError};
+    use crate::state::breakpoint_timeout::{TimeoutAction, TimeoutPolicy};
+
     use crate::events::Event;
+    use crate::server::SkipsSender;
+    use crate::server::sessions::SESSION_TOKEN_HEADER;
     use crate::server::test::tracked_client;
-    use crate::state::MockStateManager;
-    use crate::trees::{debug_tree, parsley_tree};
+    use crate::server::{launch, ServerState};
+    use crate::options::Options;
+    use crate::state::{BreakpointPolicy, HeadlessState};
+    use crate::state::{MockStateManager, StateError};
+    use crate::state::timeline::RefChange;
+    use crate::trees::{debug_tree, delta_tree, parsley_tree, ClientSessionId, DebugTree, ParsleyTreeDelta};
+    use crate::trees::session_id::test::id;
 
     /* Request unit testing */
 
+    /* Merge a delta into the nested tree as the app state would, with no rule enabled and nothing watched */
+    fn merged(delta: ParsleyTreeDelta) -> Result<MergedDelta, StateError> {
+        let mut tree: DebugTree = debug_tree::test::nested_tree();
+        let (tree_delta, _) = tree.merge(delta)?;
+
+        Ok(MergedDelta { delta: tree_delta, input: tree.get_input().clone(), matches_rules: None, changes: None })
+    }
+
     #[test]
     fn get_responds_onboarding() {
         let mock = MockStateManager::new();
@@ -207,9 +508,9 @@ pub mod test {
             .with(predicate::eq(debug_tree::test::tree()))
             .returning(|_| Ok(()));
 
-        mock.expect_next_session_id().returning(|| Ok(-1));
+        mock.expect_next_session_id().returning(|| Ok(id(0)));
 
-        mock.expect_update_tree().returning(|_, _| Ok(()));
+        mock.expect_update_tree().returning(|_| Ok(()));
 
         mock.expect_emit().withf(|expected| &Event::NewTree == expected)
             .returning(|_| Ok(()));
@@ -220,13 +521,348 @@ pub mod test {
         let response: blocking::LocalResponse = client
             .post(rocket::uri!(super::post_tree))
             .header(http::ContentType::JSON)
-            .body(&parsley_tree::test::json())
+            .body(parsley_tree::test::json())
+            .dispatch();
+
+        /* Assert that POST succeeded */
+        assert_eq!(response.status(), http::Status::Ok);
+    }
+
+    #[test]
+    fn deep_post_tree_succeeds() {
+        const DEPTH: usize = 10_000;
+
+        let mut mock = MockStateManager::new();
+        mock.expect_set_tree()
+            .withf(|tree| tree.get_node(DEPTH as u32).is_some_and(|node| node.is_leaf))
+            .returning(|_| Ok(()));
+
+        mock.expect_next_session_id().returning(|| Ok(id(0)));
+        mock.expect_update_tree().returning(|_| Ok(()));
+        mock.expect_emit().returning(|_| Ok(()));
+
+        let client: blocking::Client = tracked_client(mock);
+
+        /* Perform POST request of a tree nested beyond serde_json's default limit */
+        let response: blocking::LocalResponse = client
+            .post(rocket::uri!(super::post_tree))
+            .header(http::ContentType::JSON)
+            .body(parsley_tree::test::deep_tree_json(DEPTH))
             .dispatch();
 
         /* Assert that POST succeeded */
         assert_eq!(response.status(), http::Status::Ok);
     }
 
+    #[test]
+    fn post_tree_delta_succeeds() {
+        let mut mock = MockStateManager::new();
+
+        /* Merge the delta into a real tree, as the app state would */
+        mock.expect_merge_delta()
+            .withf(|session_id, delta| *session_id == id(0) && delta.session_id() == ClientSessionId::Opaque(id(0)))
+            .times(1)
+            .returning(|_, delta| merged(delta));
+
+        mock.expect_new_transmitter().returning(|_, tx| {
+            tx.send(BreakpointMessage { code: BreakpointCode::Skip(2), refs: vec![(0, String::from("b"))] }).expect("Receiver should be waiting");
+            Ok(())
+        });
+        mock.expect_timeout_policy().returning(|_| Ok(TimeoutPolicy::default()));
+        mock.expect_reset_refs().returning(|_, _| Ok(()));
+
+        /* The response is built only from the message sent over the channel */
+        mock.expect_get_refs().never();
+
+        mock.expect_update_tree().with(predicate::eq(id(0))).times(1).returning(|_| Ok(()));
+        mock.expect_focus_session().with(predicate::eq(id(0))).times(1).returning(|_| Ok(()));
+        mock.expect_emit()
+            .withf(|event| matches!(event, Event::TreeDelta(delta) if delta.num_nodes() == 3))
+            .times(1)
+            .returning(|_| Ok(()));
+
+        /* The session pauses at the breakpoint, then resumes */
+        mock.expect_emit()
+            .withf(|event| matches!(event, Event::SessionPaused(_) | Event::SessionResumed(_)))
+            .times(2)
+            .returning(|_| Ok(()));
+
+        let client: blocking::Client = tracked_client(mock);
+
+        /* Perform POST request to '/api/remote/tree/delta' */
+        let response: blocking::LocalResponse = client
+            .post(rocket::uri!(super::post_tree_delta))
+            .header(http::ContentType::JSON)
+            .body(delta_tree::test::json())
+            .dispatch();
+
+        /* Assert that POST succeeded, resuming with the skips and references */
+        assert_eq!(response.status(), http::Status::Ok);
+
+        let body: serde_json::Value = response.into_json().expect("Response should be JSON");
+        assert_eq!(body["sessionId"], id(0).to_string());
+        assert_eq!(body["mergedNodes"], 3);
+        assert_eq!(body["skipBreakpoint"], 2);
+        assert_eq!(body["newRefs"], serde_json::json!([[0, "b"]]));
+    }
+
+    /* Post a debuggable tree, continuing from its breakpoint with code */
+    fn post_breakpoint_tree(code: BreakpointCode, protocol_version: Option<u32>) -> serde_json::Value {
+        let mut mock = MockStateManager::new();
+        mock.expect_next_session_id().returning(|| Ok(id(0)));
+        mock.expect_set_tree().returning(|_| Ok(()));
+        mock.expect_update_tree().returning(|_| Ok(()));
+        mock.expect_emit().returning(|_| Ok(()));
+        mock.expect_reset_refs().returning(|_, _| Ok(()));
+        mock.expect_matches_rules().returning(|_| Ok(None));
+        mock.expect_timeout_policy().returning(|_| Ok(TimeoutPolicy::default()));
+        mock.expect_watched_changes().returning(|_, _| Ok(None));
+        mock.expect_new_transmitter().returning(move |_, tx| {
+            tx.send(BreakpointMessage { code: code.clone(), refs: Vec::new() }).expect("Receiver should be waiting");
+            Ok(())
+        });
+
+        let client: blocking::Client = tracked_client(mock);
+
+        let version: String = protocol_version.map_or(String::new(), |version| format!(r#","protocolVersion":{version}"#));
+        let body: String = parsley_tree::test::json()
+            .replace(r#""isDebuggable":false"#, &format!(r#""isDebuggable":true{version}"#));
+
+        let response: blocking::LocalResponse = client
+            .post(rocket::uri!(super::post_tree))
+            .header(http::ContentType::JSON)
+            .body(body)
+            .dispatch();
+
+        assert_eq!(response.status(), http::Status::Ok);
+        response.into_json().expect("Response should be JSON")
+    }
+
+    #[test]
+    fn unmatched_breakpoint_auto_continues() {
+        let mut mock = MockStateManager::new();
+        mock.expect_next_session_id().returning(|| Ok(id(0)));
+
+        /* The tree is kept without being shown, saved or waited on */
+        mock.expect_matches_rules().times(1).returning(|_| Ok(Some(false)));
+        mock.expect_watched_changes().returning(|_, _| Ok(None));
+        mock.expect_record_tree().withf(|tree| tree.get_session_id() == id(0)).times(1).returning(|_| Ok(()));
+
+        let client: blocking::Client = tracked_client(mock);
+
+        let response: blocking::LocalResponse = client
+            .post(rocket::uri!(super::post_tree))
+            .header(http::ContentType::JSON)
+            .body(parsley_tree::test::json().replace(r#""isDebuggable":false"#, r#""isDebuggable":true"#))
+            .dispatch();
+
+        assert_eq!(response.status(), http::Status::Ok);
+
+        let body: serde_json::Value = response.into_json().expect("Response should be JSON");
+        assert_eq!(body["skipBreakpoint"], 0);
+        assert_eq!(body["newRefs"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn watched_ref_change_pauses_unmatched_breakpoint() {
+        let mut mock = MockStateManager::new();
+        mock.expect_merge_delta()
+            .with(predicate::eq(id(0)), predicate::always())
+            .times(1)
+            .returning(|_, delta| merged(delta).map(|merged| MergedDelta {
+                matches_rules: Some(false),
+                changes: Some(vec![RefChange { address: 0, previous: Some(String::from("z")), current: Some(String::from("a")) }]),
+                ..merged
+            }));
+
+        /* The tree is stored and waited on despite no rule matching */
+        mock.expect_emit()
+            .withf(|event| matches!(event, Event::WatchpointHit(_)))
+            .times(1)
+            .returning(|_| Ok(()));
+        mock.expect_emit()
+            .withf(|event| matches!(event, Event::TreeDelta(_)))
+            .times(1)
+            .returning(|_| Ok(()));
+        mock.expect_emit()
+            .withf(|event| matches!(event, Event::SessionPaused(_) | Event::SessionResumed(_)))
+            .returning(|_| Ok(()));
+        mock.expect_update_tree().times(1).returning(|_| Ok(()));
+        mock.expect_focus_session().returning(|_| Ok(()));
+        mock.expect_timeout_policy().returning(|_| Ok(TimeoutPolicy::default()));
+        mock.expect_reset_refs().returning(|_, _| Ok(()));
+        mock.expect_new_transmitter().times(1).returning(|_, tx| {
+            tx.send(BreakpointMessage { code: BreakpointCode::Skip(1), refs: Vec::new() }).expect("Receiver should be waiting");
+            Ok(())
+        });
+
+        let client: blocking::Client = tracked_client(mock);
+
+        let response: blocking::LocalResponse = client
+            .post(rocket::uri!(super::post_tree_delta))
+            .header(http::ContentType::JSON)
+            .body(delta_tree::test::json())
+            .dispatch();
+
+        assert_eq!(response.status(), http::Status::Ok);
+
+        let body: serde_json::Value = response.into_json().expect("Response should be JSON");
+        assert_eq!(body["skipBreakpoint"], 1);
+    }
+
+    #[test]
+    fn unmatched_delta_auto_continues() {
+        let mut mock = MockStateManager::new();
+
+        /* The delta stays merged, but is not shown, saved or waited on */
+        mock.expect_merge_delta()
+            .times(1)
+            .returning(|_, delta| merged(delta).map(|merged| MergedDelta { matches_rules: Some(false), ..merged }));
+
+        let client: blocking::Client = tracked_client(mock);
+
+        let response: blocking::LocalResponse = client
+            .post(rocket::uri!(super::post_tree_delta))
+            .header(http::ContentType::JSON)
+            .body(delta_tree::test::json())
+            .dispatch();
+
+        assert_eq!(response.status(), http::Status::Ok);
+
+        let body: serde_json::Value = response.into_json().expect("Response should be JSON");
+        assert_eq!(body["skipBreakpoint"], 0);
+        assert_eq!(body["mergedNodes"], 3);
+        assert_eq!(body["newRefs"], serde_json::json!([[0, "a"]]));
+    }
+
+    #[test]
+    fn unversioned_client_gets_plain_skip_count() {
+        let body = post_breakpoint_tree(BreakpointCode::SkipAll, None);
+
+        assert_eq!(body["skipBreakpoint"], -2);
+        assert!(body.get("breakpointCommand").is_none());
+
+        /* Step commands stop older clients at the next breakpoint */
+        let body = post_breakpoint_tree(BreakpointCode::UntilFailure, None);
+
+        assert_eq!(body["skipBreakpoint"], 0);
+        assert!(body.get("breakpointCommand").is_none());
+    }
+
+    #[test]
+    fn headless_breakpoint_continues_by_policy() {
+        let dir: std::path::PathBuf = std::env::temp_dir().join(format!("dill-headless-policy-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("Could not create trees directory");
+
+        let state = ServerState::headless(HeadlessState::new(dir.clone()), BreakpointPolicy::SkipAll);
+        let client = blocking::Client::tracked(launch::build(state, &Options::default())).expect("Could not launch rocket");
+
+        let response: blocking::LocalResponse = client
+            .post(rocket::uri!(super::post_tree))
+            .header(http::ContentType::JSON)
+            .body(parsley_tree::test::json().replace(r#""isDebuggable":false"#, r#""isDebuggable":true"#))
+            .dispatch();
+
+        assert_eq!(response.status(), http::Status::Ok);
+
+        /* Nothing continued the breakpoint but the policy, after the tree was written */
+        let body: serde_json::Value = response.into_json().expect("Response should be JSON");
+        assert_eq!(body["skipBreakpoint"], -2);
+
+        assert_eq!(std::fs::read_dir(&dir).expect("Trees directory should be readable").count(), 1);
+
+        std::fs::remove_dir_all(dir).expect("Could not remove trees directory");
+    }
+
+    #[test]
+    fn versioned_client_gets_structured_command() {
+        let body = post_breakpoint_tree(BreakpointCode::UntilParser(String::from("digit")), Some(2));
+
+        assert_eq!(body["skipBreakpoint"], 0);
+        assert_eq!(
+            body["breakpointCommand"],
+            serde_json::json!({ "version": 2, "kind": "untilParser", "name": "digit" })
+        );
+
+        let body = post_breakpoint_tree(BreakpointCode::UntilOffset(12), Some(2));
+        assert_eq!(body["breakpointCommand"], serde_json::json!({ "version": 2, "kind": "untilOffset", "offset": 12 }));
+    }
+
+    #[test]
+    fn forgotten_breakpoint_times_out_with_action() {
+        let mut mock = MockStateManager::new();
+        mock.expect_next_session_id().returning(|| Ok(id(0)));
+        mock.expect_set_tree().returning(|_| Ok(()));
+        mock.expect_update_tree().returning(|_| Ok(()));
+        mock.expect_reset_refs().returning(|_, _| Ok(()));
+        mock.expect_matches_rules().returning(|_| Ok(None));
+        mock.expect_watched_changes().returning(|_, _| Ok(None));
+        mock.expect_timeout_policy()
+            .with(predicate::eq(Some(id(0))))
+            .returning(|_| Ok(TimeoutPolicy::new(Some(1), TimeoutAction::SkipAll)));
+
+        /* The user never continues, so the transmitter is only used by the timeout */
+        let waiting: Arc<Mutex<Option<SkipsSender>>> = Arc::new(Mutex::new(None));
+        let transmitter: Arc<Mutex<Option<SkipsSender>>> = Arc::clone(&waiting);
+        mock.expect_new_transmitter().returning(move |_, tx| {
+            *waiting.lock().expect("Lock should not be poisoned") = Some(tx);
+            Ok(())
+        });
+        mock.expect_transmit_breakpoint_skips()
+            .with(predicate::eq(id(0)), predicate::eq(BreakpointCode::SkipAll))
+            .times(1)
+            .returning(move |_, code| {
+                let tx: SkipsSender = transmitter.lock().expect("Lock should not be poisoned").take().expect("Breakpoint should be waiting");
+                tx.send(BreakpointMessage { code, refs: Vec::new() }).map_err(|_| StateError::ChannelError)
+            });
+
+        /* The frontend is warned before the timeout */
+        mock.expect_emit()
+            .withf(|event| matches!(event, Event::BreakpointTimeoutWarning(_)))
+            .times(1)
+            .returning(|_| Ok(()));
+        mock.expect_emit().returning(|_| Ok(()));
+
+        let client: blocking::Client = tracked_client(mock);
+
+        let response: blocking::LocalResponse = client
+            .post(rocket::uri!(super::post_tree))
+            .header(http::ContentType::JSON)
+            .body(parsley_tree::test::json().replace(r#""isDebuggable":false"#, r#""isDebuggable":true"#))
+            .dispatch();
+
+        assert_eq!(response.status(), http::Status::Ok);
+
+        let body: serde_json::Value = response.into_json().expect("Response should be JSON");
+        assert_eq!(body["skipBreakpoint"], -2);
+        assert_eq!(body["timeoutAction"], "skipAll");
+    }
+
+    #[test]
+    fn continued_breakpoint_names_no_timeout_action() {
+        let body = post_breakpoint_tree(BreakpointCode::Skip(1), None);
+
+        assert!(body.get("timeoutAction").is_none());
+    }
+
+    #[test]
+    fn post_tree_delta_for_unknown_session_fails() {
+        let mut mock = MockStateManager::new();
+        mock.expect_merge_delta().returning(|_, _| Err(StateError::TreeNotFound));
+
+        let client: blocking::Client = tracked_client(mock);
+
+        /* Perform POST request to '/api/remote/tree/delta' before any tree was posted */
+        let response: blocking::LocalResponse = client
+            .post(rocket::uri!(super::post_tree_delta))
+            .header(http::ContentType::JSON)
+            .body(delta_tree::test::json())
+            .dispatch();
+
+        /* Assert that POST failed without waiting at a breakpoint */
+        assert_eq!(response.status(), http::Status::NotFound);
+    }
+
     #[test]
     fn empty_post_fails() {
         let mock = MockStateManager::new();
@@ -256,25 +892,319 @@ pub mod test {
             .dispatch();
 
         /* Assert that POST failed */
+        assert_eq!(response.status(), http::Status::UnsupportedMediaType);
+    }
+
+    #[test]
+    fn failed_store_is_json_error() {
+        let mut mock = MockStateManager::new();
+        mock.expect_next_session_id().returning(|| Ok(id(4)));
+        mock.expect_set_tree().returning(|_| Ok(()));
+        mock.expect_update_tree().returning(|_| Err(UpdateTreeError::WriteTreeFailed));
+
+        let client: blocking::Client = tracked_client(mock);
+
+        let response: blocking::LocalResponse = client
+            .post(rocket::uri!(super::post_tree))
+            .header(http::ContentType::JSON)
+            .body(parsley_tree::test::json())
+            .dispatch();
+
+        /* Assert that POST failed with an error naming the session, rather than panicking */
+        assert_eq!(response.status(), http::Status::InternalServerError);
+
+        let body: serde_json::Value = response.into_json().expect("Error should be JSON");
+        assert_eq!(body["code"], "storeFailed");
+        assert_eq!(body["sessionId"], id(4).to_string());
+    }
+
+    #[test]
+    fn closed_breakpoint_is_json_error() {
+        let mut mock = MockStateManager::new();
+        mock.expect_next_session_id().returning(|| Ok(id(0)));
+        mock.expect_matches_rules().returning(|_| Ok(None));
+        mock.expect_watched_changes().returning(|_, _| Ok(None));
+        mock.expect_reset_refs().returning(|_, _| Ok(()));
+        mock.expect_set_tree().returning(|_| Ok(()));
+        mock.expect_update_tree().returning(|_| Ok(()));
+        mock.expect_timeout_policy().returning(|_| Ok(TimeoutPolicy::default()));
+        mock.expect_emit().returning(|_| Ok(()));
+
+        /* Dropping the transmitter closes the breakpoint before the user continues */
+        mock.expect_new_transmitter().returning(|_, tx| {
+            drop(tx);
+            Ok(())
+        });
+
+        let client: blocking::Client = tracked_client(mock);
+
+        let response: blocking::LocalResponse = client
+            .post(rocket::uri!(super::post_tree))
+            .header(http::ContentType::JSON)
+            .body(parsley_tree::test::json().replace(r#""isDebuggable":false"#, r#""isDebuggable":true"#))
+            .dispatch();
+
+        assert_eq!(response.status(), http::Status::ServiceUnavailable);
+
+        let body: serde_json::Value = response.into_json().expect("Error should be JSON");
+        assert_eq!(body["code"], "breakpointClosed");
+        assert_eq!(body["sessionId"], id(0).to_string());
+    }
+
+    #[test]
+    fn get_missing_session_is_not_found() {
+        let mut mock = MockStateManager::new();
+        mock.expect_get_tree().with(predicate::eq(id(7))).returning(|_| Err(StateError::TreeNotFound));
+
+        let client: blocking::Client = tracked_client(mock);
+
+        let response: blocking::LocalResponse =
+            client.get(rocket::uri!(super::get_tree(Some(ClientSessionId::Opaque(id(7)))))).dispatch();
+
         assert_eq!(response.status(), http::Status::NotFound);
+
+        let body: serde_json::Value = response.into_json().expect("Error should be JSON");
+        assert_eq!(body["code"], "sessionNotFound");
+        assert_eq!(body["sessionId"], id(7).to_string());
+    }
+
+    #[test]
+    fn new_session_is_listed_with_client() {
+        let mut mock = MockStateManager::new();
+        mock.expect_next_session_id().times(1).returning(|| Ok(id(5)));
+
+        let client: blocking::Client = tracked_client(mock);
+
+        let response = client.post(rocket::uri!(super::new_session))
+            .header(http::ContentType::JSON)
+            .header(http::Header::new("User-Agent", "parsley-remote-view"))
+            .dispatch();
+        assert_eq!(response.status(), http::Status::Ok);
+
+        let sessions: serde_json::Value = client.get(rocket::uri!(super::get_sessions))
+            .dispatch()
+            .into_json()
+            .expect("Sessions should be JSON");
+
+        assert_eq!(sessions[0]["sessionId"], id(5).to_string());
+        assert_eq!(sessions[0]["status"], "connected");
+        assert_eq!(sessions[0]["client"]["userAgent"], "parsley-remote-view");
+    }
+
+    #[test]
+    fn new_session_handshake_stores_metadata() {
+        let mut mock = MockStateManager::new();
+        mock.expect_next_session_id().times(1).returning(|| Ok(id(6)));
+
+        let client: blocking::Client = tracked_client(mock);
+
+        let response: serde_json::Value = client.post(rocket::uri!(super::new_session))
+            .header(http::ContentType::JSON)
+            .body(r#"{"parsleyVersion":"4.6.0","protocolVersion":2,"program":"ExprSpec","sourceRoots":["src/main/scala"]}"#)
+            .dispatch()
+            .into_json()
+            .expect("Response should be JSON");
+
+        /* Clients older than opaque ids are given an integer in their place */
+        assert_eq!(response["sessionId"], 0);
+        assert_eq!(response["serverVersion"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(response["features"]["deltas"], true);
+        assert!(response["features"]["breakpointKinds"].as_array().is_some_and(|kinds| kinds.contains(&serde_json::json!("untilParser"))));
+        assert!(response["sessionToken"].as_str().is_some_and(|token| !token.is_empty()));
+
+        let sessions: serde_json::Value = client.get(rocket::uri!(super::get_sessions))
+            .dispatch()
+            .into_json()
+            .expect("Sessions should be JSON");
+
+        assert_eq!(sessions[0]["metadata"]["program"], "ExprSpec");
+        assert_eq!(sessions[0]["metadata"]["sourceRoots"][0], "src/main/scala");
+    }
+
+    #[test]
+    fn versioned_handshake_gets_opaque_id() {
+        let mut mock = MockStateManager::new();
+        mock.expect_next_session_id().times(1).returning(|| Ok(id(6)));
+
+        let client: blocking::Client = tracked_client(mock);
+
+        let response: serde_json::Value = client.post(rocket::uri!(super::new_session))
+            .header(http::ContentType::JSON)
+            .body(r#"{"protocolVersion":3}"#)
+            .dispatch()
+            .into_json()
+            .expect("Response should be JSON");
+
+        assert_eq!(response["sessionId"], id(6).to_string());
+        assert_eq!(response["features"]["protocolVersion"], 3);
+    }
+
+    #[test]
+    fn legacy_client_keeps_integer_id() {
+        let mut mock = MockStateManager::new();
+        mock.expect_next_session_id().times(1).returning(|| Ok(id(1)));
+        mock.expect_set_tree()
+            .withf(|tree| tree.get_session_id() == id(1))
+            .times(1)
+            .returning(|_| Ok(()));
+        mock.expect_update_tree().returning(|_| Ok(()));
+        mock.expect_emit().returning(|_| Ok(()));
+
+        let client: blocking::Client = tracked_client(mock);
+
+        let handshake: serde_json::Value = client.post(rocket::uri!(super::new_session))
+            .header(http::ContentType::JSON)
+            .dispatch()
+            .into_json()
+            .expect("Response should be JSON");
+
+        assert_eq!(handshake["sessionId"], 0);
+        assert!(handshake.get("sessionToken").is_none());
+
+        /* The integer the client was given is resolved to the session it stands for */
+        let response: serde_json::Value = client.post(rocket::uri!(super::post_tree))
+            .header(http::ContentType::JSON)
+            .body(parsley_tree::test::json().replace(r#""input""#, r#""sessionId":0,"input""#))
+            .dispatch()
+            .into_json()
+            .expect("Response should be JSON");
+
+        assert_eq!(response["sessionId"], 0);
+    }
+
+    #[test]
+    fn invalid_handshake_is_bad_request() {
+        let mut mock = MockStateManager::new();
+        mock.expect_next_session_id().never();
+
+        let client: blocking::Client = tracked_client(mock);
+
+        let response: blocking::LocalResponse = client.post(rocket::uri!(super::new_session))
+            .header(http::ContentType::JSON)
+            .body(r#"{"protocolVersion":"two"}"#)
+            .dispatch();
+
+        assert_eq!(response.status(), http::Status::BadRequest);
+    }
+
+    /* Post a tree to a session opened with a handshake, sending token if given */
+    fn post_with_session_token(token: Option<&str>) -> (http::Status, serde_json::Value) {
+        let mut mock = MockStateManager::new();
+        /* The posted tree has no id, so is allocated the handshake's id again */
+        mock.expect_next_session_id().returning(|| Ok(id(0)));
+        mock.expect_set_tree().never();
+
+        let client: blocking::Client = tracked_client(mock);
+
+        client.post(rocket::uri!(super::new_session))
+            .header(http::ContentType::JSON)
+            .body("{}")
+            .dispatch();
+
+        let mut request = client.post(rocket::uri!(super::post_tree))
+            .header(http::ContentType::JSON)
+            .body(parsley_tree::test::json());
+        if let Some(token) = token {
+            request = request.header(http::Header::new(SESSION_TOKEN_HEADER, token.to_owned()));
+        }
+
+        let response: blocking::LocalResponse = request.dispatch();
+        (response.status(), response.into_json().expect("Error should be JSON"))
+    }
+
+    #[test]
+    fn wrong_session_token_is_forbidden() {
+        let (status, body) = post_with_session_token(Some("not-the-token"));
+
+        assert_eq!(status, http::Status::Forbidden);
+        assert_eq!(body["code"], "sessionTokenMismatch");
+    }
+
+    #[test]
+    fn missing_session_token_is_forbidden() {
+        let (status, body) = post_with_session_token(None);
+
+        assert_eq!(status, http::Status::Forbidden);
+        assert_eq!(body["code"], "sessionTokenMismatch");
+    }
+
+    #[test]
+    fn closed_session_still_needs_token() {
+        let mut mock = MockStateManager::new();
+        mock.expect_next_session_id().returning(|| Ok(id(0)));
+        mock.expect_set_tree().times(1).returning(|_| Ok(()));
+        mock.expect_update_tree().returning(|_| Ok(()));
+        mock.expect_emit().returning(|_| Ok(()));
+
+        let client: blocking::Client = tracked_client(mock);
+
+        let handshake: serde_json::Value = client.post(rocket::uri!(super::new_session))
+            .header(http::ContentType::JSON)
+            .body(r#"{"protocolVersion":3}"#)
+            .dispatch()
+            .into_json()
+            .expect("Handshake should be JSON");
+        let token: String = handshake["sessionToken"].as_str().expect("Handshake should issue a token").to_owned();
+
+        /* Posting a tree that is not debuggable finishes the session */
+        let tree: String = parsley_tree::test::json().replace(r#""isDebuggable":false"#, &format!(r#""isDebuggable":false,"sessionId":"{}","protocolVersion":3"#, id(0)));
+        let post = |token: Option<&str>| {
+            let mut request = client.post(rocket::uri!(super::post_tree))
+                .header(http::ContentType::JSON)
+                .body(&tree);
+            if let Some(token) = token {
+                request = request.header(http::Header::new(SESSION_TOKEN_HEADER, token.to_owned()));
+            }
+            request.dispatch().status()
+        };
+
+        assert_eq!(post(Some(&token)), http::Status::Ok);
+        let sessions: serde_json::Value = client.get(rocket::uri!(super::get_sessions)).dispatch().into_json().expect("Sessions should be JSON");
+        assert_eq!(sessions, serde_json::json!([]));
+
+        assert_eq!(post(None), http::Status::Forbidden);
     }
 
     #[test]
     fn get_returns_tree() {
         let mut mock = MockStateManager::new();
-        mock.expect_get_tree().returning(|| Ok(debug_tree::test::tree()));
-        mock.expect_next_session_id().returning(|| Ok(-1));
+        mock.expect_displayed_session_id().returning(|| Ok(id(0)));
+        mock.expect_get_tree().with(predicate::eq(id(0))).returning(|_| Ok(debug_tree::test::tree()));
+        mock.expect_next_session_id().returning(|| Ok(id(0)));
 
         let client: blocking::Client = tracked_client(mock);
 
         /* Perform GET request to '/api/remote/tree' */
         let response: blocking::LocalResponse =
-            client.get(rocket::uri!(super::get_tree)).dispatch();
+            client.get(rocket::uri!(super::get_tree(_))).dispatch();
 
         /* Assert that GET succeeded */
         assert_eq!(response.status(), http::Status::Ok);
     }
 
+    #[test]
+    fn get_returns_requested_session_tree() {
+        let mut mock = MockStateManager::new();
+        mock.expect_get_tree().with(predicate::eq(id(3))).times(1).returning(|_| Ok(debug_tree::test::nested_tree()));
+
+        let client: blocking::Client = tracked_client(mock);
+
+        /* Perform GET request to '/api/remote/tree?session_id=3' */
+        let response: blocking::LocalResponse =
+            client.get(rocket::uri!(super::get_tree(Some(ClientSessionId::Opaque(id(3)))))).dispatch();
+
+        /* Assert that GET succeeded with the tree of the requested session */
+        assert_eq!(response.status(), http::Status::Ok);
+        assert_eq!(
+            response
+                .into_string()
+                .expect("get_tree response is not a String")
+                .split_whitespace()
+                .collect::<String>(),
+            debug_tree::test::nested_json()
+        );
+    }
+
     #[test]
     fn get_returns_posted_tree() {
         let mut mock = MockStateManager::new();
@@ -283,10 +1213,11 @@ pub mod test {
             .with(predicate::eq(debug_tree::test::tree()))
             .returning(|_| Ok(()));
 
-        mock.expect_get_tree().returning(|| Ok(debug_tree::test::tree()));
-        mock.expect_next_session_id().returning(|| Ok(-1));
+        mock.expect_displayed_session_id().returning(|| Ok(id(0)));
+        mock.expect_get_tree().returning(|_| Ok(debug_tree::test::tree()));
+        mock.expect_next_session_id().returning(|| Ok(id(0)));
 
-        mock.expect_update_tree().returning(|_, _| Ok(()));
+        mock.expect_update_tree().returning(|_| Ok(()));
 
         mock.expect_emit().withf(|expected| &Event::NewTree == expected)
             .returning(|_| Ok(()));
@@ -297,7 +1228,7 @@ pub mod test {
         let post_response: blocking::LocalResponse = client
             .post(rocket::uri!(super::post_tree))
             .header(http::ContentType::JSON)
-            .body(&parsley_tree::test::json())
+            .body(parsley_tree::test::json())
             .dispatch();
 
         /* Assert that POST succeeded */
@@ -305,7 +1236,7 @@ pub mod test {
 
         /* Perform GET request to '/api/remote/tree' */
         let get_response: blocking::LocalResponse =
-            client.get(rocket::uri!(super::get_tree)).dispatch();
+            client.get(rocket::uri!(super::get_tree(_))).dispatch();
 
         /* Assert that GET succeeded */
         assert_eq!(get_response.status(), http::Status::Ok);
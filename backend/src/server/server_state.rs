@@ -1,25 +1,43 @@
 use std::path::PathBuf;
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
 
 use crate::events::Event;
-use crate::state::{StateError, StateManager};
-use crate::trees::{DebugTree, DebugNode};
-use crate::state::state_manager::{BreakpointCode, DirectoryKind, UpdateTreeError, ambassador_impl_StateManager};
-use super::TokioMutex;
+use crate::state::{BreakpointPolicy, StateError, StateManager};
+use crate::trees::{ClientSessionId, DebugTree, DebugNode, ParsleyTreeDelta, SessionId, SessionMetadata};
+use crate::state::state_manager::{BreakpointCode, BreakpointMessage, DirectoryKind, MergedDelta, UpdateTreeError, ambassador_impl_StateManager};
+use crate::state::breakpoint_rules::{BreakpointRule, RuleCondition};
+use crate::state::timeline::{RefChange, SnapshotDiff, SnapshotSummary};
+use crate::state::breakpoint_timeout::{TimeoutAction, TimeoutPolicy, TimeoutWarning};
+use crate::state::breakpoint_queue::{FocusPolicy, QueuedBreakpoint};
+use super::sessions::{self, ClientInfo, SessionInfo, SessionRegistry, SessionStatus};
 
-pub type SkipsSender = rocket::tokio::sync::oneshot::Sender<i32>;
-type SkipsReceiver = rocket::tokio::sync::oneshot::Receiver<i32>;
+pub type SkipsSender = rocket::tokio::sync::oneshot::Sender<BreakpointMessage>;
+pub type SkipsReceiver = rocket::tokio::sync::oneshot::Receiver<BreakpointMessage>;
 
 
 /* Wrapper for StateManager implementation used for Rocket server state management */
-pub struct ServerState(Box<dyn StateManager>, TokioMutex<HashMap<i32, SkipsReceiver>>);
+/* Also holds the receivers of breakpoints being waited at, the status of every session */
+/* and, when headless, the policy breakpoints are continued by instead of waiting for a user */
+pub struct ServerState(Box<dyn StateManager>, Mutex<HashMap<SessionId, SkipsReceiver>>, Arc<Mutex<SessionRegistry>>, Option<BreakpointPolicy>);
 
 /* Delegate StateManager implementations to wrapped StateManager */
 #[ambassador::delegate_to_methods]
 #[delegate(StateManager, target_ref = "inner")]
 impl ServerState {
     pub fn new<S: StateManager>(state: S) -> Self {
-        ServerState(Box::new(state), TokioMutex::new(HashMap::new()))
+        ServerState(Box::new(state), Mutex::new(HashMap::new()), Arc::new(Mutex::new(SessionRegistry::new())), None)
+    }
+
+    /* Server state with no user to continue breakpoints, so every one is continued by the policy */
+    pub fn headless<S: StateManager>(state: S, policy: BreakpointPolicy) -> Self {
+        ServerState(Box::new(state), Mutex::new(HashMap::new()), Arc::new(Mutex::new(SessionRegistry::new())), Some(policy))
+    }
+
+    /* Track sessions in a registry shared with earlier servers, so their tokens and ids outlive a restart */
+    pub(super) fn with_registry(self, registry: Arc<Mutex<SessionRegistry>>) -> Self {
+        ServerState(self.0, self.1, registry, self.3)
     }
 
     /* Get wrapped StateManager implementation */
@@ -29,15 +47,178 @@ impl ServerState {
 }
 
 impl ServerState {
-    pub fn new_receiver(&self, session_id: i32, rx: SkipsReceiver) -> Option<SkipsReceiver> {
-        self.1.try_lock().ok().and_then(|mut map| map.insert(session_id, rx))
+    /* Receivers are only held while inserting or removing one, never across an await */
+    /* A panic elsewhere cannot leave the map half changed, so a poisoned lock is still used */
+    fn receivers(&self) -> MutexGuard<'_, HashMap<SessionId, SkipsReceiver>> {
+        self.1.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn new_receiver(&self, session_id: SessionId, rx: SkipsReceiver) -> Option<SkipsReceiver> {
+        self.receivers().insert(session_id, rx)
+    }
+
+    /* Take ownership of a session's receiver, to await it alongside other events */
+    pub fn take_receiver(&self, session_id: SessionId) -> Option<SkipsReceiver> {
+        self.receivers().remove(&session_id)
     }
 
-    pub async fn receive_breakpoint_skips(&self, session_id: i32) -> Option<i32> {
-        let rx = self.1.lock().await.remove(&session_id);
+    pub async fn receive_breakpoint_skips(&self, session_id: SessionId) -> Option<(BreakpointMessage, Option<TimeoutAction>)> {
+        let rx = self.take_receiver(session_id);
         match rx {
-            Some(rx) => rx.await.ok(),
+            Some(rx) => self.wait_at_breakpoint(session_id, rx).await,
             None => None,
         }
     }
+
+    /* Wait for the user to continue from a breakpoint, applying the session's timeout policy if they do not */
+    /* Returns the message the parser is resumed with, and the timeout action if it was applied */
+    pub async fn wait_at_breakpoint(&self, session_id: SessionId, mut rx: SkipsReceiver) -> Option<(BreakpointMessage, Option<TimeoutAction>)> {
+        /* The breakpoint's tree is stored by now, so a headless server continues straight away */
+        if let Some(policy) = self.3 {
+            let _ = self.transmit_breakpoint_skips(session_id, policy.code());
+            return rx.await.ok().map(|message| (message, None));
+        }
+
+        let policy: TimeoutPolicy = self.timeout_policy(Some(session_id)).unwrap_or_default();
+
+        let Some((until_warning, lead)) = policy.deadlines() else {
+            return rx.await.ok().map(|message| (message, None));
+        };
+
+        if let Ok(message) = rocket::tokio::time::timeout(until_warning, &mut rx).await {
+            return message.ok().map(|message| (message, None));
+        }
+
+        let _ = self.emit(Event::BreakpointTimeoutWarning(&TimeoutWarning::new(session_id, lead, policy.action())));
+
+        if let Ok(message) = rocket::tokio::time::timeout(lead, &mut rx).await {
+            return message.ok().map(|message| (message, None));
+        }
+
+        /* The user may have continued just as the timeout passed, in which case their message is received instead */
+        let applied: Option<TimeoutAction> = self.transmit_breakpoint_skips(session_id, policy.action().code())
+            .ok()
+            .map(|_| policy.action());
+
+        rx.await.ok().map(|message| (message, applied))
+    }
+
+    /* Drop a session's receiver if it has not been taken to be awaited */
+    pub fn drop_receiver(&self, session_id: SessionId) {
+        self.receivers().remove(&session_id);
+    }
+
+    /* Drop both ends of a breakpoint no one is waiting at, so the session is no longer debuggable */
+    pub fn release_breakpoint(&self, session_id: SessionId) {
+        self.drop_receiver(session_id);
+
+        /* Removes the transmitter, even though the parser can no longer receive the command */
+        let _ = self.transmit_breakpoint_skips(session_id, BreakpointCode::Terminate);
+    }
+
+    /* Record a session being seen with a status, telling the frontend if it paused, resumed or closed */
+    pub fn set_session_status(&self, session_id: SessionId, status: SessionStatus, client: Option<ClientInfo>) {
+        let Ok((previous, info)) = self.2.lock().map(|mut registry| registry.record(session_id, status, client)) else {
+            return;
+        };
+
+        if let Some(event) = sessions::transition_event(previous, &info) {
+            let _ = self.emit(event);
+        }
+    }
+
+    /* Record a session connecting with its client's metadata, returning the token issued to it */
+    pub fn open_session(&self, session_id: SessionId, client: ClientInfo, metadata: Option<SessionMetadata>) -> Option<String> {
+        self.set_session_status(session_id, SessionStatus::Connected, Some(client));
+
+        self.2.lock()
+            .map(|mut registry| registry.open(session_id, metadata))
+            .unwrap_or_default()
+    }
+
+    /* Record a session connecting over a WebSocket, returning the token issued to it */
+    /* The connection owns its session, so it is always issued a token for requests made over HTTP */
+    pub fn open_socket_session(&self, session_id: SessionId, client: ClientInfo) -> Option<String> {
+        self.set_session_status(session_id, SessionStatus::Connected, Some(client));
+        self.2.lock().ok().map(|mut registry| registry.issue_token(session_id))
+    }
+
+    /* Store the metadata a session's client describes itself with after connecting */
+    pub fn describe_session(&self, session_id: SessionId, metadata: SessionMetadata) {
+        if let Ok(mut registry) = self.2.lock() {
+            registry.describe(session_id, metadata);
+        }
+    }
+
+    /* Record a heartbeat from a session's client, returning false if the session is not known */
+    pub fn session_heartbeat(&self, session_id: SessionId) -> bool {
+        self.2.lock().map(|mut registry| registry.beat(session_id)).unwrap_or(false)
+    }
+
+    /* Set how long a client that sends heartbeats may go without one */
+    pub fn set_heartbeat_timeout(&self, timeout: Duration) {
+        if let Ok(mut registry) = self.2.lock() {
+            registry.set_heartbeat_timeout(timeout);
+        }
+    }
+
+    /* Wait until a session's client stops sending heartbeats, which never happens for one that sends none */
+    /* HTTP clients cannot otherwise be seen going away, as Rocket finishes a request's handler regardless */
+    pub async fn heartbeats_stopped(&self, session_id: SessionId) {
+        loop {
+            let Ok((stopped, timeout)) = self.2.lock().map(|registry| (registry.heartbeats_stopped(session_id), registry.heartbeat_timeout())) else {
+                return std::future::pending().await;
+            };
+
+            if stopped {
+                return;
+            }
+            rocket::tokio::time::sleep((timeout / 2).max(Duration::from_millis(100))).await;
+        }
+    }
+
+    /* Metadata a session's client connected with, if it shook hands */
+    pub fn session_metadata(&self, session_id: SessionId) -> Option<SessionMetadata> {
+        self.2.lock().ok().and_then(|registry| registry.metadata(session_id))
+    }
+
+    /* Whether a request sent the token issued to the session, if it was issued one */
+    pub fn session_token_matches(&self, session_id: SessionId, token: Option<&str>) -> bool {
+        self.2.lock().map(|registry| registry.token_matches(session_id, token)).unwrap_or(false)
+    }
+
+    /* The session a client's id refers to, allocating one if it gave none or an integer not seen before */
+    pub fn resolve_session_id(&self, session_id: Option<ClientSessionId>) -> Result<SessionId, StateError> {
+        let mut registry = self.2.lock().map_err(|_| StateError::LockFailed)?;
+
+        let session_id: Option<ClientSessionId> = session_id.filter(|session_id| !session_id.is_unset());
+        if let Some(known) = session_id.and_then(|session_id| registry.find(session_id)) {
+            return Ok(known);
+        }
+
+        let allocated: SessionId = self.next_session_id()?;
+        if let Some(ClientSessionId::Legacy(alias)) = session_id {
+            registry.bind_legacy(alias, allocated);
+        }
+        Ok(allocated)
+    }
+
+    /* The session a client's id refers to, if it is known */
+    pub fn find_session_id(&self, session_id: ClientSessionId) -> Result<Option<SessionId>, StateError> {
+        self.2.lock()
+            .map(|registry| registry.find(session_id))
+            .map_err(|_| StateError::LockFailed)
+    }
+
+    /* A session's id in the form a client understands, an integer for clients that predate opaque ids */
+    pub fn client_session_id(&self, session_id: SessionId, legacy: bool) -> ClientSessionId {
+        self.2.lock()
+            .map(|mut registry| registry.client_id(session_id, legacy))
+            .unwrap_or(ClientSessionId::Opaque(session_id))
+    }
+
+    /* Every session the server has seen */
+    pub fn sessions(&self) -> Vec<SessionInfo> {
+        self.2.lock().map(|registry| registry.sessions()).unwrap_or_default()
+    }
 }
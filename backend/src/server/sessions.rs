@@ -0,0 +1,591 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::time::Duration;
+
+use rocket::form::{self, FromFormField, ValueField};
+use rocket::http::uri::fmt::{Formatter, Query, UriDisplay};
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+
+use super::auth::tokens_match;
+use super::ServerState;
+use crate::events::Event;
+use crate::state::timeline::now_millis;
+use crate::trees::{ClientSessionId, SessionId, SessionMetadata};
+
+/* Header a client sends the token it was given in the newSession handshake in */
+pub const SESSION_TOKEN_HEADER: &str = "X-Dill-Session-Token";
+
+/* Time a client that sends heartbeats may go without one, unless set when Dill is launched */
+pub const DEFAULT_HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(15);
+
+
+/* Stage of a session's life, as seen by the server */
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SessionStatus {
+    Connected,      /* Allocated a session id, but no tree has been posted */
+    Paused,         /* Waiting for the user at a breakpoint */
+    Running,        /* Parsing between breakpoints */
+    Finished,       /* Posted its final tree, or was terminated */
+    Disconnected,   /* Went away while waiting at a breakpoint */
+}
+
+impl SessionStatus {
+    /* Whether the session is being debugged, so can still be stopped at a breakpoint */
+    fn is_debugging(self) -> bool {
+        matches!(self, SessionStatus::Paused | SessionStatus::Running)
+    }
+
+    fn is_closed(self) -> bool {
+        matches!(self, SessionStatus::Finished | SessionStatus::Disconnected)
+    }
+}
+
+/* Who sent a session's requests */
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClientInfo {
+    #[serde(skip_serializing_if = "Option::is_none")] address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")] user_agent: Option<String>,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClientInfo {
+    type Error = Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(ClientInfo {
+            address: req.client_ip().map(|ip| ip.to_string()),
+            user_agent: req.headers().get_one("User-Agent").map(String::from),
+        })
+    }
+}
+
+/* Token a request claims its session with, if it sent one */
+pub struct SessionToken(pub Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for SessionToken {
+    type Error = Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(SessionToken(req.headers().get_one(SESSION_TOKEN_HEADER).map(String::from)))
+    }
+}
+
+/* Session id given in a query, which older clients give as an integer */
+#[rocket::async_trait]
+impl<'v> FromFormField<'v> for ClientSessionId {
+    fn from_value(field: ValueField<'v>) -> form::Result<'v, Self> {
+        field.value.parse().map_err(|_| form::Error::validation("not a session id").into())
+    }
+}
+
+/* Session id in a generated URI, written as the client would send it */
+impl UriDisplay<Query> for ClientSessionId {
+    fn fmt(&self, f: &mut Formatter<'_, Query>) -> std::fmt::Result {
+        f.write_value(self.to_string())
+    }
+}
+
+rocket::http::impl_from_uri_param_identity!([Query] ClientSessionId);
+
+/* What the server last saw of a session */
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionInfo {
+    session_id: SessionId,
+    status: SessionStatus,
+    last_seen: u64,                                                             /* Milliseconds since the epoch */
+    #[serde(skip_serializing_if = "Option::is_none")] client: Option<ClientInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")] metadata: Option<SessionMetadata>,    /* Sent in the newSession handshake */
+    #[serde(skip)] heartbeats: bool,                                            /* Whether its client sends heartbeats, so is gone once they stop */
+}
+
+impl SessionInfo {
+    pub fn session_id(&self) -> SessionId {
+        self.session_id
+    }
+
+    pub fn status(&self) -> SessionStatus {
+        self.status
+    }
+}
+
+
+/* Integer ids given to sessions of clients that predate opaque ids, in both directions */
+struct LegacyIds {
+    sessions: HashMap<i32, SessionId>,
+    aliases: HashMap<SessionId, i32>,
+    next: i32,
+}
+
+impl LegacyIds {
+    fn new() -> Self {
+        LegacyIds { sessions: HashMap::new(), aliases: HashMap::new(), next: 0 }
+    }
+
+    fn bind(&mut self, alias: i32, session_id: SessionId) {
+        self.sessions.insert(alias, session_id);
+        self.aliases.insert(session_id, alias);
+    }
+
+    /* A session's integer id, allocating the lowest unused one if it has none */
+    fn alias(&mut self, session_id: SessionId) -> i32 {
+        if let Some(alias) = self.aliases.get(&session_id) {
+            return *alias;
+        }
+
+        while self.sessions.contains_key(&self.next) {
+            self.next += 1;
+        }
+
+        let alias: i32 = self.next;
+        self.bind(alias, session_id);
+        alias
+    }
+}
+
+
+/* Record of the sessions being tracked, and the tokens issued to them, keyed by session id */
+/* Tokens outlive their session's record, so a closed session's id cannot be posted to without its token */
+pub struct SessionRegistry {
+    sessions: HashMap<SessionId, SessionInfo>,
+    tokens: HashMap<SessionId, String>,
+    legacy: LegacyIds,
+    heartbeat_timeout: Duration,    /* Time a session sending heartbeats may go without one */
+}
+
+impl SessionRegistry {
+    /* Initialise an empty registry */
+    pub fn new() -> Self {
+        SessionRegistry { sessions: HashMap::new(), tokens: HashMap::new(), legacy: LegacyIds::new(), heartbeat_timeout: DEFAULT_HEARTBEAT_TIMEOUT }
+    }
+
+    /* Record a session being seen with a status, returning its previous status and what is now recorded */
+    /* The client is kept from earlier requests if none is given */
+    /* A closed session's record is forgotten, so it is not closed again nor listed once its closing is reported */
+    pub fn record(&mut self, session_id: SessionId, status: SessionStatus, client: Option<ClientInfo>) -> (Option<SessionStatus>, SessionInfo) {
+        let previous: Option<SessionStatus> = self.sessions.get(&session_id).map(SessionInfo::status);
+        let info: &mut SessionInfo = self.sessions.entry(session_id)
+            .or_insert(SessionInfo { session_id, status, last_seen: 0, client: None, metadata: None, heartbeats: false });
+
+        info.status = status;
+        info.last_seen = now_millis();
+        if client.is_some() {
+            info.client = client;
+        }
+
+        let info: SessionInfo = info.clone();
+        if status.is_closed() {
+            self.sessions.remove(&session_id);
+        }
+
+        (previous, info)
+    }
+
+    /* Store the metadata a session's client connected with, returning the token it is issued */
+    /* Older clients send no handshake and never send a token, so are issued none */
+    pub fn open(&mut self, session_id: SessionId, metadata: Option<SessionMetadata>) -> Option<String> {
+        self.describe(session_id, metadata?);
+        Some(self.issue_token(session_id))
+    }
+
+    /* Store the metadata a session's client describes itself with */
+    pub fn describe(&mut self, session_id: SessionId, metadata: SessionMetadata) {
+        if let Some(info) = self.sessions.get_mut(&session_id) {
+            info.metadata = Some(metadata);
+        }
+    }
+
+    /* Issue a session a new token, which must then be sent by every request acting for it */
+    pub fn issue_token(&mut self, session_id: SessionId) -> String {
+        let token: String = uuid::Uuid::new_v4().simple().to_string();
+        self.tokens.insert(session_id, token.clone());
+        token
+    }
+
+    /* Record a heartbeat from a session's client, which is then expected to keep sending them */
+    /* Returns false if the session is not known, e.g. because it was taken to have disconnected */
+    pub fn beat(&mut self, session_id: SessionId) -> bool {
+        let Some(info) = self.sessions.get_mut(&session_id) else {
+            return false;
+        };
+
+        info.heartbeats = true;
+        info.last_seen = now_millis();
+        true
+    }
+
+    pub fn set_heartbeat_timeout(&mut self, timeout: Duration) {
+        self.heartbeat_timeout = timeout;
+    }
+
+    pub fn heartbeat_timeout(&self) -> Duration {
+        self.heartbeat_timeout
+    }
+
+    /* Whether a session's client sends heartbeats but has sent none within the timeout */
+    pub fn heartbeats_stopped(&self, session_id: SessionId) -> bool {
+        let timeout: u64 = u64::try_from(self.heartbeat_timeout.as_millis()).unwrap_or(u64::MAX);
+
+        self.sessions.get(&session_id)
+            .is_some_and(|info| info.heartbeats && now_millis().saturating_sub(info.last_seen) > timeout)
+    }
+
+    pub fn metadata(&self, session_id: SessionId) -> Option<SessionMetadata> {
+        self.sessions.get(&session_id).and_then(|info| info.metadata.clone())
+    }
+
+    /* Whether a request may act for a session: once a token is issued it must be sent */
+    pub fn token_matches(&self, session_id: SessionId, token: Option<&str>) -> bool {
+        match (self.tokens.get(&session_id), token) {
+            (None, _) => true,
+            (Some(expected), Some(token)) => tokens_match(token.as_bytes(), expected.as_bytes()),
+            (Some(_), None) => false,
+        }
+    }
+
+    /* The session a client's id refers to, if it is known */
+    /* Opaque ids are always known, as a client may post its first tree to an id it was given elsewhere */
+    pub fn find(&self, session_id: ClientSessionId) -> Option<SessionId> {
+        match session_id {
+            ClientSessionId::Opaque(session_id) => Some(session_id),
+            ClientSessionId::Legacy(alias) => self.legacy.sessions.get(&alias).copied(),
+        }
+    }
+
+    /* Refer to a session by the integer a client chose for it */
+    pub fn bind_legacy(&mut self, alias: i32, session_id: SessionId) {
+        self.legacy.bind(alias, session_id)
+    }
+
+    /* A session's id in the form a client understands */
+    pub fn client_id(&mut self, session_id: SessionId, legacy: bool) -> ClientSessionId {
+        match legacy {
+            true => ClientSessionId::Legacy(self.legacy.alias(session_id)),
+            false => ClientSessionId::Opaque(session_id),
+        }
+    }
+
+    /* Every session seen, in order of id */
+    pub fn sessions(&self) -> Vec<SessionInfo> {
+        let mut sessions: Vec<SessionInfo> = self.sessions.values().cloned().collect();
+        sessions.sort_by_key(SessionInfo::session_id);
+        sessions
+    }
+}
+
+/* Event telling the frontend a session paused, resumed or closed, if its status changed in a way it shows */
+pub fn transition_event(previous: Option<SessionStatus>, info: &SessionInfo) -> Option<Event<'_>> {
+    match (previous, info.status) {
+        (Some(SessionStatus::Paused), SessionStatus::Paused) => None,
+        (_, SessionStatus::Paused) => Some(Event::SessionPaused(info)),
+        (Some(SessionStatus::Paused), SessionStatus::Running) => Some(Event::SessionResumed(info)),
+        (Some(previous), status) if previous.is_debugging() && status.is_closed() => Some(Event::SessionClosed(info)),
+        _ => None,
+    }
+}
+
+
+/* Held by a request waiting at a breakpoint, dropped once its client's WebSocket closes or heartbeats stop */
+/* Dropped while still waiting, the session's channels are released and it is marked disconnected */
+pub struct PausedSession<'a> {
+    state: &'a ServerState,
+    session_id: SessionId,
+    waiting: bool,
+}
+
+impl<'a> PausedSession<'a> {
+    pub fn new(state: &'a ServerState, session_id: SessionId) -> Self {
+        PausedSession { state, session_id, waiting: true }
+    }
+
+    /* The user continued, so the session runs on, unless it was terminated */
+    pub fn resume(mut self, terminated: bool) {
+        self.waiting = false;
+
+        let status: SessionStatus = if terminated { SessionStatus::Finished } else { SessionStatus::Running };
+        self.state.set_session_status(self.session_id, status, None);
+    }
+
+    /* The breakpoint was closed within Dill, so the session cannot be continued */
+    pub fn close(mut self) {
+        self.waiting = false;
+        self.state.set_session_status(self.session_id, SessionStatus::Finished, None);
+    }
+}
+
+impl Drop for PausedSession<'_> {
+    fn drop(&mut self) {
+        if self.waiting {
+            self.state.release_breakpoint(self.session_id);
+            self.state.set_session_status(self.session_id, SessionStatus::Disconnected, None);
+        }
+    }
+}
+
+
+#[cfg(test)]
+pub mod test {
+
+    /* Session registry unit testing */
+
+    use std::net::Ipv4Addr;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use rocket::fairing::AdHoc;
+    use rocket::Shutdown;
+    use rocket::tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use rocket::tokio::net::TcpStream;
+    use rocket::tokio::sync::oneshot;
+
+    use super::{transition_event, ClientInfo, PausedSession, SessionRegistry, SessionStatus};
+    use crate::events::Event;
+    use crate::options::Options;
+    use crate::server::{launch, ServerState, SkipsSender};
+    use crate::state::MockStateManager;
+    use crate::state::breakpoint_timeout::TimeoutPolicy;
+    use crate::trees::{parsley_tree, ClientSessionId, SessionMetadata};
+    use crate::trees::session_id::test::id;
+
+    #[test]
+    fn recorded_session_keeps_client() {
+        let mut registry = SessionRegistry::new();
+        let client = ClientInfo { address: Some(String::from("127.0.0.1")), user_agent: None };
+
+        let (previous, _) = registry.record(id(2), SessionStatus::Connected, Some(client.clone()));
+        assert_eq!(previous, None);
+
+        let (previous, info) = registry.record(id(2), SessionStatus::Paused, None);
+        assert_eq!(previous, Some(SessionStatus::Connected));
+        assert_eq!(info.status(), SessionStatus::Paused);
+        assert_eq!(info.client, Some(client));
+        assert!(info.last_seen > 0);
+
+        assert_eq!(registry.sessions().len(), 1);
+    }
+
+    #[test]
+    fn closed_session_is_forgotten() {
+        let mut registry = SessionRegistry::new();
+
+        registry.record(id(0), SessionStatus::Connected, None);
+        registry.open(id(0), Some(SessionMetadata::default())).expect("Handshake should issue a token");
+        registry.record(id(0), SessionStatus::Paused, None);
+
+        let (previous, info) = registry.record(id(0), SessionStatus::Disconnected, None);
+        assert_eq!((previous, info.status()), (Some(SessionStatus::Paused), SessionStatus::Disconnected));
+        assert!(registry.sessions().is_empty());
+        assert_eq!(registry.metadata(id(0)), None);
+
+        /* Its token is still required */
+        assert!(!registry.token_matches(id(0), None));
+
+        /* Closing it again is not reported again */
+        let (previous, info) = registry.record(id(0), SessionStatus::Finished, None);
+        assert!(transition_event(previous, &info).is_none());
+        assert!(registry.sessions().is_empty());
+    }
+
+    #[test]
+    fn opened_session_keeps_metadata_and_token() {
+        let mut registry = SessionRegistry::new();
+        let metadata = SessionMetadata { program: Some(String::from("ExprSpec")), ..SessionMetadata::default() };
+
+        registry.record(id(4), SessionStatus::Connected, None);
+        let token: String = registry.open(id(4), Some(metadata.clone())).expect("Handshake should issue a token");
+
+        assert_eq!(registry.metadata(id(4)), Some(metadata));
+        assert_eq!(registry.sessions()[0].metadata.as_ref().and_then(|metadata| metadata.program.as_deref()), Some("ExprSpec"));
+
+        assert!(registry.token_matches(id(4), Some(&token)));
+        assert!(!registry.token_matches(id(4), None));
+        assert!(!registry.token_matches(id(4), Some("not-the-token")));
+
+        /* Sessions that never shook hands are issued no token, so need none */
+        registry.record(id(5), SessionStatus::Connected, None);
+        assert_eq!(registry.open(id(5), None), None);
+        assert!(registry.token_matches(id(5), None));
+    }
+
+    #[test]
+    fn legacy_ids_map_to_sessions() {
+        let mut registry = SessionRegistry::new();
+
+        /* Sessions are given an integer alias once a client that needs one is answered */
+        assert_eq!(registry.client_id(id(0), true), ClientSessionId::Legacy(0));
+        assert_eq!(registry.find(ClientSessionId::Legacy(0)), Some(id(0)));
+
+        /* Integers a client chose itself are kept, and not handed to other sessions */
+        registry.bind_legacy(1, id(1));
+        assert_eq!(registry.find(ClientSessionId::Legacy(1)), Some(id(1)));
+
+        assert_eq!(registry.client_id(id(2), true), ClientSessionId::Legacy(2));
+        assert_eq!(registry.client_id(id(2), false), ClientSessionId::Opaque(id(2)));
+
+        assert_eq!(registry.find(ClientSessionId::Legacy(5)), None);
+        assert_eq!(registry.find(ClientSessionId::Opaque(id(5))), Some(id(5)));
+    }
+
+    #[test]
+    fn only_shown_transitions_have_events() {
+        let mut registry = SessionRegistry::new();
+
+        let (previous, info) = registry.record(id(0), SessionStatus::Finished, None);
+        assert!(transition_event(previous, &info).is_none());
+
+        let (previous, info) = registry.record(id(1), SessionStatus::Paused, None);
+        assert!(matches!(transition_event(previous, &info), Some(Event::SessionPaused(_))));
+
+        let (previous, info) = registry.record(id(1), SessionStatus::Running, None);
+        assert!(matches!(transition_event(previous, &info), Some(Event::SessionResumed(_))));
+
+        let (previous, info) = registry.record(id(1), SessionStatus::Disconnected, None);
+        assert!(matches!(transition_event(previous, &info), Some(Event::SessionClosed(_))));
+    }
+
+    #[test]
+    fn heartbeats_stop_only_once_sent() {
+        let mut registry = SessionRegistry::new();
+        registry.set_heartbeat_timeout(Duration::ZERO);
+
+        registry.record(id(0), SessionStatus::Paused, None);
+        registry.record(id(1), SessionStatus::Paused, None);
+        std::thread::sleep(Duration::from_millis(2));
+
+        /* Clients that never send heartbeats are not expected to */
+        assert!(!registry.heartbeats_stopped(id(0)));
+
+        assert!(registry.beat(id(1)));
+        std::thread::sleep(Duration::from_millis(2));
+        assert!(registry.heartbeats_stopped(id(1)));
+
+        assert!(!registry.beat(id(2)));
+    }
+
+    /* Send a request on a connection of its own, which is closed once the response is read or it is dropped */
+    /* Any extra headers are given as lines ending in CRLF */
+    pub async fn send(port: u16, request: &str, headers: &str, body: &str) -> TcpStream {
+        let mut stream: TcpStream = TcpStream::connect((Ipv4Addr::LOCALHOST, port)).await.expect("Could not connect to server");
+        let message: String = format!(
+            "{request} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n{headers}Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        );
+
+        stream.write_all(message.as_bytes()).await.expect("Could not send request");
+        stream
+    }
+
+    pub async fn response(port: u16, request: &str, body: &str) -> String {
+        response_with(port, request, "", body).await
+    }
+
+    pub async fn response_with(port: u16, request: &str, headers: &str, body: &str) -> String {
+        let mut response = String::new();
+        send(port, request, headers, body).await.read_to_string(&mut response).await.expect("Could not read response");
+        response
+    }
+
+    /* Launch a server on a free port, returning the port once it is listening */
+    pub async fn serve(mock: MockStateManager, options: &Options) -> (u16, Shutdown) {
+        let (port_tx, port_rx) = oneshot::channel::<u16>();
+        let rocket = launch::build(ServerState::new(mock), options)
+            .attach(AdHoc::on_liftoff("Test port", move |rocket| Box::pin(async move {
+                let _ = port_tx.send(rocket.config().port);
+            })))
+            .ignite().await.expect("Could not ignite rocket");
+
+        let shutdown: Shutdown = rocket.shutdown();
+        rocket::tokio::spawn(rocket.launch());
+        (port_rx.await.expect("Rocket should lift off"), shutdown)
+    }
+
+    /* Status of the first session the server lists, if it lists any */
+    pub async fn listed_status(port: u16) -> Option<String> {
+        let response: String = response(port, "GET /api/remote/sessions", "").await;
+        let (_, body) = response.split_once("\r\n\r\n").expect("Response should have a body");
+
+        let sessions: serde_json::Value = serde_json::from_str(body).expect("Sessions should be JSON");
+        sessions[0]["status"].as_str().map(String::from)
+    }
+
+    #[rocket::async_test]
+    async fn killed_http_client_is_disconnected_once_heartbeats_stop() {
+        let pending: Arc<Mutex<Option<SkipsSender>>> = Arc::new(Mutex::new(None));
+        let held: Arc<Mutex<Option<SkipsSender>>> = Arc::clone(&pending);
+        let released: Arc<Mutex<Option<SkipsSender>>> = Arc::clone(&pending);
+
+        let mut mock = MockStateManager::new();
+        mock.expect_next_session_id().returning(|| Ok(id(3)));
+        mock.expect_set_tree().returning(|_| Ok(()));
+        mock.expect_update_tree().returning(|_| Ok(()));
+        mock.expect_reset_refs().returning(|_, _| Ok(()));
+        mock.expect_matches_rules().returning(|_| Ok(None));
+        mock.expect_watched_changes().returning(|_, _| Ok(None));
+        mock.expect_timeout_policy().returning(|_| Ok(TimeoutPolicy::default()));
+        mock.expect_new_transmitter().times(1).returning(move |_, tx| {
+            *held.lock().expect("Sender lock poisoned") = Some(tx);
+            Ok(())
+        });
+
+        /* The pending transmitter is dropped, so the session is no longer debuggable */
+        mock.expect_transmit_breakpoint_skips().times(1).returning(move |_, _| {
+            released.lock().expect("Sender lock poisoned").take();
+            Ok(())
+        });
+        mock.expect_emit()
+            .withf(|event| matches!(event, Event::SessionClosed(info) if info.status() == SessionStatus::Disconnected))
+            .times(1)
+            .returning(|_| Ok(()));
+        mock.expect_emit()
+            .withf(|event| matches!(event, Event::NewTree | Event::SessionPaused(_)))
+            .returning(|_| Ok(()));
+
+        let options = Options { address: Some(Ipv4Addr::LOCALHOST.into()), port: Some(0), heartbeat_timeout: Some(1), ..Options::default() };
+        let (port, shutdown) = serve(mock, &options).await;
+
+        /* An older client, issued integer id 0, which starts sending heartbeats */
+        assert!(response(port, "POST /api/remote/newSession", "").await.starts_with("HTTP/1.1 200"));
+        assert!(response(port, "POST /api/remote/heartbeat?session_id=0", "").await.starts_with("HTTP/1.1 204"));
+
+        let tree: String = parsley_tree::test::json().replace(r#""isDebuggable":false"#, r#""isDebuggable":true,"sessionId":0"#);
+        let connection: TcpStream = send(port, "POST /api/remote/tree", "", &tree).await;
+
+        while listed_status(port).await.as_deref() != Some("paused") {
+            rocket::tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        /* The client is killed at the breakpoint, so its connection closes and its heartbeats stop */
+        drop(connection);
+
+        let disconnected = async {
+            while listed_status(port).await.is_some() {
+                rocket::tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        };
+        rocket::tokio::time::timeout(Duration::from_secs(5), disconnected).await.expect("Session should be disconnected");
+        assert!(pending.lock().expect("Sender lock poisoned").is_none());
+
+        /* Its heartbeats are refused, so a client that was only slow learns it was disconnected */
+        assert!(response(port, "POST /api/remote/heartbeat?session_id=0", "").await.starts_with("HTTP/1.1 404"));
+
+        shutdown.notify();
+    }
+
+    #[test]
+    fn resumed_paused_session_is_running() {
+        let mut mock = MockStateManager::new();
+        mock.expect_transmit_breakpoint_skips().never();
+        mock.expect_emit().returning(|_| Ok(()));
+
+        let state = ServerState::new(mock);
+        state.set_session_status(id(3), SessionStatus::Paused, None);
+
+        PausedSession::new(&state, id(3)).resume(false);
+
+        assert_eq!(state.sessions()[0].status(), SessionStatus::Running);
+    }
+}
@@ -0,0 +1,284 @@
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use rocket::{Orbit, Rocket, Shutdown};
+use rocket::tokio::sync::MutexGuard as TokioMutexGuard;
+
+use super::TokioMutex;
+use super::auth::APP_IDENTIFIER;
+use super::sessions::SessionRegistry;
+use crate::options::Options;
+
+
+/* Number of ports after the configured port tried when falling back */
+const MAX_PORT_ATTEMPTS: u16 = 10;
+
+
+/* Lifecycle of the Rocket server, sent to the frontend as the server-status event */
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum ServerStatus {
+    Starting,
+    Listening {
+        address: IpAddr,
+        port: u16,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        socket: Option<PathBuf>,
+    },
+    Failed { reason: String },
+}
+
+/* Reasons the Rocket server could not be launched */
+#[derive(Debug)]
+pub enum LaunchError {
+    ConfigInvalid,
+    PortInUse(u16),
+    NoFreePort(u16),
+    SocketUnavailable(PathBuf, String),
+    LaunchFailed(String),
+}
+
+impl fmt::Display for LaunchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LaunchError::ConfigInvalid => write!(f, "Server configuration is invalid"),
+            LaunchError::PortInUse(port) => write!(f, "Port {port} is already in use"),
+            LaunchError::NoFreePort(port) => write!(f, "No free port found from {port} to {}", port.saturating_add(MAX_PORT_ATTEMPTS)),
+            LaunchError::SocketUnavailable(path, reason) => write!(f, "Could not listen on socket {}: {reason}", path.display()),
+            LaunchError::LaunchFailed(reason) => write!(f, "{reason}"),
+        }
+    }
+}
+
+impl std::error::Error for LaunchError {}
+
+impl From<rocket::Error> for LaunchError {
+    fn from(err: rocket::Error) -> Self {
+        LaunchError::LaunchFailed(err.to_string())
+    }
+}
+
+
+/* Contents of the discovery file, which lets clients find the port and socket Dill is listening on */
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Discovery {
+    pub address: IpAddr,
+    pub port: u16,
+    pub pid: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub socket: Option<PathBuf>,
+}
+
+impl Discovery {
+    pub fn write(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        std::fs::write(path, serde_json::to_string(self)?)
+    }
+}
+
+/* Discovery file named after this process, so instances run at once do not overwrite each other's */
+/* It is kept in the user's data directory where there is one, which other users cannot write to */
+fn default_discovery_path() -> PathBuf {
+    let file: String = format!("dill-server-{}.json", std::process::id());
+
+    dirs::data_dir()
+        .map(|dir| dir.join(APP_IDENTIFIER))
+        .unwrap_or_else(std::env::temp_dir)
+        .join(file)
+}
+
+
+/* Find the configured port, or with fallback the first free port after it */
+pub fn free_port(address: IpAddr, port: u16, fallback: bool) -> Result<u16, LaunchError> {
+    let is_free = |port: u16| TcpListener::bind((address, port)).is_ok();
+
+    /* Port 0 asks the OS for any free port, so is always available */
+    if port == 0 || is_free(port) {
+        return Ok(port);
+    }
+
+    match fallback {
+        true => (1..=MAX_PORT_ATTEMPTS)
+            .filter_map(|offset| port.checked_add(offset))
+            .find(|port| is_free(*port))
+            .ok_or(LaunchError::NoFreePort(port)),
+        false => Err(LaunchError::PortInUse(port)),
+    }
+}
+
+
+/* Address the server can be reached on locally, as a server bound to every interface cannot be connected to */
+fn loopback(address: IpAddr, port: u16) -> SocketAddr {
+    match address {
+        IpAddr::V4(address) if address.is_unspecified() => SocketAddr::from((Ipv4Addr::LOCALHOST, port)),
+        IpAddr::V6(address) if address.is_unspecified() => SocketAddr::from((Ipv6Addr::LOCALHOST, port)),
+        address => SocketAddr::from((address, port)),
+    }
+}
+
+/* Bind the Unix domain socket, relaying it to the server until it shuts down */
+#[cfg(unix)]
+fn serve_socket(path: &Path, target: SocketAddr, shutdown: Shutdown) -> std::io::Result<()> {
+    let listener = super::unix_socket::bind(path)?;
+    rocket::tokio::spawn(super::unix_socket::relay(listener, path.to_path_buf(), target, shutdown));
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn serve_socket(_path: &Path, _target: SocketAddr, _shutdown: Shutdown) -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "Unix domain sockets are not supported on this platform"))
+}
+
+
+type StatusListener = Box<dyn Fn(&ServerStatus) + Send + Sync>;
+
+/* Unsynchronised ServerControl */
+struct ServerControlInternal {
+    options: Options,                   /* Options the server is launched with, updated on restart */
+    status: ServerStatus,               /* Most recent status, for frontends that missed the event */
+    shutdown: Option<Shutdown>,         /* Handle to stop the running server */
+}
+
+/* Shared handle to the Rocket server's lifecycle, used to report its status and to restart it */
+#[derive(Clone)]
+pub struct ServerControl {
+    inner: Arc<Mutex<ServerControlInternal>>,
+    on_status: Arc<StatusListener>,     /* Called with every status change, e.g. to emit an event */
+    running: Arc<TokioMutex<()>>,       /* Held while a server runs, so a restart waits for the port to be released */
+    sessions: Arc<Mutex<SessionRegistry>>, /* Sessions tracked by every server launched, kept across restarts */
+}
+
+impl ServerControl {
+    /* Create a server control, calling on_status whenever the server's status changes */
+    pub fn new(options: Options, on_status: impl Fn(&ServerStatus) + Send + Sync + 'static) -> Self {
+        ServerControl {
+            inner: Arc::new(Mutex::new(ServerControlInternal {
+                options,
+                status: ServerStatus::Starting,
+                shutdown: None,
+            })),
+            on_status: Arc::new(Box::new(on_status)),
+            running: Arc::new(TokioMutex::new(())),
+            sessions: Arc::new(Mutex::new(SessionRegistry::new())),
+        }
+    }
+
+    /* Poisoning only happens if a listener panicked, after which the state is still usable */
+    fn inner(&self) -> MutexGuard<'_, ServerControlInternal> {
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /* Wait for any previous server to stop, holding the guard while the next one runs */
+    pub(super) async fn running(&self) -> TokioMutexGuard<'_, ()> {
+        self.running.lock().await
+    }
+
+    /* Registry of sessions shared by the servers this control launches */
+    pub(super) fn sessions(&self) -> Arc<Mutex<SessionRegistry>> {
+        Arc::clone(&self.sessions)
+    }
+
+    pub fn options(&self) -> Options {
+        self.inner().options.clone()
+    }
+
+    pub fn status(&self) -> ServerStatus {
+        self.inner().status.clone()
+    }
+
+    pub fn set_status(&self, status: ServerStatus) {
+        self.inner().status = status.clone();
+        (self.on_status)(&status);
+    }
+
+    /* Record a server that has started listening, serving the socket and writing the discovery file for clients */
+    /* The socket is bound before the server is reported, so clients are never pointed at one that does not exist */
+    /* Fails if the socket cannot be bound when it is the only way clients are meant to reach the server */
+    pub fn listening(&self, rocket: &Rocket<Orbit>) -> Result<(), LaunchError> {
+        self.inner().shutdown = Some(rocket.shutdown());
+
+        let options: Options = self.options();
+        let socket: Option<PathBuf> = match options.socket {
+            Some(path) => match serve_socket(&path, loopback(rocket.config().address, rocket.config().port), rocket.shutdown()) {
+                Ok(()) => Some(path),
+                Err(err) if options.socket_only => return Err(LaunchError::SocketUnavailable(path, err.to_string())),
+                Err(err) => {
+                    log::warn!("Could not listen on socket {}: {err}", path.display());
+                    None
+                },
+            },
+            None => None,
+        };
+
+        let discovery = Discovery { address: rocket.config().address, port: rocket.config().port, pid: std::process::id(), socket };
+        let discovery_path: PathBuf = self.discovery_path();
+
+        if let Err(err) = discovery.write(&discovery_path) {
+            log::warn!("Could not write discovery file {}: {err}", discovery_path.display());
+        }
+
+        self.set_status(ServerStatus::Listening { address: discovery.address, port: discovery.port, socket: discovery.socket });
+        Ok(())
+    }
+
+    /* Path of the discovery file written once the server is listening */
+    pub fn discovery_path(&self) -> PathBuf {
+        self.inner().options.discovery_file.clone()
+            .unwrap_or_else(default_discovery_path)
+    }
+
+    /* Stop the running server, launching it next on the given port */
+    pub fn stop(&self, port: Option<u16>) {
+        let mut control = self.inner();
+
+        if port.is_some() {
+            control.options.port = port;
+        }
+
+        if let Some(shutdown) = control.shutdown.take() {
+            shutdown.notify();
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+
+    /* Server status unit testing */
+
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener};
+
+    use super::{free_port, loopback, LaunchError, ServerStatus};
+
+    #[test]
+    fn status_serialises_with_tag() {
+        let status = ServerStatus::Listening { address: IpAddr::V4(Ipv4Addr::LOCALHOST), port: 17484, socket: None };
+
+        assert_eq!(
+            serde_json::to_string(&status).expect("Could not serialise status"),
+            r#"{"status":"listening","address":"127.0.0.1","port":17484}"#
+        );
+    }
+
+    #[test]
+    fn unspecified_address_is_reached_on_loopback() {
+        assert_eq!(loopback(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 17484), SocketAddr::from((Ipv4Addr::LOCALHOST, 17484)));
+        assert_eq!(loopback(IpAddr::V4(Ipv4Addr::LOCALHOST), 17484), SocketAddr::from((Ipv4Addr::LOCALHOST, 17484)));
+    }
+
+    #[test]
+    fn taken_port_falls_back_to_next_free_port() {
+        let address = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let listener = TcpListener::bind((address, 0)).expect("Could not bind a port");
+        let taken: u16 = listener.local_addr().expect("Listener has no address").port();
+
+        assert!(matches!(free_port(address, taken, false), Err(LaunchError::PortInUse(port)) if port == taken));
+        assert!(free_port(address, taken, true).is_ok_and(|port| port > taken));
+    }
+}
@@ -0,0 +1,114 @@
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use rocket::tokio::{self, io::copy_bidirectional};
+use rocket::tokio::net::{TcpStream, UnixListener, UnixStream};
+use rocket::Shutdown;
+
+use crate::files::{self, FileError};
+
+
+/* Bind the Unix domain socket the remote API is served on */
+pub fn bind(path: &Path) -> io::Result<UnixListener> {
+    /* A socket left by a previous run that did not exit cleanly would stop binding, but no other file is replaced */
+    match files::delete_socket(path) {
+        Err(FileError::NotASocket) => return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} exists and is not a socket", path.display()))),
+        Err(_) => return Err(io::Error::new(io::ErrorKind::PermissionDenied, format!("Could not remove the old socket at {}", path.display()))),
+        Ok(()) => (),
+    }
+
+    UnixListener::bind(path)
+}
+
+/* Serve the remote API on a bound Unix domain socket until the server shuts down */
+/* Rocket only listens on TCP, so each connection is relayed to the server's loopback port, */
+/* which keeps the routes, WebSockets and breakpoint semantics identical */
+pub async fn relay(listener: UnixListener, path: PathBuf, target: SocketAddr, shutdown: Shutdown) {
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => { tokio::spawn(relay_connection(stream, target)); },
+                Err(err) => log::warn!("Could not accept connection on {}: {err}", path.display()),
+            },
+            _ = shutdown.clone() => break,
+        }
+    }
+
+    let _ = files::delete_socket(&path);
+}
+
+async fn relay_connection(mut unix: UnixStream, target: SocketAddr) {
+    match TcpStream::connect(target).await {
+        /* Either side closing ends the relay, which is not an error */
+        Ok(mut tcp) => { let _ = copy_bidirectional(&mut unix, &mut tcp).await; },
+        Err(err) => log::warn!("Could not relay socket connection to {target}: {err}"),
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+
+    /* Unix socket unit testing */
+
+    use std::net::SocketAddr;
+    use std::path::PathBuf;
+
+    use rocket::tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use rocket::tokio::net::{TcpListener, UnixStream};
+
+    use crate::options::Options;
+    use crate::server::launch;
+    use crate::server::ServerState;
+    use crate::state::MockStateManager;
+
+    #[rocket::async_test]
+    async fn socket_connections_reach_the_server() {
+        let path: PathBuf = std::env::temp_dir().join(format!("dill-socket-test-{}.sock", std::process::id()));
+
+        /* Stand in for the server with an echoing TCP listener */
+        let server: TcpListener = TcpListener::bind("127.0.0.1:0").await.expect("Could not bind a port");
+        let target: SocketAddr = server.local_addr().expect("Listener has no address");
+
+        rocket::tokio::spawn(async move {
+            let (mut stream, _) = server.accept().await.expect("Could not accept");
+            let mut buf = [0u8; 4];
+            stream.read_exact(&mut buf).await.expect("Could not read");
+            stream.write_all(&buf).await.expect("Could not write");
+        });
+
+        /* Any ignited rocket provides a shutdown handle */
+        let rocket = launch::build(ServerState::new(MockStateManager::new()), &Options::default())
+            .ignite().await.expect("Could not ignite rocket");
+        let shutdown = rocket.shutdown();
+
+        let listener = super::bind(&path).expect("Could not bind socket");
+        let relay = rocket::tokio::spawn(super::relay(listener, path.clone(), target, shutdown.clone()));
+
+        let mut client: UnixStream = UnixStream::connect(&path).await.expect("Could not connect to socket");
+        client.write_all(b"dill").await.expect("Could not write");
+
+        let mut echoed = [0u8; 4];
+        client.read_exact(&mut echoed).await.expect("Could not read");
+        assert_eq!(&echoed, b"dill");
+
+        /* The socket is removed when the server shuts down */
+        shutdown.notify();
+        relay.await.expect("Relay panicked");
+        assert!(!path.exists());
+    }
+
+    #[rocket::async_test]
+    async fn bind_does_not_replace_other_files() {
+        let path: PathBuf = std::env::temp_dir().join(format!("dill-socket-file-{}.sock", std::process::id()));
+        std::fs::write(&path, "not a socket").expect("Could not write file");
+
+        let err = super::bind(&path).expect_err("Binding should refuse the file");
+
+        assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read_to_string(&path).expect("File should be kept"), "not a socket");
+
+        std::fs::remove_file(path).expect("Could not remove file");
+    }
+}
@@ -0,0 +1,450 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use rocket::data::{ByteUnit, Data, Limits};
+use rocket::fairing::AdHoc;
+use rocket::http::ContentType;
+use rocket::serde::json::Json;
+use rocket::{post, put};
+
+use super::auth::Authorised;
+use super::data::{self, BodyEncoding, BodyFormat, TreeBodyError};
+use super::error::{ApiError, ErrorCode};
+use super::request::{self, PostTreeResponse};
+use super::sessions::{ClientInfo, SessionToken};
+use super::ServerState;
+use crate::trees::{ClientSessionId, ParsleyTree};
+
+
+/* Name of the limit on a reassembled upload, applied both as sent and once decompressed */
+pub const UPLOAD_LIMIT: &str = "upload";
+
+/* Time after its last chunk that an abandoned upload is discarded, unless set with --upload-expiry */
+pub const DEFAULT_UPLOAD_EXPIRY: Duration = Duration::from_secs(300);
+
+/* Most uploads held at once, their chunks together are also held to the upload limit */
+pub const MAX_UPLOADS: usize = 16;
+
+/* Expose routes for mounting during launch */
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![begin_upload, put_chunk, commit_upload]
+}
+
+
+/* A tree being uploaded in chunks */
+struct Upload {
+    session_id: Option<ClientSessionId>,    /* Session the tree is posted to, if RemoteView already has one */
+    format: BodyFormat,                 /* Serialisation of the reassembled tree */
+    encoding: BodyEncoding,             /* Compression of the reassembled tree, not of each chunk */
+    chunks: BTreeMap<u32, Vec<u8>>,     /* Chunks received so far, by number */
+    last_active: Instant,               /* When the upload was begun or last sent a chunk */
+}
+
+impl Upload {
+    fn received_bytes(&self) -> u64 {
+        self.chunks.values().map(|chunk| chunk.len() as u64).sum()
+    }
+
+    /* Chunk numbers below the total that have not been received */
+    fn missing_chunks(&self, total_chunks: u32) -> Vec<u32> {
+        (0..total_chunks).filter(|index| !self.chunks.contains_key(index)).collect()
+    }
+}
+
+/* Uploads in progress, managed by Rocket and shared with the task expiring them */
+#[derive(Clone)]
+pub struct UploadStore {
+    uploads: Arc<Mutex<HashMap<String, Upload>>>,
+    expiry: Duration,
+}
+
+impl UploadStore {
+    pub fn new(expiry: Duration) -> Self {
+        UploadStore { uploads: Arc::new(Mutex::new(HashMap::new())), expiry }
+    }
+
+    /* Lock the uploads, first discarding any that have been abandoned */
+    fn uploads(&self) -> MutexGuard<'_, HashMap<String, Upload>> {
+        /* An upload is only ever replaced whole, so a poisoned map is still consistent */
+        let mut uploads = self.uploads.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        uploads.retain(|_, upload| upload.last_active.elapsed() < self.expiry);
+        uploads
+    }
+
+    /* Discard abandoned uploads, freeing their chunks */
+    fn expire(&self) {
+        drop(self.uploads());
+    }
+
+    /* How often abandoned uploads are looked for, so none outlives its expiry by more than half again */
+    fn sweep_interval(&self) -> Duration {
+        (self.expiry / 2).max(Duration::from_millis(100))
+    }
+}
+
+/* Discard abandoned uploads on a timer, so their chunks are freed even if no other upload request arrives */
+pub fn expire_uploads() -> AdHoc {
+    AdHoc::on_liftoff("Upload expiry", |rocket| Box::pin(async move {
+        let Some(store) = rocket.state::<UploadStore>().cloned() else {
+            return;
+        };
+        let shutdown = rocket.shutdown();
+
+        rocket::tokio::spawn(async move {
+            let mut sweep = rocket::tokio::time::interval(store.sweep_interval());
+            loop {
+                rocket::tokio::select! {
+                    _ = sweep.tick() => store.expire(),
+                    _ = shutdown.clone() => break,
+                }
+            }
+        });
+    }))
+}
+
+
+/* Request to begin an upload, describing the tree once its chunks are reassembled */
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct BeginUpload {
+    session_id: Option<ClientSessionId>,
+    content_type: Option<String>,       /* Defaults to JSON */
+    content_encoding: Option<String>,   /* Defaults to uncompressed */
+}
+
+/* Request to reassemble an upload once every chunk has been sent */
+#[derive(Debug, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CommitUpload {
+    total_chunks: u32,
+}
+
+/* Progress of an upload */
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct UploadResponse {
+    message: String,
+    upload_id: String,
+    received_chunks: usize,
+    received_bytes: u64,
+}
+
+impl UploadResponse {
+    fn new(message: impl Into<String>, upload_id: &str, upload: Option<&Upload>) -> Json<UploadResponse> {
+        Json(UploadResponse {
+            message: message.into(),
+            upload_id: upload_id.to_string(),
+            received_chunks: upload.map_or(0, |upload| upload.chunks.len()),
+            received_bytes: upload.map_or(0, Upload::received_bytes),
+        })
+    }
+}
+
+fn upload_not_found(upload_id: &str) -> ApiError {
+    ApiError::new(ErrorCode::UploadNotFound, "No upload in progress with this id, it may have expired")
+        .with_details(serde_json::json!({ "uploadId": upload_id }))
+}
+
+fn upload_too_large(upload_id: &str, limit: ByteUnit, decompressed: bool) -> ApiError {
+    let message: String = match decompressed {
+        true => format!("Upload is larger than the {limit} limit once decompressed"),
+        false => format!("Upload is larger than the {limit} limit"),
+    };
+
+    ApiError::new(ErrorCode::PayloadTooLarge, message)
+        .with_details(serde_json::json!({ "uploadId": upload_id, "limit": limit.as_u64() }))
+}
+
+fn upload_limit(limits: &Limits) -> ByteUnit {
+    limits.get(UPLOAD_LIMIT).unwrap_or(ByteUnit::Gibibyte(1))
+}
+
+
+/* Begin a chunked upload, for trees too large to send in one request */
+#[post("/api/remote/tree/upload", format = "application/json", data = "<begin>")]
+fn begin_upload(_auth: Authorised, begin: Json<BeginUpload>, uploads: &rocket::State<UploadStore>) -> Result<Json<UploadResponse>, ApiError> {
+    let begin: BeginUpload = begin.into_inner();
+
+    let content_type: Option<ContentType> = begin.content_type.as_deref().map_or(Some(ContentType::JSON), ContentType::parse_flexible);
+    let format: BodyFormat = BodyFormat::from_content_type(content_type.as_ref()).ok_or(TreeBodyError::UnsupportedFormat)?;
+    let encoding: BodyEncoding = BodyEncoding::from_name(begin.content_encoding.as_deref()).ok_or(TreeBodyError::UnsupportedEncoding)?;
+
+    let mut uploads = uploads.uploads();
+    if uploads.len() >= MAX_UPLOADS {
+        return Err(ApiError::new(ErrorCode::TooManyUploads, format!("{MAX_UPLOADS} uploads are already in progress, commit or abandon one first"))
+            .with_details(serde_json::json!({ "limit": MAX_UPLOADS })));
+    }
+
+    let upload_id: String = uuid::Uuid::new_v4().simple().to_string();
+    uploads.insert(upload_id.clone(), Upload {
+        session_id: begin.session_id,
+        format,
+        encoding,
+        chunks: BTreeMap::new(),
+        last_active: Instant::now(),
+    });
+
+    Ok(UploadResponse::new("Upload begun, send its chunks numbered from 0", &upload_id, None))
+}
+
+/* Receive one chunk of an upload, replacing any chunk already sent with the same number */
+#[put("/api/remote/tree/upload/<upload_id>/<index>", data = "<chunk>")]
+async fn put_chunk(_auth: Authorised, upload_id: &str, index: u32, chunk: Data<'_>, limits: &Limits, uploads: &rocket::State<UploadStore>) -> Result<Json<UploadResponse>, ApiError> {
+    let chunk: Vec<u8> = data::read_limited(chunk, data::tree_limit(limits)).await?;
+
+    let mut uploads = uploads.uploads();
+    let held_bytes: u64 = uploads.values().map(Upload::received_bytes).sum();
+    let upload: &mut Upload = uploads.get_mut(upload_id).ok_or_else(|| upload_not_found(upload_id))?;
+
+    /* A resent chunk replaces the previous one, so is not counted twice */
+    let replaced_bytes: u64 = upload.chunks.get(&index).map_or(0, |previous| previous.len() as u64);
+    let limit: ByteUnit = upload_limit(limits);
+    if upload.received_bytes() - replaced_bytes + chunk.len() as u64 > limit.as_u64() {
+        return Err(upload_too_large(upload_id, limit, false));
+    }
+
+    /* Every upload in progress together is held to the same limit */
+    if held_bytes - replaced_bytes + chunk.len() as u64 > limit.as_u64() {
+        return Err(ApiError::new(ErrorCode::PayloadTooLarge, format!("Uploads in progress would hold more than the {limit} limit, commit or abandon one first"))
+            .with_details(serde_json::json!({ "uploadId": upload_id, "limit": limit.as_u64() })));
+    }
+
+    upload.chunks.insert(index, chunk);
+    upload.last_active = Instant::now();
+
+    Ok(UploadResponse::new(format!("Received chunk {index}"), upload_id, Some(upload)))
+}
+
+/* Reassemble an upload and post the tree, once every chunk has arrived */
+#[post("/api/remote/tree/upload/<upload_id>/commit", format = "application/json", data = "<commit>")]
+#[allow(clippy::too_many_arguments)]
+async fn commit_upload(
+    _auth: Authorised,
+    client: ClientInfo,
+    token: SessionToken,
+    upload_id: &str,
+    commit: Json<CommitUpload>,
+    limits: &Limits,
+    uploads: &rocket::State<UploadStore>,
+    state: &rocket::State<ServerState>,
+) -> Result<Json<PostTreeResponse>, ApiError> {
+    let total_chunks: u32 = commit.total_chunks;
+
+    let upload: Upload = {
+        let mut uploads = uploads.uploads();
+        let upload: &Upload = uploads.get(upload_id).ok_or_else(|| upload_not_found(upload_id))?;
+
+        /* Incomplete uploads are kept, so the missing chunks can still be sent */
+        let missing_chunks: Vec<u32> = upload.missing_chunks(total_chunks);
+        if !missing_chunks.is_empty() {
+            return Err(ApiError::new(ErrorCode::UploadIncomplete, format!("{} of {total_chunks} chunks are missing", missing_chunks.len()))
+                .with_details(serde_json::json!({ "uploadId": upload_id, "missingChunks": missing_chunks })));
+        }
+
+        if let Some(extra) = upload.chunks.keys().find(|index| **index >= total_chunks) {
+            return Err(ApiError::new(ErrorCode::InvalidTree, format!("Chunk {extra} is beyond the {total_chunks} chunks committed"))
+                .with_details(serde_json::json!({ "uploadId": upload_id })));
+        }
+
+        uploads.remove(upload_id).expect("Upload was found while the lock was held")
+    };
+
+    let limit: ByteUnit = upload_limit(limits);
+    let body: Vec<u8> = upload.chunks.into_values().flatten().collect();
+    let mut parsley_tree: ParsleyTree = match data::decode_body(upload.format, upload.encoding, body, limit).await {
+        Ok(tree) => tree,
+        Err(TreeBodyError::TooLarge) => return Err(upload_too_large(upload_id, limit, true)),
+        Err(err) => return Err(ApiError::from(err)),
+    };
+
+    /* Upload to the session it was begun for, unless the tree names its own */
+    if let (Some(session_id), true) = (upload.session_id.filter(|session_id| !session_id.is_unset()), parsley_tree.session_not_set()) {
+        parsley_tree.set_session_id(session_id);
+    }
+
+    request::handle_parsley_tree(parsley_tree, state, client, token).await
+}
+
+
+#[cfg(test)]
+mod test {
+
+    /* Chunked upload unit testing */
+
+    use std::collections::BTreeMap;
+    use std::time::{Duration, Instant};
+
+    use rocket::http::{self, Status};
+    use rocket::local::{asynchronous, blocking};
+
+    use super::{Upload, UploadStore};
+    use crate::server::data::{BodyEncoding, BodyFormat};
+    use crate::server::{launch, ServerState};
+
+    use crate::options::Options;
+    use crate::server::test::{posting_mock, tracked_client, tracked_client_with};
+    use crate::state::MockStateManager;
+    use crate::trees::parsley_tree;
+
+    /* Begin an upload, returning its id */
+    fn begin(client: &blocking::Client, body: &str) -> String {
+        let response = client.post("/api/remote/tree/upload")
+            .header(http::ContentType::JSON)
+            .body(body)
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+
+        let body: serde_json::Value = response.into_json().expect("Response should be JSON");
+        body["uploadId"].as_str().expect("Response should hold an upload id").to_string()
+    }
+
+    fn put_chunk(client: &blocking::Client, upload_id: &str, index: u32, chunk: &[u8]) -> Status {
+        client.put(format!("/api/remote/tree/upload/{upload_id}/{index}"))
+            .header(http::ContentType::Binary)
+            .body(chunk)
+            .dispatch()
+            .status()
+    }
+
+    fn commit<'c>(client: &'c blocking::Client, upload_id: &str, total_chunks: u32) -> blocking::LocalResponse<'c> {
+        client.post(format!("/api/remote/tree/upload/{upload_id}/commit"))
+            .header(http::ContentType::JSON)
+            .body(format!(r#"{{"totalChunks":{total_chunks}}}"#))
+            .dispatch()
+    }
+
+    #[test]
+    fn chunks_sent_out_of_order_are_reassembled() {
+        let client: blocking::Client = tracked_client(posting_mock());
+        let json: String = parsley_tree::test::json();
+        let chunks: Vec<&[u8]> = json.as_bytes().chunks(json.len() / 3 + 1).collect();
+
+        let upload_id: String = begin(&client, "{}");
+        for index in [2, 0, 1] {
+            assert_eq!(put_chunk(&client, &upload_id, index, chunks[index as usize]), Status::Ok);
+        }
+
+        let response = commit(&client, &upload_id, 3);
+        assert_eq!(response.status(), Status::Ok);
+
+        let body: serde_json::Value = response.into_json().expect("Response should be JSON");
+        assert!(body["message"].as_str().is_some_and(|message| message.starts_with("Posted parser tree")));
+    }
+
+    #[test]
+    fn compressed_upload_is_decompressed_once_reassembled() {
+        let client: blocking::Client = tracked_client(posting_mock());
+        let compressed: Vec<u8> = zstd::encode_all(parsley_tree::test::json().as_bytes(), 0).expect("Could not compress tree");
+        let (first, second) = compressed.split_at(compressed.len() / 2);
+
+        let upload_id: String = begin(&client, r#"{"contentEncoding":"zstd"}"#);
+        assert_eq!(put_chunk(&client, &upload_id, 0, first), Status::Ok);
+        assert_eq!(put_chunk(&client, &upload_id, 1, second), Status::Ok);
+
+        assert_eq!(commit(&client, &upload_id, 2).status(), Status::Ok);
+    }
+
+    #[test]
+    fn commit_with_missing_chunks_can_be_completed() {
+        let client: blocking::Client = tracked_client(posting_mock());
+        let json: String = parsley_tree::test::json();
+        let (first, second) = json.as_bytes().split_at(json.len() / 2);
+
+        let upload_id: String = begin(&client, "{}");
+        assert_eq!(put_chunk(&client, &upload_id, 0, first), Status::Ok);
+
+        let response = commit(&client, &upload_id, 2);
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+
+        let body: serde_json::Value = response.into_json().expect("Response should be JSON");
+        assert_eq!(body["code"], "uploadIncomplete");
+        assert_eq!(body["details"]["missingChunks"], serde_json::json!([1]));
+
+        /* The upload is kept, so sending the missing chunk completes it */
+        assert_eq!(put_chunk(&client, &upload_id, 1, second), Status::Ok);
+        assert_eq!(commit(&client, &upload_id, 2).status(), Status::Ok);
+    }
+
+    #[test]
+    fn invalid_reassembled_tree_is_rejected() {
+        let client: blocking::Client = tracked_client(MockStateManager::new());
+
+        let upload_id: String = begin(&client, "{}");
+        assert_eq!(put_chunk(&client, &upload_id, 0, b"{\"input\":"), Status::Ok);
+
+        assert_eq!(commit(&client, &upload_id, 1).status(), Status::UnprocessableEntity);
+
+        /* A committed upload is gone, even if its tree was invalid */
+        assert_eq!(commit(&client, &upload_id, 1).status(), Status::NotFound);
+    }
+
+    #[test]
+    fn unsupported_upload_format_is_rejected() {
+        let client: blocking::Client = tracked_client(MockStateManager::new());
+
+        let response = client.post("/api/remote/tree/upload")
+            .header(http::ContentType::JSON)
+            .body(r#"{"contentType":"text/plain"}"#)
+            .dispatch();
+
+        assert_eq!(response.status(), Status::UnsupportedMediaType);
+    }
+
+    #[test]
+    fn unknown_upload_is_not_found() {
+        let client: blocking::Client = tracked_client(MockStateManager::new());
+
+        assert_eq!(put_chunk(&client, "unknown", 0, b"{}"), Status::NotFound);
+        assert_eq!(commit(&client, "unknown", 1).status(), Status::NotFound);
+    }
+
+    #[test]
+    fn abandoned_upload_expires() {
+        let options = Options { upload_expiry: Some(0), ..Options::default() };
+        let client: blocking::Client = tracked_client_with(MockStateManager::new(), &options);
+
+        let upload_id: String = begin(&client, "{}");
+        assert_eq!(put_chunk(&client, &upload_id, 0, b"{}"), Status::NotFound);
+    }
+
+    #[test]
+    fn uploads_beyond_the_most_held_are_rejected() {
+        let client: blocking::Client = tracked_client(MockStateManager::new());
+
+        for _ in 0..super::MAX_UPLOADS {
+            begin(&client, "{}");
+        }
+
+        let response = client.post("/api/remote/tree/upload")
+            .header(http::ContentType::JSON)
+            .body("{}")
+            .dispatch();
+
+        assert_eq!(response.status(), Status::TooManyRequests);
+
+        let body: serde_json::Value = response.into_json().expect("Response should be JSON");
+        assert_eq!(body["code"], "tooManyUploads");
+    }
+
+    #[rocket::async_test]
+    async fn abandoned_upload_expires_without_another_request() {
+        let options = Options { upload_expiry: Some(1), ..Options::default() };
+        let rocket = launch::build(ServerState::new(MockStateManager::new()), &options);
+        let client = asynchronous::Client::tracked(rocket).await.expect("Could not launch rocket");
+
+        /* An upload last sent a chunk before its expiry, which no request will touch */
+        let store: &UploadStore = client.rocket().state::<UploadStore>().expect("Uploads should be managed");
+        store.uploads.lock().expect("Uploads should lock").insert(String::from("abandoned"), Upload {
+            session_id: None,
+            format: BodyFormat::Json,
+            encoding: BodyEncoding::Identity,
+            chunks: BTreeMap::new(),
+            last_active: Instant::now() - Duration::from_secs(2),
+        });
+
+        rocket::tokio::time::sleep(store.sweep_interval() + Duration::from_millis(200)).await;
+        assert!(store.uploads.lock().expect("Uploads should lock").is_empty());
+    }
+}
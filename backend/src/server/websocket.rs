@@ -0,0 +1,378 @@
+use rocket::futures::{SinkExt, StreamExt};
+use rocket::get;
+use rocket::tokio;
+use rocket_ws::{stream::DuplexStream, Channel, Message, WebSocket};
+
+use super::auth::Authorised;
+use super::request::{create_breakpoint_channels, emit_watchpoint_hit, matched_rules, should_pause, watched_changes, PostTreeResponse};
+use super::sessions::{ClientInfo, PausedSession, SessionStatus};
+use super::ServerState;
+use crate::events::Event;
+use crate::state::StateManager;
+use crate::state::state_manager::{BreakpointCode, BreakpointMessage};
+use crate::state::breakpoint_timeout::TimeoutAction;
+use crate::state::timeline::RefChange;
+use crate::trees::{deep_json, DebugTree, ParsleyTree, SessionId, SessionMetadata};
+
+/* Expose routes for mounting during launch */
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![remote_ws]
+}
+
+/* Messages sent by the RemoteView client over the WebSocket */
+/* Externally tagged, so a tree is never buffered before being deserialised */
+#[derive(Debug, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ClientMessage {
+    Handshake(SessionMetadata), /* Description of the client, as sent to newSession over HTTP */
+    Tree(ParsleyTree),          /* A parse tree, a breakpoint snapshot if it is debuggable */
+}
+
+/* Messages pushed to the RemoteView client over the WebSocket */
+#[derive(Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ServerMessage {
+    #[serde(rename_all = "camelCase")]
+    Session {                                   /* Session allocated to this connection */
+        session_id: SessionId,
+        #[serde(skip_serializing_if = "Option::is_none")] session_token: Option<String>,   /* Only needed by requests for the session made over HTTP */
+    },
+    Received { message: String },               /* A non-debuggable tree was stored */
+    UpdateRefs { refs: Vec<(i32, String)> },    /* New values for the state references */
+    Skip { skips: i32 },                        /* Continue, skipping this many breakpoints */
+    SkipAll,                                    /* Continue, skipping every breakpoint */
+    Terminate,                                  /* Stop the parse */
+    UntilParser { name: String },               /* Continue until a parser with this name is entered */
+    UntilFailure,                               /* Continue until a parser fails */
+    UntilOffset { offset: u32 },                /* Continue until this input offset is reached */
+    TimedOut { action: TimeoutAction },         /* No one continued in time, so the command is the timeout action */
+    Error { message: String },                  /* The previous message could not be handled */
+}
+
+impl From<BreakpointCode> for ServerMessage {
+    fn from(code: BreakpointCode) -> Self {
+        match code {
+            BreakpointCode::Skip(skips) => ServerMessage::Skip { skips },
+            BreakpointCode::SkipAll => ServerMessage::SkipAll,
+            BreakpointCode::Terminate => ServerMessage::Terminate,
+            BreakpointCode::UntilParser(name) => ServerMessage::UntilParser { name },
+            BreakpointCode::UntilFailure => ServerMessage::UntilFailure,
+            BreakpointCode::UntilOffset(offset) => ServerMessage::UntilOffset { offset },
+        }
+    }
+}
+
+impl ServerMessage {
+    fn error(message: impl Into<String>) -> Self {
+        ServerMessage::Error { message: message.into() }
+    }
+
+    /* Serialise into a WebSocket text frame */
+    fn into_frame(self) -> Message {
+        /* Server messages hold no nested trees, so cannot fail to serialise */
+        Message::Text(serde_json::to_string(&self).expect("ServerMessage should serialise"))
+    }
+}
+
+/* Send a message to the client */
+async fn send(stream: &mut DuplexStream, message: ServerMessage) -> rocket_ws::result::Result<()> {
+    stream.send(message.into_frame()).await
+}
+
+
+/* WebSocket handler: each connection is one debugging session */
+/* Messages on the connection need no session token, as no other client can send them */
+#[get("/api/remote/ws")]
+fn remote_ws(_auth: Authorised, client: ClientInfo, ws: WebSocket, state: &rocket::State<ServerState>) -> Channel<'_> {
+    ws.channel(move |mut stream| Box::pin(async move {
+        let session_id: SessionId = match state.next_session_id() {
+            Ok(session_id) => session_id,
+            Err(_) => return send(&mut stream, ServerMessage::error("Could not allocate a session id")).await,
+        };
+
+        let session_token: Option<String> = state.open_socket_session(session_id, client);
+        send(&mut stream, ServerMessage::Session { session_id, session_token }).await?;
+
+        while let Some(frame) = stream.next().await {
+            let text: String = match frame? {
+                Message::Text(text) => text,
+                Message::Close(_) => break,
+                _ => continue, /* Pings are answered by tungstenite */
+            };
+
+            /* Trees may be arbitrarily deep, so are parsed on a blocking thread */
+            let message = tokio::task::spawn_blocking(move || deep_json::from_str::<ClientMessage>(&text)).await;
+
+            match message {
+                Ok(Ok(ClientMessage::Handshake(metadata))) => state.describe_session(session_id, metadata),
+                Ok(Ok(ClientMessage::Tree(tree))) => receive_tree(state, session_id, tree, &mut stream).await?,
+                _ => send(&mut stream, ServerMessage::error("Could not parse message")).await?,
+            }
+        }
+
+        /* A session that disconnected at a breakpoint stays disconnected */
+        state.set_session_status(session_id, SessionStatus::Finished, None);
+        Ok(())
+    }))
+}
+
+/* Store a tree streamed over the WebSocket, waiting for the user if it is a breakpoint */
+async fn receive_tree(state: &ServerState, session_id: SessionId, parsley_tree: ParsleyTree, stream: &mut DuplexStream) -> rocket_ws::result::Result<()> {
+    /* The connection owns the session, whatever id the client sent */
+    let mut debug_tree: DebugTree = parsley_tree.into_debug_tree(session_id);
+    debug_tree.set_metadata(state.session_metadata(session_id));
+    let is_debuggable: bool = debug_tree.is_debuggable();
+
+    /* Continue straight away from breakpoints that no auto-continue rule or watchpoint matches */
+    let changes: Option<Vec<RefChange>> = is_debuggable.then(|| watched_changes(state, session_id, &debug_tree.refs())).flatten();
+
+    if is_debuggable && !should_pause(matched_rules(state, &debug_tree), changes.as_deref()) {
+        let refs: Vec<(i32, String)> = debug_tree.refs();
+
+        /* Kept in the session's timeline like a breakpoint posted over HTTP that continued */
+        if state.record_tree(debug_tree).is_err() {
+            return send(stream, ServerMessage::error("Could not store tree")).await;
+        }
+
+        state.set_session_status(session_id, SessionStatus::Running, None);
+        send(stream, ServerMessage::UpdateRefs { refs }).await?;
+        return send(stream, ServerMessage::Skip { skips: 0 }).await;
+    }
+
+    let paused: Option<PausedSession> = match is_debuggable {
+        true => match create_breakpoint_channels(state, session_id, debug_tree.refs()) {
+            Ok(paused) => Some(paused),
+            Err(err) => return send(stream, ServerMessage::error(err.message())).await,
+        },
+        false => None,
+    };
+
+    let success_msg: String = PostTreeResponse::success_msg(debug_tree.get_input());
+
+    if state.set_tree(debug_tree).is_err()
+        || state.update_tree(session_id).is_err()
+        || state.emit(Event::NewTree).is_err()
+        || emit_watchpoint_hit(state, session_id, changes).is_err() {
+        return send(stream, ServerMessage::error("Could not store tree")).await;
+    }
+
+    let Some(paused) = paused else {
+        return send(stream, ServerMessage::Received { message: success_msg }).await;
+    };
+
+    state.set_session_status(session_id, SessionStatus::Paused, None);
+
+    let Some(skips_rx) = state.take_receiver(session_id) else {
+        paused.close();
+        return send(stream, ServerMessage::error("Breakpoint channel closed")).await;
+    };
+
+    let wait = state.wait_at_breakpoint(session_id, skips_rx);
+    tokio::pin!(wait);
+
+    /* Wait for the user or the timeout policy to continue, while watching for the client going away */
+    let (BreakpointMessage { code, refs }, timeout_action) = loop {
+        tokio::select! {
+            message = &mut wait => match message {
+                Some(message) => break message,
+                None => {
+                    paused.close();
+                    return send(stream, ServerMessage::error("Breakpoint channel closed")).await;
+                },
+            },
+            frame = stream.next() => match frame {
+                /* Dropping the paused session releases the pending transmitter, the client can no longer be resumed */
+                None | Some(Ok(Message::Close(_))) | Some(Err(_)) => return Ok(()),
+                Some(Ok(Message::Text(_) | Message::Binary(_))) =>
+                    send(stream, ServerMessage::error("Message received while paused at a breakpoint")).await?,
+                Some(Ok(_)) => continue, /* Pings are answered by tungstenite */
+            },
+        }
+    };
+
+    paused.resume(code == BreakpointCode::Terminate);
+
+    if let Some(action) = timeout_action {
+        send(stream, ServerMessage::TimedOut { action }).await?;
+    }
+
+    /* Push the references sent with the action before the breakpoint command */
+    send(stream, ServerMessage::UpdateRefs { refs }).await?;
+    send(stream, ServerMessage::from(code)).await
+}
+
+
+#[cfg(test)]
+mod test {
+
+    /* WebSocket protocol unit testing */
+
+    use std::net::Ipv4Addr;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use rocket::futures::{SinkExt, StreamExt};
+    use rocket::tokio::net::TcpStream;
+    use tokio_tungstenite::tungstenite::Message;
+    use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+    use super::{ClientMessage, ServerMessage};
+    use crate::events::Event;
+    use crate::options::Options;
+    use crate::server::SkipsSender;
+    use crate::server::sessions::SessionStatus;
+    use crate::server::sessions::test::{listed_status, response, response_with, serve};
+    use crate::server::test::tracked_client;
+    use crate::state::MockStateManager;
+    use crate::state::state_manager::{BreakpointCode, BreakpointMessage};
+    use crate::state::breakpoint_timeout::{TimeoutAction, TimeoutPolicy};
+    use crate::trees::{deep_json, parsley_tree, SessionMetadata};
+    use crate::trees::session_id::test::id;
+
+    type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+    #[test]
+    fn client_handshake_message_deserialises() {
+        let message: ClientMessage = deep_json::from_str(r#"{"handshake":{"program":"ExprSpec"}}"#)
+            .expect("Could not deserialise ClientMessage");
+
+        assert_eq!(message, ClientMessage::Handshake(SessionMetadata { program: Some(String::from("ExprSpec")), ..SessionMetadata::default() }));
+    }
+
+    #[test]
+    fn client_tree_message_deserialises() {
+        let json: String = format!(r#"{{"tree":{}}}"#, parsley_tree::test::json());
+
+        let message: ClientMessage = deep_json::from_str(&json)
+            .expect("Could not deserialise ClientMessage");
+
+        assert_eq!(message, ClientMessage::Tree(parsley_tree::test::tree()));
+    }
+
+    #[test]
+    fn deep_client_tree_message_deserialises() {
+        let json: String = format!(r#"{{"tree":{}}}"#, parsley_tree::test::deep_tree_json(parsley_tree::test::DEEP_TREE_DEPTH));
+
+        assert!(deep_json::from_str::<ClientMessage>(&json).is_ok());
+    }
+
+    #[test]
+    fn server_messages_serialise() {
+        let to_json = |message: ServerMessage| serde_json::to_string(&message).expect("Could not serialise ServerMessage");
+
+        assert_eq!(
+            to_json(ServerMessage::Session { session_id: id(3), session_token: Some(String::from("abc")) }),
+            format!(r#"{{"session":{{"sessionId":"{}","sessionToken":"abc"}}}}"#, id(3))
+        );
+        assert_eq!(to_json(ServerMessage::from(BreakpointCode::Skip(2))), r#"{"skip":{"skips":2}}"#);
+        assert_eq!(to_json(ServerMessage::from(BreakpointCode::SkipAll)), r#""skipAll""#);
+        assert_eq!(to_json(ServerMessage::from(BreakpointCode::Terminate)), r#""terminate""#);
+        assert_eq!(
+            to_json(ServerMessage::from(BreakpointCode::UntilParser(String::from("digit")))),
+            r#"{"untilParser":{"name":"digit"}}"#
+        );
+        assert_eq!(
+            to_json(ServerMessage::UpdateRefs { refs: vec![(0, String::from("1"))] }),
+            r#"{"updateRefs":{"refs":[[0,"1"]]}}"#
+        );
+        assert_eq!(
+            to_json(ServerMessage::TimedOut { action: TimeoutAction::SkipAll }),
+            r#"{"timedOut":{"action":"skipAll"}}"#
+        );
+    }
+
+    #[test]
+    fn plain_get_is_not_upgraded() {
+        let client = tracked_client(MockStateManager::new());
+
+        /* A request without WebSocket upgrade headers is forwarded with a bad request status */
+        let response = client.get(rocket::uri!(super::remote_ws)).dispatch();
+
+        assert_eq!(response.status(), rocket::http::Status::BadRequest);
+    }
+    /* Next message the server pushes, as JSON */
+    async fn receive(socket: &mut Socket) -> serde_json::Value {
+        loop {
+            match socket.next().await.expect("Server should not close the connection").expect("Could not read message") {
+                Message::Text(text) => return serde_json::from_str(&text).expect("Server message should be JSON"),
+                _ => continue,
+            }
+        }
+    }
+
+    async fn listed_as(port: u16, status: Option<&str>) {
+        let listed = async {
+            while listed_status(port).await.as_deref() != status {
+                rocket::tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        };
+        rocket::tokio::time::timeout(Duration::from_secs(5), listed).await.expect("Session should reach the status");
+    }
+
+    #[rocket::async_test]
+    async fn client_is_paused_and_resumed_over_websocket() {
+        let pending: Arc<Mutex<Option<SkipsSender>>> = Arc::new(Mutex::new(None));
+        let held: Arc<Mutex<Option<SkipsSender>>> = Arc::clone(&pending);
+
+        let mut mock = MockStateManager::new();
+        mock.expect_next_session_id().returning(|| Ok(id(3)));
+        mock.expect_set_tree()
+            .withf(|tree| tree.get_session_id() == id(3) && tree.metadata().and_then(|metadata| metadata.program.as_deref()) == Some("ExprSpec"))
+            .times(1)
+            .returning(|_| Ok(()));
+        mock.expect_update_tree().returning(|_| Ok(()));
+        mock.expect_reset_refs().returning(|_, _| Ok(()));
+        mock.expect_matches_rules().returning(|_| Ok(None));
+        mock.expect_watched_changes().returning(|_, _| Ok(None));
+        mock.expect_timeout_policy().returning(|_| Ok(TimeoutPolicy::default()));
+        mock.expect_new_transmitter().times(1).returning(move |_, tx| {
+            *held.lock().expect("Sender lock poisoned") = Some(tx);
+            Ok(())
+        });
+        mock.expect_emit()
+            .withf(|event| matches!(event, Event::SessionClosed(info) if info.status() == SessionStatus::Finished))
+            .times(1)
+            .returning(|_| Ok(()));
+        mock.expect_emit()
+            .withf(|event| matches!(event, Event::NewTree | Event::SessionPaused(_) | Event::SessionResumed(_)))
+            .returning(|_| Ok(()));
+
+        let options = Options { address: Some(Ipv4Addr::LOCALHOST.into()), port: Some(0), ..Options::default() };
+        let (port, shutdown) = serve(mock, &options).await;
+
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://127.0.0.1:{port}/api/remote/ws")).await
+            .expect("Could not open WebSocket");
+
+        /* The connection is allocated a session, and a token for requests made for it over HTTP */
+        let session: serde_json::Value = receive(&mut socket).await;
+        assert_eq!(session["session"]["sessionId"], id(3).to_string());
+        let token: &str = session["session"]["sessionToken"].as_str().expect("Session should be issued a token");
+
+        let heartbeat: String = format!("POST /api/remote/heartbeat?session_id={}", id(3));
+        assert!(response(port, &heartbeat, "").await.starts_with("HTTP/1.1 403"));
+        assert!(response_with(port, &heartbeat, &format!("X-Dill-Session-Token: {token}\r\n"), "").await.starts_with("HTTP/1.1 204"));
+
+        /* Messages on the connection need no token */
+        let handshake: &str = r#"{"handshake":{"program":"ExprSpec"}}"#;
+        let tree: String = format!(r#"{{"tree":{}}}"#, parsley_tree::test::json().replace(r#""isDebuggable":false"#, r#""isDebuggable":true"#));
+        socket.send(Message::Text(handshake.to_string())).await.expect("Could not send handshake");
+        socket.send(Message::Text(tree)).await.expect("Could not send tree");
+
+        listed_as(port, Some("paused")).await;
+
+        let tx: SkipsSender = pending.lock().expect("Sender lock poisoned").take().expect("Breakpoint should be waiting");
+        tx.send(BreakpointMessage { code: BreakpointCode::Skip(2), refs: vec![(0, String::from("1"))] })
+            .expect("Breakpoint should be awaited");
+
+        /* The references are pushed before the breakpoint command */
+        assert_eq!(receive(&mut socket).await, serde_json::json!({ "updateRefs": { "refs": [[0, "1"]] } }));
+        assert_eq!(receive(&mut socket).await, serde_json::json!({ "skip": { "skips": 2 } }));
+        listed_as(port, Some("running")).await;
+
+        /* Closing the connection finishes the session */
+        socket.close(None).await.expect("Could not close WebSocket");
+        listed_as(port, None).await;
+
+        shutdown.notify();
+    }
+}
@@ -1,10 +1,18 @@
 mod app_state;
+mod session_core;
 pub mod state_manager;
 mod app_handle;
-mod session_counter;
+mod session_store;
+pub mod breakpoint_rules;
+pub mod timeline;
+pub mod watchpoints;
+pub mod breakpoint_timeout;
+pub mod breakpoint_queue;
+pub mod headless_state;
 
 pub use app_state::AppState;
 pub use app_handle::AppHandle;
+pub use headless_state::{HeadlessState, BreakpointPolicy};
 
 pub use state_manager::{StateManager, StateError};
 #[cfg(test)] pub use state_manager::MockStateManager;
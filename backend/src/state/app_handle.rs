@@ -3,11 +3,15 @@ use std::path::PathBuf;
 use tauri::{Emitter, Manager};
 
 use crate::events::Event;
-use crate::trees::{DebugNode, DebugTree};
+use crate::trees::{DebugNode, DebugTree, ParsleyTreeDelta, SessionId};
 use crate::state::state_manager::{BreakpointCode};
 use crate::server::SkipsSender;
 
-use super::state_manager::{DirectoryKind, UpdateTreeError};
+use super::state_manager::{DirectoryKind, MergedDelta, UpdateTreeError};
+use super::breakpoint_rules::{BreakpointRule, RuleCondition};
+use super::timeline::{RefChange, SnapshotDiff, SnapshotSummary};
+use super::breakpoint_timeout::TimeoutPolicy;
+use super::breakpoint_queue::{FocusPolicy, QueuedBreakpoint};
 use super::{AppState, StateManager, StateError};
 
 /* Wrapper for Tauri AppHandle */
@@ -42,12 +46,40 @@ impl StateManager for tauri::AppHandle {
         self.state::<AppState>().set_tree(tree)
     }
 
-    fn get_tree(&self) -> Result<DebugTree, StateError> {
-        self.state::<AppState>().get_tree()
+    fn record_tree(&self, tree: DebugTree) -> Result<(), StateError> {
+        self.state::<AppState>().record_tree(tree)
     }
 
-    fn get_node(&self, id: u32) -> Result<DebugNode, StateError> {
-        self.state::<AppState>().get_node(id)
+    fn get_tree(&self, session_id: SessionId) -> Result<DebugTree, StateError> {
+        self.state::<AppState>().get_tree(session_id)
+    }
+
+    fn get_children(&self, session_id: SessionId, node_id: u32) -> Result<Vec<DebugNode>, StateError> {
+        self.state::<AppState>().get_children(session_id, node_id)
+    }
+
+    fn merge_delta(&self, session_id: SessionId, delta: ParsleyTreeDelta) -> Result<MergedDelta, StateError> {
+        self.state::<AppState>().merge_delta(session_id, delta)
+    }
+
+    fn display_tree(&self, session_id: SessionId) -> Result<(), StateError> {
+        self.state::<AppState>().display_tree(session_id)
+    }
+
+    fn timeline(&self, session_id: SessionId) -> Result<Vec<SnapshotSummary>, StateError> {
+        self.state::<AppState>().timeline(session_id)
+    }
+
+    fn view_snapshot(&self, session_id: SessionId, index: usize) -> Result<(), StateError> {
+        self.state::<AppState>().view_snapshot(session_id, index)
+    }
+
+    fn diff_snapshot(&self, session_id: SessionId, index: usize) -> Result<SnapshotDiff, StateError> {
+        self.state::<AppState>().diff_snapshot(session_id, index)
+    }
+
+    fn displayed_session_id(&self) -> Result<SessionId, StateError> {
+        self.state::<AppState>().displayed_session_id()
     }
 
     /* Emit event using the Tauri AppHandle */
@@ -56,11 +88,15 @@ impl StateManager for tauri::AppHandle {
             .map_err(|_| StateError::EventEmitFailed)
     }
 
-    fn transmit_breakpoint_skips(&self, session_id: i32, code: BreakpointCode) -> Result<(), StateError> {
+    fn transmit_breakpoint_skips(&self, session_id: SessionId, code: BreakpointCode) -> Result<(), StateError> {
         self.state::<AppState>().transmit_breakpoint_skips(session_id, code)
     }
+
+    fn resume_breakpoint(&self, session_id: SessionId, code: BreakpointCode, refs: Vec<(i32, String)>) -> Result<(), StateError> {
+        self.state::<AppState>().resume_breakpoint(session_id, code, refs)
+    }
     
-    // fn add_session_id(&self, tree_name: String, session_id:i32) -> Result<(), StateError> {
+    // fn add_session_id(&self, tree_name: String, session_id: SessionId) -> Result<(), StateError> {
     //     self.state::<AppState>().add_session_id(tree_name, session_id)
     // }
     
@@ -68,19 +104,19 @@ impl StateManager for tauri::AppHandle {
         self.state::<AppState>().rmv_tab(index)
     }
     
-    fn get_tab(&self, index: usize) -> Result<(i32, String), StateError> {
+    fn get_tab(&self, index: usize) -> Result<(SessionId, String), StateError> {
         self.state::<AppState>().get_tab(index)
     }
 
-    fn debuggable_session_ids(&self) -> Result<Vec<i32>, StateError> {
+    fn debuggable_session_ids(&self) -> Result<Vec<SessionId>, StateError> {
         self.state::<AppState>().debuggable_session_ids()
     }
 
-    fn next_session_id(&self) -> Result<i32, StateError> {
+    fn next_session_id(&self) -> Result<SessionId, StateError> {
         self.state::<AppState>().next_session_id()
     }
     
-    fn new_transmitter(&self, session_id: i32, tx: SkipsSender) -> Result<(), StateError> {
+    fn new_transmitter(&self, session_id: SessionId, tx: SkipsSender) -> Result<(), StateError> {
         self.state::<AppState>().new_transmitter(session_id, tx)
     }
 
@@ -88,11 +124,11 @@ impl StateManager for tauri::AppHandle {
         self.state::<AppState>().system_path(dir)
     }
     
-    fn reset_refs(&self, session_id: i32, default_refs: Vec<(i32, String)>) -> Result<(), StateError> {
+    fn reset_refs(&self, session_id: SessionId, default_refs: Vec<(i32, String)>) -> Result<(), StateError> {
         self.state::<AppState>().reset_refs(session_id, default_refs)
     }
 
-    fn get_refs(&self, session_id: i32) -> Result<Vec<(i32, String)>, StateError> {
+    fn get_refs(&self, session_id: SessionId) -> Result<Vec<(i32, String)>, StateError> {
         self.state::<AppState>().get_refs(session_id)
     }
 
@@ -100,7 +136,67 @@ impl StateManager for tauri::AppHandle {
         self.state::<AppState>().reset_trees()
     }
 
-    fn update_tree(&self, tree: &DebugTree, session_id: i32) -> Result<(), UpdateTreeError> {
-        self.state::<AppState>().update_tree(tree, session_id)
+    fn add_rule(&self, condition: RuleCondition) -> Result<BreakpointRule, StateError> {
+        self.state::<AppState>().add_rule(condition)
+    }
+
+    fn rules(&self) -> Result<Vec<BreakpointRule>, StateError> {
+        self.state::<AppState>().rules()
+    }
+
+    fn set_rule_enabled(&self, rule_id: u32, enabled: bool) -> Result<(), StateError> {
+        self.state::<AppState>().set_rule_enabled(rule_id, enabled)
+    }
+
+    fn remove_rule(&self, rule_id: u32) -> Result<(), StateError> {
+        self.state::<AppState>().remove_rule(rule_id)
+    }
+
+    fn matches_rules(&self, tree: &DebugTree) -> Result<Option<bool>, StateError> {
+        self.state::<AppState>().matches_rules(tree)
+    }
+
+    fn add_watchpoint(&self, address: i32) -> Result<(), StateError> {
+        self.state::<AppState>().add_watchpoint(address)
+    }
+
+    fn watchpoints(&self) -> Result<Vec<i32>, StateError> {
+        self.state::<AppState>().watchpoints()
+    }
+
+    fn remove_watchpoint(&self, address: i32) -> Result<(), StateError> {
+        self.state::<AppState>().remove_watchpoint(address)
+    }
+
+    fn watched_changes(&self, session_id: SessionId, refs: &[(i32, String)]) -> Result<Option<Vec<RefChange>>, StateError> {
+        self.state::<AppState>().watched_changes(session_id, refs)
+    }
+
+    fn set_timeout_policy(&self, session_id: Option<SessionId>, policy: TimeoutPolicy) -> Result<(), StateError> {
+        self.state::<AppState>().set_timeout_policy(session_id, policy)
+    }
+
+    fn timeout_policy(&self, session_id: Option<SessionId>) -> Result<TimeoutPolicy, StateError> {
+        self.state::<AppState>().timeout_policy(session_id)
+    }
+
+    fn focus_session(&self, session_id: SessionId) -> Result<(), StateError> {
+        self.state::<AppState>().focus_session(session_id)
+    }
+
+    fn breakpoint_queue(&self) -> Result<Vec<QueuedBreakpoint>, StateError> {
+        self.state::<AppState>().breakpoint_queue()
+    }
+
+    fn set_focus_policy(&self, policy: FocusPolicy) -> Result<(), StateError> {
+        self.state::<AppState>().set_focus_policy(policy)
+    }
+
+    fn focus_policy(&self) -> Result<FocusPolicy, StateError> {
+        self.state::<AppState>().focus_policy()
+    }
+
+    fn update_tree(&self, session_id: SessionId) -> Result<(), UpdateTreeError> {
+        self.state::<AppState>().update_tree(session_id)
     }
 }
@@ -1,259 +1,74 @@
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::Write;
-use std::sync::{Mutex, MutexGuard};
+use std::sync::Mutex;
 use std::path::PathBuf;
 
-use indexmap::IndexMap;
-
 use crate::events::Event;
 use crate::files::SAVED_TREE_DIR;
-use crate::state::state_manager::BreakpointCode;
-use crate::trees::{DebugNode, DebugTree, SavedTree};
-use crate::server::SkipsSender;
+use crate::options::Options;
+use crate::trees::SessionId;
 
-use super::session_counter::SessionCounter;
-use super::state_manager::{DirectoryKind, UpdateTreeError};
+use super::session_core::{Frontend, SessionCore};
+use super::state_manager::DirectoryKind;
+use super::breakpoint_timeout::TimeoutPolicy;
+use super::timeline::DEFAULT_MAX_SNAPSHOTS;
 use super::{StateError, StateManager, AppHandle};
 
-/* Unsynchronised AppState */
-struct AppStateInternal {
+/* Tauri frontend of the app state, which shows trees in the window and saves them where the user chose */
+pub struct AppFrontend {
     app: AppHandle,                                 /* Handle to instance of Tauri app, used for events */
-    tree: Option<DebugTree>,                        /* Parser tree that is posted to Server */
-    map: HashMap<u32, DebugNode>,                   /* Map from node_id to the respective node */
-    skips_tx: HashMap<i32, SkipsSender>,            /* Transmitter how many breakpoints to skip, sent to parsley */
-    tabs: IndexMap<i32, String>,                    /* List of saved tree names */
-    saved_refs: HashMap<i32, Vec<(i32, String)>>,   /* Map of sessionId to saved refs for a tab */
-    counter: SessionCounter                         /* Counter to hold next sessionId */
+    saved_trees_dir: Option<PathBuf>,               /* Directory trees are saved to, if not the temporary directory */
+    startup_files: Mutex<Vec<PathBuf>>,             /* Saved trees to import once the frontend has loaded */
 }
 
-
-/* Synchronised global app state wrapping internal AppState */
-pub struct AppState(Mutex<AppStateInternal>);
-
-impl AppState {
-    /* Create a new app state with the app_handle */
-    pub fn new(app_handle: tauri::AppHandle) -> AppState {
-        AppState(
-            Mutex::new(
-                AppStateInternal {
-                    app: AppHandle::new(app_handle),
-                    tree: None,
-                    map: HashMap::new(),
-                    skips_tx: HashMap::new(),
-                    tabs: IndexMap::new(),
-                    saved_refs: HashMap::new(),
-                    counter: SessionCounter::new(),
-                }
-            )
-        )
-    }
-
-    /* Access wrapped inner AppStateInternal struct */
-    fn inner(&self) -> Result<MutexGuard<'_, AppStateInternal>, StateError> {
-        self.0.lock()
-            .map_err(|_| StateError::LockFailed)
-    }
-
-    fn tab_names(&self) -> Result<Vec<String>, StateError> {
-        let state: MutexGuard<AppStateInternal> = self.inner()?;
-        Ok(state.tabs.values().map(|name| name.clone()).collect())
-    }
-
-    // /* Add tree name to tab_names */
-    // pub fn add_tree(&self, session_id: i32, tab_name: String) -> Result<Vec<String>, StateError> {
-    //     let mut state: MutexGuard<AppStateInternal> = self.inner()?;
-
-    //     /* Add new tree name and return all saved tree names */
-    //     state.tabs.insert(session_id, tab_name);
-
-    //     self.tab_names()
-    // }
-
-    // /* Remove tree name from tab_names */
-    // pub fn rmv_tree(&self, index: usize) -> Result<Vec<String>, StateError> {
-    //     let mut state: MutexGuard<AppStateInternal> = self.inner()?;
-
-    //     /* Remove tree name at index */
-    //     state.tabs.shift_remove_index(index);
-
-    //     /* If this was the final tree then the loaded tree needs to be removed */
-    //     if state.tabs.is_empty() {
-    //         state.tree = None
-    //     }
-
-    //     self.tab_names()
-    // }
-
-    pub fn update_refs(&self, session_id: i32, new_refs: Vec<(i32, String)>) -> Result<(), StateError> {
-        let mut state: MutexGuard<AppStateInternal> = self.inner()?;
-
-        state.saved_refs.insert(session_id, new_refs);
-
-        Ok(())
-    }
-
-    fn is_debuggable_session(&self, session_id: i32) -> Result<bool, StateError> {
-        Ok(self.inner()?.skips_tx.contains_key(&session_id))
-    }
-}
-
-
-impl StateManager for AppState {
-    fn emit<'a>(&self, event: Event<'a>) -> Result<(), StateError> {
-        self.inner()?.app.emit(event)
-    }
-
-    /* Update StateManager's tree */
-    fn set_tree(&self, tree: DebugTree) -> Result<(), StateError> {
-        let mut state: MutexGuard<AppStateInternal> = self.inner()?;
-        
-        /* Reset map */
-        state.map.clear();
-        
-        /* Recursively insert nodes into map of node ids to nodes */
-        fn insert_node(state: &mut MutexGuard<AppStateInternal>, node: &DebugNode) -> Result<(), StateError> {
-            state.map.insert(node.node_id, node.clone());
-
-            for child in node.children.iter() {
-                insert_node(state, child)?;
-            }
-            
-            Ok(())
-        }
-
-        insert_node(&mut state, tree.get_root())?;
-
-        state.tabs.insert(tree.get_session_id(), tree.get_session_name());
-        
-        /* Update tree */
-        state.tree = Some(tree);
-    
-        /* Unwrap cannot fail as tree has just been set */
-        let event: Event = Event::TreeReady(state.tree.as_ref().unwrap());
-        state.app.emit(event) /* Notify frontend listener - call inline to avoid deadlock */ 
-            .map_err(|_| StateError::EventEmitFailed)
-    }
-    
-    /* Get StateManager's tree */
-    fn get_tree(&self) -> Result<DebugTree, StateError> {
-        self.inner()?
-            .tree
-            .as_ref()
-            .ok_or(StateError::TreeNotFound)
-            .cloned()
-    }
-    
-    /* Get node associated with node ID */
-    fn get_node(&self, id: u32) -> Result<DebugNode, StateError> {
-        self.inner()?
-            .map
-            .get(&id)
-            .ok_or(StateError::NodeNotFound(id))
-            .cloned()
-    }
-
-    fn transmit_breakpoint_skips(&self, session_id: i32, code: BreakpointCode) -> Result<(), StateError> {
-        self.inner()?
-            .skips_tx
-            .remove(&session_id)
-            .ok_or(StateError::ChannelError)?
-            .send(code.i32_code())
-            .map_err(|_| StateError::ChannelError)
+impl Frontend for AppFrontend {
+    fn emit(&self, event: Event<'_>) -> Result<(), StateError> {
+        self.app.emit(event)
     }
 
-    fn system_path(&self, dir: super::state_manager::DirectoryKind) -> Result<PathBuf, StateError> {
-        let handle: &AppHandle = &self.inner()?.app;
-
+    fn system_path(&self, dir: DirectoryKind) -> Result<PathBuf, StateError> {
         match dir {
-            DirectoryKind::SavedTrees => handle.tauri_temp_dir()
-                                               .map(|path| path.join(SAVED_TREE_DIR)),
-            DirectoryKind::Downloads => handle.tauri_downloads_dir(),
-        }
-    }
-    
-    fn rmv_tab(&self, index: usize) -> Result<Vec<String>, StateError> {
-        let mut state: MutexGuard<'_, AppStateInternal> = self.inner()?;
-
-        let session_id: i32 = state.tabs.keys()[index];
-        if self.is_debuggable_session(session_id)? {
-            self.transmit_breakpoint_skips(session_id, BreakpointCode::Terminate)?;
+            DirectoryKind::SavedTrees => match &self.saved_trees_dir {
+                Some(path) => Ok(path.clone()),
+                None => self.app.tauri_temp_dir().map(|path| path.join(SAVED_TREE_DIR)),
+            },
+            DirectoryKind::Downloads => self.app.tauri_downloads_dir(),
         }
-        state.saved_refs.remove(&session_id);
-
-        self.tab_names()
-    }
-    
-    fn get_tab(&self, index: usize) -> Result<(i32, String), StateError> {
-        let state: MutexGuard<'_, AppStateInternal> = self.inner()?;
-        
-        state.tabs.get_index(index).map(|(id, name)| (*id, name.clone())).ok_or(StateError::TabOutOfBounds)
-    }
-
-    fn debuggable_session_ids(&self) -> Result<Vec<i32>, StateError> {
-        let state: MutexGuard<'_, AppStateInternal> = self.inner()?;
-        Ok(state.tabs.keys().filter(|id| state.skips_tx.contains_key(id)).cloned().collect())
-    }
-
-    fn next_session_id(&self) -> Result<i32, StateError> {
-        let mut state: MutexGuard<'_, AppStateInternal> = self.inner()?;
-        
-        Ok(state.counter.get_and_increment())
-    }
-
-    fn new_transmitter(&self, session_id: i32, tx: SkipsSender) -> Result<(), StateError> {
-        match self.inner()?
-            .skips_tx
-            .insert(session_id, tx) {
-                Some(_) => Err(StateError::ChannelError),
-                None => Ok(())
-            }
     }
+}
 
-    fn get_refs(&self, session_id: i32) -> Result<Vec<(i32, String)>, StateError> {
-        let state: MutexGuard<AppStateInternal> = self.inner()?;
 
-        let refs_opt: Option<&Vec<(i32, String)>> = state.saved_refs.get(&session_id);
+/* Synchronised global app state */
+pub type AppState = SessionCore<AppFrontend>;
 
-        match refs_opt {
-            Some(refs) => Ok(refs.clone()),
-            None => Ok(Vec::new()),
-        }
+impl AppState {
+    /* Create a new app state with the app_handle and the options Dill was launched with */
+    pub fn new(app_handle: tauri::AppHandle, options: &Options) -> AppState {
+        let frontend = AppFrontend {
+            app: AppHandle::new(app_handle),
+            saved_trees_dir: options.saved_trees_dir.clone(),
+            startup_files: Mutex::new(options.open.clone()),
+        };
+
+        SessionCore::with_frontend(
+            frontend,
+            TimeoutPolicy::new(options.breakpoint_timeout, options.timeout_action),
+            options.focus_policy,
+            options.max_snapshots.unwrap_or(DEFAULT_MAX_SNAPSHOTS),
+        )
     }
 
-    fn reset_refs(&self, session_id: i32, default_refs: Vec<(i32, String)>) -> Result<(), StateError> {
-        let mut state: MutexGuard<AppStateInternal> = self.inner()?;
-
-        state.saved_refs.insert(session_id, default_refs);
-
-        Ok(())
+    pub fn update_refs(&self, session_id: SessionId, new_refs: Vec<(i32, String)>) -> Result<(), StateError> {
+        self.reset_refs(session_id, new_refs)
     }
 
-    fn reset_trees(&self) -> Result<(), StateError> {
-        let mut state: MutexGuard<AppStateInternal> = self.inner()?;
-
-        state.tabs = IndexMap::new();
-        state.saved_refs = HashMap::new();
-
-        Ok(())
+    /* Take the files Dill was opened with, so they are only imported once */
+    pub fn take_startup_files(&self) -> Result<Vec<PathBuf>, StateError> {
+        self.frontend.startup_files.lock()
+            .map(|mut files| std::mem::take(&mut *files))
+            .map_err(|_| StateError::LockFailed)
     }
 
-    fn update_tree(&self, tree: &DebugTree, session_id: i32) -> Result<(), UpdateTreeError> {
-        let new_tree: SavedTree = SavedTree::from(tree.clone());
-        /* Get the serialised JSON */
-        let tree_json: String = serde_json::to_string_pretty(&new_tree)
-            .map_err(|_| UpdateTreeError::SerialiseFailed)?;
-
-        /* Open the json file to update the tree */
-        /* TODO: look into only updating the extra bits rather than replacing the tree */
-        let file_path = PathBuf::from(format!("{}.json", session_id));
-        let full_path: PathBuf = self.system_path_to(DirectoryKind::SavedTrees, file_path).map_err(|_| UpdateTreeError::OpenFileFailed)?;
-
-        let mut data_file: File = File::create(full_path).map_err(|_| UpdateTreeError::OpenFileFailed)?;
-
-        /* Write tree json to the json file */
-        data_file.write(tree_json.as_bytes()).map_err(|_| UpdateTreeError::WriteTreeFailed)?;
-
-        Ok(())
+    /* Whether trees are saved to a directory the user chose, whose files are theirs to keep */
+    pub fn keeps_saved_trees(&self) -> Result<bool, StateError> {
+        Ok(self.frontend.saved_trees_dir.is_some())
     }
 }
@@ -0,0 +1,200 @@
+use std::str::FromStr;
+
+use crate::trees::{DebugTree, SessionId};
+
+
+/* Which paused session is shown when several hit breakpoints at once */
+#[derive(Debug, Clone, Copy, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FocusPolicy {
+    #[default]
+    MostRecent, /* Show every breakpoint as it is hit */
+    FirstCome,  /* Show breakpoints in the order they were hit, once the shown one is continued */
+    Never,      /* Keep showing the current session, only notifying of others */
+}
+
+impl FromStr for FocusPolicy {
+    type Err = String;
+
+    fn from_str(policy: &str) -> Result<Self, Self::Err> {
+        match policy {
+            "most-recent" => Ok(FocusPolicy::MostRecent),
+            "first-come" => Ok(FocusPolicy::FirstCome),
+            "never" => Ok(FocusPolicy::Never),
+            _ => Err(format!("Unknown focus policy '{policy}', expected most-recent, first-come or never")),
+        }
+    }
+}
+
+
+/* A session waiting at a breakpoint */
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueuedBreakpoint {
+    session_id: SessionId,
+    paused_at: u64,                                                             /* Milliseconds since the epoch */
+    #[serde(skip_serializing_if = "Option::is_none")] session_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")] parser: Option<String>,   /* Parser most recently added to the tree */
+}
+
+impl QueuedBreakpoint {
+    /* Name the session and parser from the breakpoint's tree, if it has been stored */
+    fn describe(mut self, tree: Option<&DebugTree>) -> Self {
+        if let Some(tree) = tree {
+            self.session_name = Some(tree.get_session_name());
+            self.parser = tree.nodes().last().map(|node| node.name.clone());
+        }
+        self
+    }
+}
+
+
+/* Sessions waiting at a breakpoint, in the order they were hit */
+pub struct BreakpointQueue {
+    paused: Vec<QueuedBreakpoint>,
+    policy: FocusPolicy,
+}
+
+impl BreakpointQueue {
+    pub fn new(policy: FocusPolicy) -> Self {
+        BreakpointQueue { paused: Vec::new(), policy }
+    }
+
+    pub fn policy(&self) -> FocusPolicy {
+        self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: FocusPolicy) {
+        self.policy = policy;
+    }
+
+    /* Queue a session that hit a breakpoint at paused_at */
+    pub fn push(&mut self, session_id: SessionId, paused_at: u64) {
+        self.remove(session_id);
+        self.paused.push(QueuedBreakpoint { session_id, paused_at, session_name: None, parser: None });
+    }
+
+    /* Remove a session that was continued or stopped waiting */
+    pub fn remove(&mut self, session_id: SessionId) {
+        self.paused.retain(|queued| queued.session_id != session_id);
+    }
+
+    /* Keep only the sessions still waiting */
+    pub fn retain(&mut self, waiting: impl Fn(SessionId) -> bool) {
+        self.paused.retain(|queued| waiting(queued.session_id));
+    }
+
+    pub fn contains(&self, session_id: SessionId) -> bool {
+        self.paused.iter().any(|queued| queued.session_id == session_id)
+    }
+
+    /* Whether a session with a new tree is shown in place of the displayed session */
+    /* Trees not paused at a breakpoint are always shown, the policy only decides between paused sessions */
+    pub fn takes_focus(&self, session_id: SessionId, displayed: Option<SessionId>) -> bool {
+        let Some(displayed) = displayed.filter(|displayed| *displayed != session_id) else {
+            return true;
+        };
+
+        if !self.contains(session_id) {
+            return true;
+        }
+
+        match self.policy {
+            FocusPolicy::MostRecent => true,
+            FocusPolicy::FirstCome => !self.contains(displayed),
+            FocusPolicy::Never => false,
+        }
+    }
+
+    /* Session to show once the displayed session is continued, under a first come first served policy */
+    pub fn next_focus(&self) -> Option<SessionId> {
+        match self.policy {
+            FocusPolicy::FirstCome => self.paused.first().map(|queued| queued.session_id),
+            FocusPolicy::MostRecent | FocusPolicy::Never => None,
+        }
+    }
+
+    /* A queued session, described by its tree */
+    pub fn get(&self, session_id: SessionId, tree: Option<&DebugTree>) -> Option<QueuedBreakpoint> {
+        self.paused.iter()
+            .find(|queued| queued.session_id == session_id)
+            .map(|queued| queued.clone().describe(tree))
+    }
+
+    /* Every queued session, oldest first, described by their trees */
+    pub fn entries<'a>(&self, tree: impl Fn(SessionId) -> Option<&'a DebugTree>) -> Vec<QueuedBreakpoint> {
+        self.paused.iter()
+            .map(|queued| queued.clone().describe(tree(queued.session_id)))
+            .collect()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+
+    /* Breakpoint queue unit testing */
+
+    use super::{BreakpointQueue, FocusPolicy};
+    use crate::trees::{debug_tree, session_id::test::id};
+
+    #[test]
+    fn policy_parses_from_name() {
+        assert_eq!("most-recent".parse(), Ok(FocusPolicy::MostRecent));
+        assert_eq!("first-come".parse(), Ok(FocusPolicy::FirstCome));
+        assert_eq!("never".parse(), Ok(FocusPolicy::Never));
+        assert!("oldest".parse::<FocusPolicy>().is_err());
+    }
+
+    #[test]
+    fn most_recent_breakpoint_takes_focus() {
+        let mut queue = BreakpointQueue::new(FocusPolicy::MostRecent);
+        queue.push(id(0), 10);
+        queue.push(id(1), 20);
+
+        assert!(queue.takes_focus(id(1), Some(id(0))));
+        assert_eq!(queue.next_focus(), None);
+    }
+
+    #[test]
+    fn first_come_waits_for_displayed_breakpoint() {
+        let mut queue = BreakpointQueue::new(FocusPolicy::FirstCome);
+        queue.push(id(0), 10);
+        queue.push(id(1), 20);
+
+        assert!(!queue.takes_focus(id(1), Some(id(0))));
+
+        /* Continuing the displayed session hands focus to the next in line */
+        queue.remove(id(0));
+        assert_eq!(queue.next_focus(), Some(id(1)));
+        assert!(queue.takes_focus(id(1), Some(id(0))));
+    }
+
+    #[test]
+    fn never_keeps_displayed_session() {
+        let mut queue = BreakpointQueue::new(FocusPolicy::Never);
+        queue.push(id(1), 20);
+
+        assert!(!queue.takes_focus(id(1), Some(id(0))));
+        assert!(queue.takes_focus(id(1), None));
+
+        /* Trees that are not breakpoints are still shown */
+        assert!(queue.takes_focus(id(2), Some(id(0))));
+    }
+
+    #[test]
+    fn entries_are_described_by_their_trees() {
+        let mut queue = BreakpointQueue::new(FocusPolicy::MostRecent);
+        queue.push(id(0), 10);
+        queue.push(id(3), 20);
+
+        let tree = debug_tree::test::tree();
+        let entries = queue.entries(|session_id| (session_id == id(0)).then_some(&tree));
+
+        let json = serde_json::to_value(&entries).expect("Could not serialise queue");
+        assert_eq!(json[0]["sessionId"], id(0).to_string());
+        assert_eq!(json[0]["pausedAt"], 10);
+        assert!(json[0]["parser"].is_string());
+        assert!(json[1].get("parser").is_none());
+    }
+}
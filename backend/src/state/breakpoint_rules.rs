@@ -0,0 +1,150 @@
+use crate::trees::{DebugNode, DebugTree};
+
+/* Condition under which a breakpoint is worth stopping at */
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum RuleCondition {
+    ParserName { name: String },        /* A parser with this user-defined or internal name ran */
+    Failure,                            /* A parser failed */
+    RefValue { address: i32, value: String }, /* A state reference holds this value */
+    OffsetRange { start: u32, end: u32 },     /* A parser started within input offsets start..end */
+}
+
+/* User-defined rule, a breakpoint is only shown if an enabled rule matches it */
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BreakpointRule {
+    id: u32,
+    enabled: bool,
+    condition: RuleCondition,
+}
+
+impl RuleCondition {
+    /* Whether this condition holds for a breakpoint's tree */
+    pub fn matches(&self, tree: &DebugTree) -> bool {
+        /* Only look at what ran since the previous breakpoint, if the client marks it */
+        let recent: Vec<&DebugNode> = match tree.nodes().iter().any(|node| node.newly_generated) {
+            true => tree.nodes().iter().filter(|node| node.newly_generated).collect(),
+            false => tree.nodes().iter().collect(),
+        };
+
+        match self {
+            RuleCondition::ParserName { name } =>
+                recent.iter().any(|node| node.name == *name || node.internal == *name),
+            RuleCondition::Failure =>
+                recent.iter().any(|node| !node.success),
+            RuleCondition::RefValue { address, value } =>
+                tree.refs().iter().any(|(ref_address, ref_value)| ref_address == address && ref_value == value),
+            RuleCondition::OffsetRange { start, end } =>
+                recent.iter().any(|node| (*start..*end).contains(&node.input_start)),
+        }
+    }
+}
+
+/* Rules shared by every debugging session */
+pub struct RuleSet {
+    rules: Vec<BreakpointRule>,
+    next_id: u32,
+}
+
+impl RuleSet {
+    pub fn new() -> Self {
+        RuleSet { rules: Vec::new(), next_id: 0 }
+    }
+
+    /* Add an enabled rule, returning it with its allocated id */
+    pub fn add(&mut self, condition: RuleCondition) -> BreakpointRule {
+        let rule = BreakpointRule { id: self.next_id, enabled: true, condition };
+        self.next_id += 1;
+
+        self.rules.push(rule.clone());
+        rule
+    }
+
+    pub fn rules(&self) -> &[BreakpointRule] {
+        &self.rules
+    }
+
+    /* Enable or disable a rule, returning false if it does not exist */
+    pub fn set_enabled(&mut self, id: u32, enabled: bool) -> bool {
+        self.rules.iter_mut()
+            .find(|rule| rule.id == id)
+            .map(|rule| rule.enabled = enabled)
+            .is_some()
+    }
+
+    /* Remove a rule, returning false if it does not exist */
+    pub fn remove(&mut self, id: u32) -> bool {
+        let len: usize = self.rules.len();
+        self.rules.retain(|rule| rule.id != id);
+        self.rules.len() != len
+    }
+
+    /* Whether any enabled rule matches a breakpoint's tree, or None if no rule is enabled */
+    pub fn matches(&self, tree: &DebugTree) -> Option<bool> {
+        let mut enabled = self.rules.iter().filter(|rule| rule.enabled).peekable();
+        enabled.peek()?;
+
+        Some(enabled.any(|rule| rule.condition.matches(tree)))
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+
+    /* Breakpoint rule unit testing */
+
+    use super::{RuleCondition, RuleSet};
+    use crate::trees::{debug_tree, DebugTree};
+
+    #[test]
+    fn conditions_match_tree() {
+        let mut tree: DebugTree = debug_tree::test::nested_tree();
+        tree.set_refs(vec![(0, String::from("a"))]);
+
+        assert!(RuleCondition::ParserName { name: String::from("3") }.matches(&tree));
+        assert!(!RuleCondition::ParserName { name: String::from("5") }.matches(&tree));
+
+        /* Every node of the nested tree succeeds */
+        assert!(!RuleCondition::Failure.matches(&tree));
+
+        assert!(RuleCondition::RefValue { address: 0, value: String::from("a") }.matches(&tree));
+        assert!(!RuleCondition::RefValue { address: 0, value: String::from("b") }.matches(&tree));
+
+        assert!(RuleCondition::OffsetRange { start: 4, end: 6 }.matches(&tree));
+        assert!(!RuleCondition::OffsetRange { start: 5, end: 6 }.matches(&tree));
+    }
+
+    #[test]
+    fn only_enabled_rules_are_matched() {
+        let tree: DebugTree = debug_tree::test::nested_tree();
+        let mut rules = RuleSet::new();
+
+        /* Without rules there is nothing to match */
+        assert_eq!(rules.matches(&tree), None);
+
+        let failure = rules.add(RuleCondition::Failure);
+        assert_eq!(rules.matches(&tree), Some(false));
+
+        /* A disabled rule is ignored */
+        assert!(rules.set_enabled(failure.id, false));
+        assert_eq!(rules.matches(&tree), None);
+
+        rules.set_enabled(failure.id, true);
+        rules.add(RuleCondition::ParserName { name: String::from("2") });
+        assert_eq!(rules.matches(&tree), Some(true));
+
+        assert!(rules.remove(failure.id));
+        assert!(!rules.remove(failure.id));
+        assert_eq!(rules.rules().len(), 1);
+    }
+
+    #[test]
+    fn condition_deserialises_tagged() {
+        let condition: RuleCondition = serde_json::from_str(r#"{"kind":"offsetRange","start":1,"end":3}"#)
+            .expect("Could not deserialise RuleCondition");
+
+        assert_eq!(condition, RuleCondition::OffsetRange { start: 1, end: 3 });
+    }
+}
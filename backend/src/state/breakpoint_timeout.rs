@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+use super::state_manager::BreakpointCode;
+use crate::trees::SessionId;
+
+/* Longest warning given before a breakpoint times out */
+const MAX_WARNING_LEAD: Duration = Duration::from_secs(10);
+
+
+/* Action applied to a breakpoint no one continued from in time */
+#[derive(Debug, Clone, Copy, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TimeoutAction {
+    #[default]
+    Continue,   /* Continue to the next breakpoint */
+    SkipAll,    /* Skip every remaining breakpoint */
+    Terminate,  /* Stop the parse */
+}
+
+impl TimeoutAction {
+    pub fn code(&self) -> BreakpointCode {
+        match self {
+            TimeoutAction::Continue => BreakpointCode::Skip(0),
+            TimeoutAction::SkipAll => BreakpointCode::SkipAll,
+            TimeoutAction::Terminate => BreakpointCode::Terminate,
+        }
+    }
+}
+
+impl FromStr for TimeoutAction {
+    type Err = String;
+
+    fn from_str(action: &str) -> Result<Self, Self::Err> {
+        match action {
+            "continue" => Ok(TimeoutAction::Continue),
+            "skip-all" => Ok(TimeoutAction::SkipAll),
+            "terminate" => Ok(TimeoutAction::Terminate),
+            _ => Err(format!("Unknown timeout action '{action}', expected continue, skip-all or terminate")),
+        }
+    }
+}
+
+
+/* How long a breakpoint is waited at before its action is applied, forever if seconds is None */
+#[derive(Debug, Clone, Copy, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeoutPolicy {
+    #[serde(default)] seconds: Option<u64>,
+    #[serde(default)] action: TimeoutAction,
+}
+
+impl TimeoutPolicy {
+    pub fn new(seconds: Option<u64>, action: TimeoutAction) -> Self {
+        TimeoutPolicy { seconds, action }
+    }
+
+    pub fn action(&self) -> TimeoutAction {
+        self.action
+    }
+
+    /* Time waited before the warning, and then before the action, or None to wait forever */
+    /* The warning is given half the timeout ahead, up to ten seconds */
+    pub fn deadlines(&self) -> Option<(Duration, Duration)> {
+        let timeout: Duration = Duration::from_secs(self.seconds?);
+        let lead: Duration = std::cmp::min(MAX_WARNING_LEAD, timeout / 2);
+
+        Some((timeout - lead, lead))
+    }
+}
+
+
+/* Warning that a session's breakpoint is about to time out */
+#[derive(Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeoutWarning {
+    session_id: SessionId,
+    seconds_left: u64,
+    action: TimeoutAction,
+}
+
+impl TimeoutWarning {
+    pub fn new(session_id: SessionId, left: Duration, action: TimeoutAction) -> Self {
+        TimeoutWarning { session_id, seconds_left: left.as_secs(), action }
+    }
+}
+
+
+/* Timeout policy of every session, falling back to a global policy */
+pub struct TimeoutPolicies {
+    global: TimeoutPolicy,
+    sessions: HashMap<SessionId, TimeoutPolicy>,
+}
+
+impl TimeoutPolicies {
+    pub fn new(global: TimeoutPolicy) -> Self {
+        TimeoutPolicies { global, sessions: HashMap::new() }
+    }
+
+    /* A session's own policy, or the global policy if it has none or no session is given */
+    pub fn get(&self, session_id: Option<SessionId>) -> TimeoutPolicy {
+        session_id.and_then(|session_id| self.sessions.get(&session_id))
+            .copied()
+            .unwrap_or(self.global)
+    }
+
+    /* Set a session's policy, or the global policy if no session is given */
+    pub fn set(&mut self, session_id: Option<SessionId>, policy: TimeoutPolicy) {
+        match session_id {
+            Some(session_id) => { self.sessions.insert(session_id, policy); },
+            None => self.global = policy,
+        }
+    }
+
+    /* Forget a closed session's policy */
+    pub fn remove(&mut self, session_id: SessionId) {
+        self.sessions.remove(&session_id);
+    }
+
+    /* Forget every session's policy, keeping the global policy */
+    pub fn clear(&mut self) {
+        self.sessions.clear();
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+
+    /* Breakpoint timeout unit testing */
+
+    use std::time::Duration;
+
+    use super::{TimeoutAction, TimeoutPolicies, TimeoutPolicy};
+    use crate::trees::session_id::test::id;
+
+    #[test]
+    fn action_parses_from_name() {
+        assert_eq!("continue".parse(), Ok(TimeoutAction::Continue));
+        assert_eq!("skip-all".parse(), Ok(TimeoutAction::SkipAll));
+        assert_eq!("terminate".parse(), Ok(TimeoutAction::Terminate));
+        assert!("wait".parse::<TimeoutAction>().is_err());
+    }
+
+    #[test]
+    fn warning_leads_timeout() {
+        assert_eq!(TimeoutPolicy::default().deadlines(), None);
+        assert_eq!(TimeoutPolicy::new(Some(4), TimeoutAction::Continue).deadlines(), Some((Duration::from_secs(2), Duration::from_secs(2))));
+        assert_eq!(TimeoutPolicy::new(Some(60), TimeoutAction::Continue).deadlines(), Some((Duration::from_secs(50), Duration::from_secs(10))));
+    }
+
+    #[test]
+    fn session_policy_overrides_global() {
+        let mut policies = TimeoutPolicies::new(TimeoutPolicy::new(Some(30), TimeoutAction::SkipAll));
+        policies.set(Some(id(1)), TimeoutPolicy::default());
+
+        assert_eq!(policies.get(Some(id(1))), TimeoutPolicy::default());
+        assert_eq!(policies.get(Some(id(2))).action(), TimeoutAction::SkipAll);
+
+        policies.set(None, TimeoutPolicy::new(None, TimeoutAction::Terminate));
+        assert_eq!(policies.get(None).action(), TimeoutAction::Terminate);
+    }
+
+    #[test]
+    fn removed_session_falls_back_to_global() {
+        let mut policies = TimeoutPolicies::new(TimeoutPolicy::new(Some(30), TimeoutAction::SkipAll));
+        policies.set(Some(id(1)), TimeoutPolicy::default());
+        policies.set(Some(id(2)), TimeoutPolicy::default());
+
+        policies.remove(id(1));
+        assert_eq!(policies.get(Some(id(1))).action(), TimeoutAction::SkipAll);
+        assert_eq!(policies.get(Some(id(2))), TimeoutPolicy::default());
+
+        policies.clear();
+        assert_eq!(policies.get(Some(id(2))).action(), TimeoutAction::SkipAll);
+    }
+
+    #[test]
+    fn policy_deserialises_without_seconds() {
+        let policy: TimeoutPolicy = serde_json::from_str(r#"{"action":"terminate"}"#).expect("Could not deserialise policy");
+        assert_eq!(policy, TimeoutPolicy::new(None, TimeoutAction::Terminate));
+    }
+}
@@ -0,0 +1,118 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::events::Event;
+use crate::state::state_manager::{BreakpointCode, DirectoryKind};
+
+use super::session_core::{Frontend, SessionCore};
+use super::breakpoint_timeout::TimeoutPolicy;
+use super::breakpoint_queue::FocusPolicy;
+use super::StateError;
+
+
+/* How the headless server continues from breakpoints, as there is no user to do so */
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum BreakpointPolicy {
+    Step,       /* Continue to the next breakpoint, recording the tree at each */
+    #[default]
+    SkipAll,    /* Skip every remaining breakpoint, recording only the final tree */
+    Terminate,  /* Stop debugging at the first breakpoint */
+}
+
+impl BreakpointPolicy {
+    pub fn code(&self) -> BreakpointCode {
+        match self {
+            BreakpointPolicy::Step => BreakpointCode::Skip(0),
+            BreakpointPolicy::SkipAll => BreakpointCode::SkipAll,
+            BreakpointPolicy::Terminate => BreakpointCode::Terminate,
+        }
+    }
+}
+
+impl FromStr for BreakpointPolicy {
+    type Err = String;
+
+    fn from_str(policy: &str) -> Result<Self, Self::Err> {
+        match policy {
+            "step" => Ok(BreakpointPolicy::Step),
+            "skip-all" => Ok(BreakpointPolicy::SkipAll),
+            "terminate" => Ok(BreakpointPolicy::Terminate),
+            _ => Err(format!("Unknown breakpoint policy '{policy}', expected step, skip-all or terminate")),
+        }
+    }
+}
+
+
+/* Frontend of a server running without Tauri, which writes trees to a directory instead of showing them */
+pub struct HeadlessFrontend {
+    trees_dir: PathBuf,         /* Directory every received tree is written to */
+}
+
+impl Frontend for HeadlessFrontend {
+    /* There is no frontend to notify */
+    fn emit(&self, _event: Event<'_>) -> Result<(), StateError> {
+        Ok(())
+    }
+
+    fn system_path(&self, dir: DirectoryKind) -> Result<PathBuf, StateError> {
+        match dir {
+            DirectoryKind::SavedTrees => Ok(self.trees_dir.clone()),
+            DirectoryKind::Downloads => Err(StateError::GetDownloadPathFail),
+        }
+    }
+}
+
+/* State of a headless server, where the most recently received tree is the displayed one */
+pub type HeadlessState = SessionCore<HeadlessFrontend>;
+
+impl HeadlessState {
+    pub fn new(trees_dir: PathBuf) -> Self {
+        /* Without a frontend earlier snapshots are never looked back through, so only the latest is kept */
+        SessionCore::with_frontend(HeadlessFrontend { trees_dir }, TimeoutPolicy::default(), FocusPolicy::default(), 1)
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+
+    /* Headless state unit testing */
+
+    use std::path::PathBuf;
+
+    use super::{BreakpointPolicy, HeadlessState};
+    use crate::state::StateManager;
+    use crate::trees::{debug_tree, DebugTree, SessionId};
+
+    /* Fresh directory for the trees written by a test */
+    fn trees_dir(name: &str) -> PathBuf {
+        let dir: PathBuf = std::env::temp_dir().join(format!("dill-headless-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("Could not create trees directory");
+        dir
+    }
+
+    #[test]
+    fn policy_parses_from_name() {
+        assert_eq!("step".parse(), Ok(BreakpointPolicy::Step));
+        assert_eq!("skip-all".parse(), Ok(BreakpointPolicy::SkipAll));
+        assert_eq!("terminate".parse(), Ok(BreakpointPolicy::Terminate));
+        assert!("pause".parse::<BreakpointPolicy>().is_err());
+    }
+
+    #[test]
+    fn received_tree_is_written_and_displayed() {
+        let dir: PathBuf = trees_dir("written");
+        let state = HeadlessState::new(dir.clone());
+
+        let tree: DebugTree = debug_tree::test::tree();
+        let session_id: SessionId = tree.get_session_id();
+
+        state.set_tree(tree).expect("Tree should be stored");
+        state.update_tree(session_id).expect("Tree should be written");
+
+        assert!(dir.join(format!("{session_id}.json")).exists());
+        assert_eq!(state.displayed_session_id().ok(), Some(session_id));
+
+        std::fs::remove_dir_all(dir).expect("Could not remove trees directory");
+    }
+}
@@ -0,0 +1,489 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+
+use indexmap::IndexMap;
+
+use crate::events::Event;
+use crate::server::SkipsSender;
+use crate::state::state_manager::{BreakpointCode, BreakpointMessage, DirectoryKind, MergedDelta, UpdateTreeError};
+use crate::trees::{deep_json, DebugNode, DebugTree, ParsleyTreeDelta, SavedTree, SessionId, TreeDelta};
+
+use super::breakpoint_rules::{BreakpointRule, RuleCondition, RuleSet};
+use super::session_store::SessionStore;
+use super::timeline::{RefChange, SnapshotDiff, SnapshotSummary, Timeline};
+use super::watchpoints::Watchpoints;
+use super::breakpoint_timeout::{TimeoutPolicies, TimeoutPolicy};
+use super::breakpoint_queue::{BreakpointQueue, FocusPolicy, QueuedBreakpoint};
+use super::timeline::now_millis;
+use super::{StateError, StateManager};
+
+
+/* What differs between the app and the headless server: where events go and where trees are saved */
+pub trait Frontend: Send + Sync + 'static {
+    fn emit(&self, event: Event<'_>) -> Result<(), StateError>;
+
+    fn system_path(&self, dir: DirectoryKind) -> Result<PathBuf, StateError>;
+}
+
+/* Unsynchronised session bookkeeping */
+struct SessionCoreInternal {
+    sessions: SessionStore,                         /* Tree and node index of every session */
+    displayed: Option<SessionId>,                   /* Session id of the tree shown in the frontend */
+    skips_tx: HashMap<SessionId, SkipsSender>,      /* Transmitter how many breakpoints to skip, sent to parsley */
+    tabs: IndexMap<SessionId, String>,              /* List of saved tree names */
+    saved_refs: HashMap<SessionId, Vec<(i32, String)>>, /* Map of sessionId to saved refs for a tab */
+    rules: RuleSet,                                 /* Rules deciding which breakpoints are shown */
+    watchpoints: Watchpoints,                       /* References whose changes stop at a breakpoint */
+    timeouts: TimeoutPolicies,                      /* How long breakpoints are waited at before continuing */
+    queue: BreakpointQueue,                         /* Sessions waiting at a breakpoint, and which is shown */
+}
+
+impl SessionCoreInternal {
+    /* Resume a session's breakpoint, recording the references returned against its snapshot */
+    fn send_breakpoint(&mut self, frontend: &impl Frontend, session_id: SessionId, message: BreakpointMessage) -> Result<(), StateError> {
+        let tx: SkipsSender = self.skips_tx.remove(&session_id).ok_or(StateError::ChannelError)?;
+        self.queue.remove(session_id);
+
+        if let Some(timeline) = self.sessions.timeline_mut(session_id) {
+            timeline.set_refs_returned(message.refs.clone());
+        }
+
+        /* Hand focus to the next session in line, if the continued one was shown */
+        if self.displayed == Some(session_id) {
+            if let Some(next) = self.queue.next_focus() {
+                let _ = self.focus(frontend, next);
+            }
+        }
+
+        tx.send(message).map_err(|_| StateError::ChannelError)
+    }
+
+    /* Show a session's tree if the focus policy allows, otherwise tell the frontend it is waiting */
+    fn focus(&mut self, frontend: &impl Frontend, session_id: SessionId) -> Result<(), StateError> {
+        if self.queue.takes_focus(session_id, self.displayed) {
+            self.displayed = Some(session_id);
+
+            let tree: &DebugTree = self.sessions.tree(session_id).ok_or(StateError::TreeNotFound)?;
+            return frontend.emit(Event::TreeReady(tree)); /* Notify frontend listener - call inline to avoid deadlock */
+        }
+
+        match self.queue.get(session_id, self.sessions.tree(session_id)) {
+            Some(queued) => frontend.emit(Event::BreakpointQueued(&queued)),
+            None => Ok(()),
+        }
+    }
+}
+
+
+/* Sessions, breakpoints and rules shared by every state, synchronised and paired with its frontend */
+pub struct SessionCore<F: Frontend> {
+    inner: Mutex<SessionCoreInternal>,
+    pub(super) frontend: F,
+}
+
+impl<F: Frontend> SessionCore<F> {
+    pub(super) fn with_frontend(frontend: F, timeout: TimeoutPolicy, focus: FocusPolicy, max_snapshots: usize) -> Self {
+        SessionCore {
+            inner: Mutex::new(SessionCoreInternal {
+                sessions: SessionStore::new(max_snapshots),
+                displayed: None,
+                skips_tx: HashMap::new(),
+                tabs: IndexMap::new(),
+                saved_refs: HashMap::new(),
+                rules: RuleSet::new(),
+                watchpoints: Watchpoints::new(),
+                timeouts: TimeoutPolicies::new(timeout),
+                queue: BreakpointQueue::new(focus),
+            }),
+            frontend,
+        }
+    }
+
+    /* Access wrapped inner SessionCoreInternal struct */
+    fn inner(&self) -> Result<MutexGuard<'_, SessionCoreInternal>, StateError> {
+        self.inner.lock()
+            .map_err(|_| StateError::LockFailed)
+    }
+
+    fn tab_names(&self) -> Result<Vec<String>, StateError> {
+        Ok(self.inner()?.tabs.values().cloned().collect())
+    }
+
+    fn is_debuggable_session(&self, session_id: SessionId) -> Result<bool, StateError> {
+        Ok(self.inner()?.skips_tx.get(&session_id).is_some_and(|tx| !tx.is_closed()))
+    }
+}
+
+
+impl<F: Frontend> StateManager for SessionCore<F> {
+    fn emit<'a>(&self, event: Event<'a>) -> Result<(), StateError> {
+        self.frontend.emit(event)
+    }
+
+    /* Store a session's tree and show it in the frontend */
+    fn set_tree(&self, tree: DebugTree) -> Result<(), StateError> {
+        let mut state: MutexGuard<SessionCoreInternal> = self.inner()?;
+        let session_id: SessionId = tree.get_session_id();
+
+        state.tabs.insert(session_id, tree.get_session_name());
+
+        /* Replace any tree previously posted for this session */
+        state.sessions.insert(tree);
+        state.focus(&self.frontend, session_id)
+    }
+
+    /* Store a session's tree as its next snapshot, leaving the frontend showing what it was */
+    fn record_tree(&self, tree: DebugTree) -> Result<(), StateError> {
+        let mut state: MutexGuard<SessionCoreInternal> = self.inner()?;
+
+        state.tabs.insert(tree.get_session_id(), tree.get_session_name());
+        state.sessions.record(tree);
+        Ok(())
+    }
+
+    /* Get the tree stored for a session */
+    fn get_tree(&self, session_id: SessionId) -> Result<DebugTree, StateError> {
+        self.inner()?
+            .sessions
+            .tree(session_id)
+            .ok_or(StateError::TreeNotFound)
+            .cloned()
+    }
+
+    /* Get the children of the node associated with node ID in a session's tree */
+    fn get_children(&self, session_id: SessionId, node_id: u32) -> Result<Vec<DebugNode>, StateError> {
+        self.inner()?
+            .sessions
+            .children(session_id, node_id)
+            .ok_or(StateError::NodeNotFound(node_id))
+            .map(|children| children.into_iter().cloned().collect())
+    }
+
+    /* Merge a delta into a session's latest tree as a new snapshot, reading whether it should pause there */
+    fn merge_delta(&self, session_id: SessionId, delta: ParsleyTreeDelta) -> Result<MergedDelta, StateError> {
+        let mut state: MutexGuard<SessionCoreInternal> = self.inner()?;
+        let SessionCoreInternal { sessions, rules, watchpoints, .. } = &mut *state;
+
+        /* Compare against the session's tree before the delta is merged into it */
+        let previous: Vec<(i32, String)> = sessions.latest(session_id).ok_or(StateError::TreeNotFound)?.refs();
+        let changes: Option<Vec<RefChange>> = watchpoints.changes(Some(&previous), &delta.refs());
+
+        /* Merged in place, the previous breakpoint's snapshot is kept as what the tree was before */
+        /* The session is shown once it is known whether it is paused, by focus_session */
+        let tree_delta: TreeDelta = sessions.merge(session_id, delta).ok_or(StateError::TreeNotFound)??;
+        let tree: &DebugTree = sessions.latest(session_id).ok_or(StateError::TreeNotFound)?;
+
+        Ok(MergedDelta { delta: tree_delta, input: tree.get_input().clone(), matches_rules: rules.matches(tree), changes })
+    }
+
+    /* Show a stored tree in the frontend without re-reading it from disk */
+    fn display_tree(&self, session_id: SessionId) -> Result<(), StateError> {
+        let mut state: MutexGuard<SessionCoreInternal> = self.inner()?;
+
+        let tree: &DebugTree = state.sessions.tree(session_id).ok_or(StateError::TreeNotFound)?;
+        self.frontend.emit(Event::TreeReady(tree))?;
+
+        state.displayed = Some(session_id);
+        Ok(())
+    }
+
+    fn timeline(&self, session_id: SessionId) -> Result<Vec<SnapshotSummary>, StateError> {
+        self.inner()?
+            .sessions
+            .timeline(session_id)
+            .map(Timeline::summaries)
+            .ok_or(StateError::TreeNotFound)
+    }
+
+    /* Show an earlier breakpoint snapshot of a session in the frontend */
+    fn view_snapshot(&self, session_id: SessionId, index: usize) -> Result<(), StateError> {
+        let mut state: MutexGuard<SessionCoreInternal> = self.inner()?;
+
+        if state.sessions.timeline(session_id).is_none() {
+            return Err(StateError::TreeNotFound);
+        }
+
+        let tree: &DebugTree = state.sessions.view(session_id, index).ok_or(StateError::SnapshotNotFound(index))?;
+        self.frontend.emit(Event::TreeReady(tree))?;
+
+        state.displayed = Some(session_id);
+        Ok(())
+    }
+
+    fn diff_snapshot(&self, session_id: SessionId, index: usize) -> Result<SnapshotDiff, StateError> {
+        self.inner()?
+            .sessions
+            .timeline(session_id)
+            .ok_or(StateError::TreeNotFound)?
+            .diff(index)
+            .ok_or(StateError::SnapshotNotFound(index))
+    }
+
+    fn displayed_session_id(&self) -> Result<SessionId, StateError> {
+        self.inner()?
+            .displayed
+            .ok_or(StateError::TreeNotFound)
+    }
+
+    fn transmit_breakpoint_skips(&self, session_id: SessionId, code: BreakpointCode) -> Result<(), StateError> {
+        let mut state: MutexGuard<SessionCoreInternal> = self.inner()?;
+
+        let refs: Vec<(i32, String)> = state.saved_refs.get(&session_id).cloned().unwrap_or_default();
+        state.send_breakpoint(&self.frontend, session_id, BreakpointMessage { code, refs })
+    }
+
+    fn resume_breakpoint(&self, session_id: SessionId, code: BreakpointCode, refs: Vec<(i32, String)>) -> Result<(), StateError> {
+        let mut state: MutexGuard<SessionCoreInternal> = self.inner()?;
+
+        /* A breakpoint no longer waited at keeps the references it was last resumed with */
+        state.send_breakpoint(&self.frontend, session_id, BreakpointMessage { code, refs: refs.clone() })?;
+        state.saved_refs.insert(session_id, refs);
+        Ok(())
+    }
+
+    fn system_path(&self, dir: DirectoryKind) -> Result<PathBuf, StateError> {
+        self.frontend.system_path(dir)
+    }
+
+    fn rmv_tab(&self, index: usize) -> Result<Vec<String>, StateError> {
+        let (session_id, _) = self.get_tab(index)?;
+
+        if self.is_debuggable_session(session_id)? {
+            self.transmit_breakpoint_skips(session_id, BreakpointCode::Terminate)?;
+        }
+
+        /* Forget the session, releasing the lock before reading the remaining names */
+        {
+            let mut state: MutexGuard<'_, SessionCoreInternal> = self.inner()?;
+
+            state.tabs.shift_remove(&session_id);
+            state.saved_refs.remove(&session_id);
+            state.sessions.remove(session_id);
+            state.timeouts.remove(session_id);
+
+            if state.displayed == Some(session_id) {
+                state.displayed = None;
+            }
+        }
+
+        self.tab_names()
+    }
+
+    fn get_tab(&self, index: usize) -> Result<(SessionId, String), StateError> {
+        let state: MutexGuard<'_, SessionCoreInternal> = self.inner()?;
+
+        state.tabs.get_index(index).map(|(id, name)| (*id, name.clone())).ok_or(StateError::TabOutOfBounds)
+    }
+
+    fn debuggable_session_ids(&self) -> Result<Vec<SessionId>, StateError> {
+        let state: MutexGuard<'_, SessionCoreInternal> = self.inner()?;
+        Ok(state.tabs.keys().filter(|id| state.skips_tx.get(id).is_some_and(|tx| !tx.is_closed())).cloned().collect())
+    }
+
+    /* Ids are unique across runs, so need no state to allocate */
+    fn next_session_id(&self) -> Result<SessionId, StateError> {
+        Ok(SessionId::new())
+    }
+
+    fn new_transmitter(&self, session_id: SessionId, tx: SkipsSender) -> Result<(), StateError> {
+        let mut state: MutexGuard<'_, SessionCoreInternal> = self.inner()?;
+
+        /* Forget breakpoints whose parser stopped waiting, so they do not block new ones */
+        let SessionCoreInternal { skips_tx, queue, .. } = &mut *state;
+        skips_tx.retain(|_, tx| !tx.is_closed());
+        queue.retain(|session_id| skips_tx.contains_key(&session_id));
+
+        match state.skips_tx.contains_key(&session_id) {
+            true => Err(StateError::ChannelError),
+            false => {
+                state.skips_tx.insert(session_id, tx);
+                state.queue.push(session_id, now_millis());
+                Ok(())
+            },
+        }
+    }
+
+    fn get_refs(&self, session_id: SessionId) -> Result<Vec<(i32, String)>, StateError> {
+        Ok(self.inner()?.saved_refs.get(&session_id).cloned().unwrap_or_default())
+    }
+
+    fn reset_refs(&self, session_id: SessionId, default_refs: Vec<(i32, String)>) -> Result<(), StateError> {
+        self.inner()?.saved_refs.insert(session_id, default_refs);
+        Ok(())
+    }
+
+    fn reset_trees(&self) -> Result<(), StateError> {
+        let mut state: MutexGuard<SessionCoreInternal> = self.inner()?;
+
+        state.tabs = IndexMap::new();
+        state.saved_refs = HashMap::new();
+        state.sessions.clear();
+        state.timeouts.clear();
+        state.displayed = None;
+
+        Ok(())
+    }
+
+    fn add_rule(&self, condition: RuleCondition) -> Result<BreakpointRule, StateError> {
+        Ok(self.inner()?.rules.add(condition))
+    }
+
+    fn rules(&self) -> Result<Vec<BreakpointRule>, StateError> {
+        Ok(self.inner()?.rules.rules().to_vec())
+    }
+
+    fn set_rule_enabled(&self, rule_id: u32, enabled: bool) -> Result<(), StateError> {
+        match self.inner()?.rules.set_enabled(rule_id, enabled) {
+            true => Ok(()),
+            false => Err(StateError::RuleNotFound(rule_id)),
+        }
+    }
+
+    fn remove_rule(&self, rule_id: u32) -> Result<(), StateError> {
+        match self.inner()?.rules.remove(rule_id) {
+            true => Ok(()),
+            false => Err(StateError::RuleNotFound(rule_id)),
+        }
+    }
+
+    fn matches_rules(&self, tree: &DebugTree) -> Result<Option<bool>, StateError> {
+        Ok(self.inner()?.rules.matches(tree))
+    }
+
+    fn add_watchpoint(&self, address: i32) -> Result<(), StateError> {
+        match self.inner()?.watchpoints.add(address) {
+            true => Ok(()),
+            false => Err(StateError::WatchpointExists(address)),
+        }
+    }
+
+    fn watchpoints(&self) -> Result<Vec<i32>, StateError> {
+        Ok(self.inner()?.watchpoints.addresses())
+    }
+
+    fn remove_watchpoint(&self, address: i32) -> Result<(), StateError> {
+        match self.inner()?.watchpoints.remove(address) {
+            true => Ok(()),
+            false => Err(StateError::WatchpointNotFound(address)),
+        }
+    }
+
+    fn watched_changes(&self, session_id: SessionId, refs: &[(i32, String)]) -> Result<Option<Vec<RefChange>>, StateError> {
+        let state: MutexGuard<SessionCoreInternal> = self.inner()?;
+        let previous: Option<Vec<(i32, String)>> = state.sessions.latest(session_id).map(DebugTree::refs);
+
+        Ok(state.watchpoints.changes(previous.as_deref(), refs))
+    }
+
+    fn focus_session(&self, session_id: SessionId) -> Result<(), StateError> {
+        let mut state: MutexGuard<SessionCoreInternal> = self.inner()?;
+
+        /* The shown session's frontend reloads its tree from the delta itself */
+        match state.displayed == Some(session_id) {
+            true => Ok(()),
+            false => state.focus(&self.frontend, session_id),
+        }
+    }
+
+    fn breakpoint_queue(&self) -> Result<Vec<QueuedBreakpoint>, StateError> {
+        let state: MutexGuard<SessionCoreInternal> = self.inner()?;
+        Ok(state.queue.entries(|session_id| state.sessions.tree(session_id)))
+    }
+
+    fn set_focus_policy(&self, policy: FocusPolicy) -> Result<(), StateError> {
+        self.inner()?.queue.set_policy(policy);
+        Ok(())
+    }
+
+    fn focus_policy(&self) -> Result<FocusPolicy, StateError> {
+        Ok(self.inner()?.queue.policy())
+    }
+
+    fn set_timeout_policy(&self, session_id: Option<SessionId>, policy: TimeoutPolicy) -> Result<(), StateError> {
+        self.inner()?.timeouts.set(session_id, policy);
+        Ok(())
+    }
+
+    fn timeout_policy(&self, session_id: Option<SessionId>) -> Result<TimeoutPolicy, StateError> {
+        Ok(self.inner()?.timeouts.get(session_id))
+    }
+
+    /* Write a session's latest tree to the saved trees directory */
+    fn update_tree(&self, session_id: SessionId) -> Result<(), UpdateTreeError> {
+        /* Convert under the lock, but write the file after releasing it */
+        let new_tree: SavedTree = {
+            let state: MutexGuard<SessionCoreInternal> = self.inner().map_err(|_| UpdateTreeError::LockFailed)?;
+            state.sessions.latest(session_id).map(SavedTree::from).ok_or(UpdateTreeError::TreeNotFound)?
+        };
+
+        /* Get the serialised JSON */
+        let tree_json: String = deep_json::to_string(&new_tree)
+            .map_err(|_| UpdateTreeError::SerialiseFailed)?;
+
+        /* Open the json file to update the tree */
+        /* TODO: look into only updating the extra bits rather than replacing the tree */
+        let file_path = PathBuf::from(format!("{}.json", session_id));
+        let full_path: PathBuf = self.system_path_to(DirectoryKind::SavedTrees, file_path).map_err(|_| UpdateTreeError::OpenFileFailed)?;
+
+        let mut data_file: File = File::create(full_path).map_err(|_| UpdateTreeError::OpenFileFailed)?;
+
+        /* Write tree json to the json file */
+        data_file.write_all(tree_json.as_bytes()).map_err(|_| UpdateTreeError::WriteTreeFailed)?;
+
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+
+    /* Session core unit testing */
+
+    use std::path::PathBuf;
+
+    use rocket::tokio::sync::oneshot;
+
+    use crate::state::breakpoint_rules::RuleCondition;
+    use crate::state::state_manager::{BreakpointCode, BreakpointMessage, MergedDelta};
+    use crate::state::timeline::RefChange;
+    use crate::state::{HeadlessState, StateError, StateManager};
+    use crate::trees::{debug_tree, delta_tree};
+    use crate::trees::session_id::test::id;
+
+    #[test]
+    fn refs_are_saved_only_when_resumed() {
+        let state = HeadlessState::new(PathBuf::new());
+        let refs = vec![(0, String::from("a"))];
+
+        /* Nothing is waiting, so the references are not kept */
+        assert!(matches!(state.resume_breakpoint(id(0), BreakpointCode::Skip(0), refs.clone()), Err(StateError::ChannelError)));
+        assert_eq!(state.get_refs(id(0)).ok(), Some(Vec::new()));
+
+        let (tx, mut rx) = oneshot::channel::<BreakpointMessage>();
+        state.new_transmitter(id(0), tx).expect("Transmitter should be stored");
+
+        state.resume_breakpoint(id(0), BreakpointCode::Skip(0), refs.clone()).expect("Breakpoint should resume");
+        assert_eq!(rx.try_recv(), Ok(BreakpointMessage { code: BreakpointCode::Skip(0), refs: refs.clone() }));
+        assert_eq!(state.get_refs(id(0)).ok(), Some(refs));
+    }
+
+    #[test]
+    fn merged_delta_reads_rules_and_watchpoints() {
+        let state = HeadlessState::new(PathBuf::new());
+        state.set_tree(debug_tree::test::nested_tree()).expect("Tree should be stored");
+        state.add_rule(RuleCondition::ParserName { name: String::from("5") }).expect("Rule should be added");
+        state.add_watchpoint(0).expect("Watchpoint should be added");
+
+        let MergedDelta { delta, matches_rules, changes, .. } = state.merge_delta(id(0), delta_tree::test::delta()).expect("Delta should merge");
+
+        /* Only the merged tree has a parser named "5", and the delta sets the watched reference */
+        assert_eq!(delta.num_nodes(), 3);
+        assert_eq!(matches_rules, Some(true));
+        assert_eq!(changes, Some(vec![RefChange { address: 0, previous: None, current: Some(String::from("a")) }]));
+        assert!(matches!(state.merge_delta(id(1), delta_tree::test::delta()), Err(StateError::TreeNotFound)));
+    }
+}
@@ -0,0 +1,239 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::trees::{DebugNode, DebugTree, ParsleyTreeDelta, SessionId, TreeDelta};
+use crate::trees::delta_tree::MergeError;
+
+use super::timeline::Timeline;
+
+/* Trees posted for one session, and which of them is shown in the frontend */
+struct Session {
+    timeline: Timeline,
+    viewing: Option<usize>,     /* Index of an earlier snapshot being viewed, otherwise the latest */
+    rebuilt: Option<DebugTree>, /* Viewed snapshot, if it had to be rebuilt from a later tree */
+}
+
+impl Session {
+    fn tree(&self) -> &DebugTree {
+        self.rebuilt.as_ref()
+            .or_else(|| self.viewing.and_then(|index| self.timeline.whole(index)))
+            .unwrap_or(self.timeline.latest())
+    }
+
+    /* Show the latest tree again */
+    fn view_latest(&mut self) {
+        self.viewing = None;
+        self.rebuilt = None;
+    }
+}
+
+/* Store of every session's trees, keyed by session id */
+pub struct SessionStore {
+    sessions: HashMap<SessionId, Session>,
+    max_snapshots: usize,       /* Most snapshots kept per session */
+}
+
+impl SessionStore {
+    /* Initialise an empty store, keeping at most max_snapshots per session */
+    pub fn new(max_snapshots: usize) -> Self {
+        SessionStore { sessions: HashMap::new(), max_snapshots }
+    }
+
+    /* Insert tree under its session id, recording it after any previous trees for that session */
+    pub fn insert(&mut self, tree: DebugTree) {
+        match self.sessions.get_mut(&tree.get_session_id()) {
+            Some(session) => {
+                session.timeline.push(tree);
+                session.view_latest();
+            },
+            None => {
+                let timeline = Timeline::new(tree, self.max_snapshots);
+                self.sessions.insert(timeline.latest().get_session_id(), Session { timeline, viewing: None, rebuilt: None });
+            },
+        }
+    }
+
+    /* Record tree after any previous trees for its session, without changing which of them is shown */
+    pub fn record(&mut self, tree: DebugTree) {
+        match self.sessions.get_mut(&tree.get_session_id()) {
+            Some(session) => {
+                /* The latest tree stays shown as the snapshot it becomes */
+                let shown: usize = session.viewing.unwrap_or(session.timeline.latest_index());
+                session.timeline.push(tree);
+                session.viewing = Some(shown);
+            },
+            None => self.insert(tree),
+        }
+    }
+
+    /* Merge a delta into a session's latest tree, recording the result as its next snapshot */
+    pub fn merge(&mut self, session_id: SessionId, delta: ParsleyTreeDelta) -> Option<Result<TreeDelta, MergeError>> {
+        let session: &mut Session = self.sessions.get_mut(&session_id)?;
+
+        let merged: Result<TreeDelta, MergeError> = session.timeline.merge(delta);
+        if merged.is_ok() {
+            session.view_latest();
+        }
+
+        Some(merged)
+    }
+
+    /* Get the tree shown for a session */
+    pub fn tree(&self, session_id: SessionId) -> Option<&DebugTree> {
+        self.sessions.get(&session_id).map(Session::tree)
+    }
+
+    /* Get the most recently posted tree for a session, whichever snapshot is shown */
+    pub fn latest(&self, session_id: SessionId) -> Option<&DebugTree> {
+        self.sessions.get(&session_id).map(|session| session.timeline.latest())
+    }
+
+    /* Get the children of a node from the tree shown for a session */
+    pub fn children(&self, session_id: SessionId, node_id: u32) -> Option<Vec<&DebugNode>> {
+        self.tree(session_id).and_then(|tree| tree.get_children(node_id))
+    }
+
+    pub fn timeline(&self, session_id: SessionId) -> Option<&Timeline> {
+        self.sessions.get(&session_id).map(|session| &session.timeline)
+    }
+
+    pub fn timeline_mut(&mut self, session_id: SessionId) -> Option<&mut Timeline> {
+        self.sessions.get_mut(&session_id).map(|session| &mut session.timeline)
+    }
+
+    /* Show an earlier snapshot of a session, returning its tree if it exists */
+    pub fn view(&mut self, session_id: SessionId, index: usize) -> Option<&DebugTree> {
+        let session: &mut Session = self.sessions.get_mut(&session_id)?;
+
+        session.rebuilt = match session.timeline.tree(index)? {
+            Cow::Borrowed(_) => None,
+            Cow::Owned(tree) => Some(tree),
+        };
+        session.viewing = Some(index);
+        Some(session.tree())
+    }
+
+    /* Remove a session, returning its latest tree if it was stored */
+    pub fn remove(&mut self, session_id: SessionId) -> Option<DebugTree> {
+        self.sessions.remove(&session_id).map(|session| session.timeline.into_latest())
+    }
+
+    /* Remove every session */
+    pub fn clear(&mut self) {
+        self.sessions.clear()
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+
+    /* Session store unit testing */
+
+    use super::SessionStore;
+    use crate::state::timeline::DEFAULT_MAX_SNAPSHOTS;
+    use crate::trees::{debug_tree, delta_tree, DebugTree, SessionId};
+    use crate::trees::session_id::test::id;
+
+    #[test]
+    fn inserted_tree_is_stored_by_session() {
+        let mut store = SessionStore::new(DEFAULT_MAX_SNAPSHOTS);
+        let tree: DebugTree = debug_tree::test::nested_tree();
+        let session_id: SessionId = tree.get_session_id();
+
+        store.insert(tree.clone());
+
+        assert_eq!(store.tree(session_id), Some(&tree));
+        assert!(store.tree(SessionId::new()).is_none());
+    }
+
+    #[test]
+    fn sessions_are_stored_independently() {
+        let mut store = SessionStore::new(DEFAULT_MAX_SNAPSHOTS);
+
+        let mut first: DebugTree = debug_tree::test::tree();
+        first.set_session_id(id(0));
+        let mut second: DebugTree = debug_tree::test::nested_tree();
+        second.set_session_id(id(1));
+
+        store.insert(first.clone());
+        store.insert(second.clone());
+
+        assert_eq!(store.tree(id(0)), Some(&first));
+        assert_eq!(store.tree(id(1)), Some(&second));
+
+        /* Nested tree has nodes not present in the single-node tree */
+        assert!(store.children(id(0), 1).is_none());
+        assert_eq!(store.children(id(1), 1).map(|children| children[0].name.as_str()), Some("2"));
+    }
+
+    #[test]
+    fn removed_session_is_forgotten() {
+        let mut store = SessionStore::new(DEFAULT_MAX_SNAPSHOTS);
+        let tree: DebugTree = debug_tree::test::nested_tree();
+        let session_id: SessionId = tree.get_session_id();
+
+        store.insert(tree.clone());
+
+        assert_eq!(store.remove(session_id), Some(tree));
+        assert!(store.tree(session_id).is_none());
+        assert!(store.children(session_id, 0).is_none());
+    }
+
+    #[test]
+    fn reinserted_session_can_view_earlier_snapshots() {
+        let mut store = SessionStore::new(DEFAULT_MAX_SNAPSHOTS);
+        let first: DebugTree = debug_tree::test::tree();
+        let mut second: DebugTree = debug_tree::test::nested_tree();
+        second.set_session_id(first.get_session_id());
+        let session_id: SessionId = first.get_session_id();
+
+        store.insert(first.clone());
+        store.insert(second.clone());
+        assert_eq!(store.tree(session_id), Some(&second));
+
+        /* Viewing an earlier snapshot changes the shown tree, but not the latest */
+        assert_eq!(store.view(session_id, 0), Some(&first));
+        assert_eq!(store.tree(session_id), Some(&first));
+        assert_eq!(store.latest(session_id), Some(&second));
+        assert!(store.view(session_id, 2).is_none());
+
+        /* A newly posted tree is shown again */
+        store.insert(second.clone());
+        assert_eq!(store.tree(session_id), Some(&second));
+    }
+
+    #[test]
+    fn merged_session_can_view_snapshot_before_merge() {
+        let mut store = SessionStore::new(DEFAULT_MAX_SNAPSHOTS);
+        let tree: DebugTree = debug_tree::test::nested_tree();
+        let session_id: SessionId = tree.get_session_id();
+
+        store.insert(tree.clone());
+        let tree_delta = store.merge(session_id, delta_tree::test::delta()).expect("Session should be stored");
+        assert!(tree_delta.is_ok());
+        assert_eq!(store.latest(session_id).map(|latest| latest.nodes().len()), Some(8));
+
+        /* The snapshot before the delta is rebuilt from the merged tree */
+        assert_eq!(store.view(session_id, 0), Some(&tree));
+        assert_eq!(store.tree(session_id), Some(&tree));
+        assert!(store.merge(SessionId::new(), delta_tree::test::delta()).is_none());
+    }
+
+    #[test]
+    fn recorded_tree_leaves_shown_tree() {
+        let mut store = SessionStore::new(DEFAULT_MAX_SNAPSHOTS);
+        let first: DebugTree = debug_tree::test::tree();
+        let mut second: DebugTree = debug_tree::test::nested_tree();
+        second.set_session_id(first.get_session_id());
+        let session_id: SessionId = first.get_session_id();
+
+        store.insert(first.clone());
+        store.record(second.clone());
+
+        /* The frontend was not told of the recorded tree, so it is still shown the first */
+        assert_eq!(store.latest(session_id), Some(&second));
+        assert_eq!(store.tree(session_id), Some(&first));
+        assert!(store.children(session_id, 1).is_none());
+    }
+}
@@ -4,37 +4,93 @@ use ambassador::delegatable_trait;
 #[cfg(test)] use mockall::automock;
 
 use crate::events::Event;
-use crate::trees::{DebugNode, DebugTree};
+use crate::trees::{DebugNode, DebugTree, ParsleyTreeDelta, SessionId, TreeDelta};
+use crate::trees::delta_tree::MergeError;
 use crate::server::SkipsSender;
 
+use super::breakpoint_rules::{BreakpointRule, RuleCondition};
+use super::timeline::{RefChange, SnapshotDiff, SnapshotSummary};
+use super::breakpoint_timeout::TimeoutPolicy;
+use super::breakpoint_queue::{FocusPolicy, QueuedBreakpoint};
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum BreakpointCode {
     Skip(i32),
     SkipAll,
     Terminate,
+    UntilParser(String),    /* Continue until a parser with this name is entered */
+    UntilFailure,           /* Continue until a parser fails */
+    UntilOffset(u32),       /* Continue until this input offset is reached */
 }
 
 impl BreakpointCode {
+    /* Plain skip count understood by clients that predate structured commands */
     pub fn i32_code(&self) -> i32 {
         match self {
             BreakpointCode::Skip(skips) => *skips,
             BreakpointCode::Terminate => -1,
             BreakpointCode::SkipAll => -2,
+
+            /* Step commands have no skip count, so these clients stop at the next breakpoint */
+            BreakpointCode::UntilParser(_) | BreakpointCode::UntilFailure | BreakpointCode::UntilOffset(_) => 0,
         }
     }
 }
 
+/* A delta merged into a session's latest tree, with what deciding whether to pause there needs */
+/* Read under the same lock as the merge, so no other change to the session comes between them */
+#[derive(Debug, PartialEq)]
+pub struct MergedDelta {
+    pub delta: TreeDelta,
+    pub input: String,                          /* Input of the merged tree */
+    pub matches_rules: Option<bool>,            /* Whether the merged tree matches an enabled rule, or None if none is */
+    pub changes: Option<Vec<RefChange>>,        /* Watched references changed by the delta, or None if nothing is watched */
+}
+
+/* Everything the parser is resumed with, sent together so the references match the action */
+#[derive(Debug, Clone, PartialEq)]
+pub struct BreakpointMessage {
+    pub code: BreakpointCode,
+    pub refs: Vec<(i32, String)>,
+}
+
 #[delegatable_trait]
 #[cfg_attr(test, automock)]
 pub trait StateManager: Send + Sync + 'static {
     fn set_tree(&self, tree: DebugTree) -> Result<(), StateError>;
 
-    fn get_tree(&self) -> Result<DebugTree, StateError>;
+    /* Store a session's tree without showing it, for a breakpoint continued without the user */
+    fn record_tree(&self, tree: DebugTree) -> Result<(), StateError>;
+
+    fn get_tree(&self, session_id: SessionId) -> Result<DebugTree, StateError>;
+
+    fn get_children(&self, session_id: SessionId, node_id: u32) -> Result<Vec<DebugNode>, StateError>;
+
+    /* Merge nodes generated since the previous breakpoint into a session's stored tree */
+    fn merge_delta(&self, session_id: SessionId, delta: ParsleyTreeDelta) -> Result<MergedDelta, StateError>;
 
-    fn get_node(&self, id: u32) -> Result<DebugNode, StateError>;
+    /* Show an already stored session's tree in the frontend */
+    fn display_tree(&self, session_id: SessionId) -> Result<(), StateError>;
+
+    /* Every breakpoint snapshot recorded for a session, in order */
+    fn timeline(&self, session_id: SessionId) -> Result<Vec<SnapshotSummary>, StateError>;
+
+    /* Show an earlier breakpoint snapshot of a session in the frontend */
+    fn view_snapshot(&self, session_id: SessionId, index: usize) -> Result<(), StateError>;
+
+    /* Compare a breakpoint snapshot of a session against the snapshot before it */
+    fn diff_snapshot(&self, session_id: SessionId, index: usize) -> Result<SnapshotDiff, StateError>;
+
+    /* Session id of the tree currently shown in the frontend */
+    fn displayed_session_id(&self) -> Result<SessionId, StateError>;
 
     fn emit<'a>(&self, event: Event<'a>) -> Result<(), StateError>;
 
-    fn transmit_breakpoint_skips(&self, session_id: i32, code: BreakpointCode) -> Result<(), StateError>;
+    /* Resume a session's breakpoint with the references last saved for it */
+    fn transmit_breakpoint_skips(&self, session_id: SessionId, code: BreakpointCode) -> Result<(), StateError>;
+
+    /* Resume a session's breakpoint with the given references, saving them for the session */
+    fn resume_breakpoint(&self, session_id: SessionId, code: BreakpointCode, refs: Vec<(i32, String)>) -> Result<(), StateError>;
 
     fn system_path(&self, dir: DirectoryKind) -> Result<PathBuf, StateError>;
 
@@ -45,26 +101,63 @@ pub trait StateManager: Send + Sync + 'static {
         self.system_path(dir).map(|base| base.join(path))
     }
 
-    // fn add_session_id(&self, tree_name: String, session_id: i32) -> Result<(), StateError>;
+    // fn add_session_id(&self, tree_name: String, session_id: SessionId) -> Result<(), StateError>;
 
     fn rmv_tab(&self, index: usize) -> Result<Vec<String>, StateError>;
 
-    fn get_tab(&self, index: usize) -> Result<(i32, String), StateError>;
+    fn get_tab(&self, index: usize) -> Result<(SessionId, String), StateError>;
     
-    fn debuggable_session_ids(&self) -> Result<Vec<i32>, StateError>;
+    fn debuggable_session_ids(&self) -> Result<Vec<SessionId>, StateError>;
 
-    fn next_session_id(&self) -> Result<i32, StateError>;
+    fn next_session_id(&self) -> Result<SessionId, StateError>;
 
-    fn new_transmitter(&self, session_id: i32, tx: SkipsSender) -> Result<(), StateError>;
+    fn new_transmitter(&self, session_id: SessionId, tx: SkipsSender) -> Result<(), StateError>;
 
-    fn reset_refs(&self, session_id: i32, default_refs: Vec<(i32, String)>) -> Result<(), StateError>;
+    fn reset_refs(&self, session_id: SessionId, default_refs: Vec<(i32, String)>) -> Result<(), StateError>;
 
-    fn get_refs(&self, session_id: i32) -> Result<Vec<(i32, String)>, StateError>;
+    fn get_refs(&self, session_id: SessionId) -> Result<Vec<(i32, String)>, StateError>;
 
     fn reset_trees(&self) -> Result<(), StateError>;
 
-    /* Updates a saved tree with new breakpoint skips */
-    fn update_tree(&self, tree: &DebugTree, session_id: i32) -> Result<(), UpdateTreeError>;
+    /* Add an enabled auto-continue rule, returning it with its id */
+    fn add_rule(&self, condition: RuleCondition) -> Result<BreakpointRule, StateError>;
+
+    fn rules(&self) -> Result<Vec<BreakpointRule>, StateError>;
+
+    fn set_rule_enabled(&self, rule_id: u32, enabled: bool) -> Result<(), StateError>;
+
+    fn remove_rule(&self, rule_id: u32) -> Result<(), StateError>;
+
+    /* Whether a debuggable tree matches an enabled rule, or None if no rule is enabled */
+    fn matches_rules(&self, tree: &DebugTree) -> Result<Option<bool>, StateError>;
+
+    fn add_watchpoint(&self, address: i32) -> Result<(), StateError>;
+
+    fn watchpoints(&self) -> Result<Vec<i32>, StateError>;
+
+    fn remove_watchpoint(&self, address: i32) -> Result<(), StateError>;
+
+    /* Watched references changed since a session's latest snapshot, or None if nothing is watched */
+    fn watched_changes(&self, session_id: SessionId, refs: &[(i32, String)]) -> Result<Option<Vec<RefChange>>, StateError>;
+
+    /* Set how long a session's breakpoints are waited at, or every session's if None */
+    fn set_timeout_policy(&self, session_id: Option<SessionId>, policy: TimeoutPolicy) -> Result<(), StateError>;
+
+    /* Timeout policy of a session's breakpoints, or the global policy if None */
+    fn timeout_policy(&self, session_id: Option<SessionId>) -> Result<TimeoutPolicy, StateError>;
+
+    /* Show a session whose tree changed if the focus policy allows, otherwise notify that it is waiting */
+    fn focus_session(&self, session_id: SessionId) -> Result<(), StateError>;
+
+    /* Sessions waiting at a breakpoint, oldest first */
+    fn breakpoint_queue(&self) -> Result<Vec<QueuedBreakpoint>, StateError>;
+
+    fn set_focus_policy(&self, policy: FocusPolicy) -> Result<(), StateError>;
+
+    fn focus_policy(&self) -> Result<FocusPolicy, StateError>;
+
+    /* Write a session's latest stored tree to its save file */
+    fn update_tree(&self, session_id: SessionId) -> Result<(), UpdateTreeError>;
 }
 
 #[derive(Debug)]
@@ -74,7 +167,10 @@ pub enum DirectoryKind {
 }
 
 #[derive(Debug, serde::Serialize)]
+#[allow(clippy::enum_variant_names)]
 pub enum UpdateTreeError {
+    LockFailed,
+    TreeNotFound,
     SerialiseFailed,
     OpenFileFailed,
     WriteTreeFailed,
@@ -85,10 +181,23 @@ pub enum StateError {
     LockFailed,
     TreeNotFound,
     NodeNotFound(u32),
+    ParentNotFound(u32), /* A tree delta was attached to a child id not in the stored tree */
     EventEmitFailed,
     AbsolutePathNotAllowed, /* A function was given an unexpected absolute path */
     GetTempdirPathFail,
     GetDownloadPathFail,
     ChannelError, /* Non-fatal error: The receiver from Parsley is no longer listening */
     TabOutOfBounds,
+    SnapshotNotFound(usize),
+    RuleNotFound(u32),
+    WatchpointExists(i32),
+    WatchpointNotFound(i32),
+}
+
+impl From<MergeError> for StateError {
+    fn from(err: MergeError) -> StateError {
+        match err {
+            MergeError::ParentNotFound(child_id) => StateError::ParentNotFound(child_id),
+        }
+    }
 }
@@ -0,0 +1,335 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::trees::{DebugTree, ParsleyTreeDelta, TreeDelta, TreeDiff};
+use crate::trees::delta_tree::{MergeBase, MergeError};
+
+/* Tree of a session as posted at one breakpoint */
+#[derive(Debug, PartialEq)]
+struct Snapshot<T> {
+    tree: T,
+    timestamp: u64,                                 /* Milliseconds since the Unix epoch when recorded */
+    refs_returned: Option<Vec<(i32, String)>>,      /* References sent back when the breakpoint was continued */
+}
+
+/* How an earlier snapshot's tree is kept */
+#[derive(Debug, PartialEq)]
+enum SnapshotTree {
+    Whole(Box<DebugTree>),  /* The next tree was posted whole, replacing this one */
+    Merged(MergeBase),      /* A delta was merged into this tree, which is the prefix of the next whole tree */
+}
+
+impl SnapshotTree {
+    fn num_nodes(&self) -> usize {
+        match self {
+            SnapshotTree::Whole(tree) => tree.nodes().len(),
+            SnapshotTree::Merged(base) => base.num_nodes(),
+        }
+    }
+
+    fn refs(&self) -> Vec<(i32, String)> {
+        match self {
+            SnapshotTree::Whole(tree) => tree.refs(),
+            SnapshotTree::Merged(base) => base.refs(),
+        }
+    }
+}
+
+/* Summary of a snapshot, listed in the frontend's timeline */
+#[derive(Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotSummary {
+    index: usize,
+    timestamp: u64,
+    num_nodes: usize,
+    refs_sent: Vec<(i32, String)>,
+    refs_returned: Option<Vec<(i32, String)>>,
+}
+
+/* Change in the value of a state reference between two snapshots */
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefChange {
+    pub address: i32,
+    pub previous: Option<String>,
+    pub current: Option<String>,
+}
+
+/* Differences between a snapshot and the snapshot before it */
+#[derive(Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotDiff {
+    index: usize,
+    previous_dropped: bool,     /* The snapshot before was dropped, so nothing was compared */
+    nodes: TreeDiff,
+    refs: Vec<RefChange>,
+}
+
+/* Most snapshots kept per session unless configured otherwise */
+pub const DEFAULT_MAX_SNAPSHOTS: usize = 64;
+
+/* The most recent trees posted for a session, in the order they were received */
+/* Only the latest tree is always kept whole, a snapshot reached by a delta is rebuilt from the tree after it */
+/* Older snapshots are dropped past the cap, keeping the indices of the rest */
+#[derive(Debug, PartialEq)]
+pub struct Timeline {
+    earlier: VecDeque<Snapshot<SnapshotTree>>,
+    latest: Snapshot<DebugTree>,
+    dropped: usize,                 /* Number of snapshots dropped, the index of the oldest kept */
+    max_snapshots: usize,           /* Most snapshots kept, including the latest */
+}
+
+/* Every reference whose value differs between two lists of references, in address order */
+pub fn ref_changes(old_refs: &[(i32, String)], new_refs: &[(i32, String)]) -> Vec<RefChange> {
+    let old_refs: BTreeMap<i32, &String> = old_refs.iter().map(|(address, value)| (*address, value)).collect();
+    let new_refs: BTreeMap<i32, &String> = new_refs.iter().map(|(address, value)| (*address, value)).collect();
+
+    /* Every address in either list, in ascending order */
+    let addresses: BTreeSet<i32> = old_refs.keys().chain(new_refs.keys()).copied().collect();
+
+    addresses.into_iter()
+        .filter(|address| old_refs.get(address) != new_refs.get(address))
+        .map(|address| RefChange {
+            address,
+            previous: old_refs.get(&address).map(|value| value.to_string()),
+            current: new_refs.get(&address).map(|value| value.to_string()),
+        })
+        .collect()
+}
+
+/* Current time, falling back to the epoch if the system clock is set before it */
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+impl Timeline {
+    /* Start a timeline from the first tree posted for a session, keeping at most max_snapshots */
+    pub fn new(tree: DebugTree, max_snapshots: usize) -> Self {
+        Timeline {
+            earlier: VecDeque::new(),
+            latest: Snapshot { tree, timestamp: now_millis(), refs_returned: None },
+            dropped: 0,
+            max_snapshots: max_snapshots.max(1),
+        }
+    }
+
+    /* Record the next tree posted for the session, keeping the previous one whole */
+    pub fn push(&mut self, tree: DebugTree) {
+        let previous: Snapshot<DebugTree> = std::mem::replace(&mut self.latest, Snapshot { tree, timestamp: now_millis(), refs_returned: None });
+
+        self.keep(Snapshot { tree: SnapshotTree::Whole(Box::new(previous.tree)), timestamp: previous.timestamp, refs_returned: previous.refs_returned });
+    }
+
+    /* Merge a delta into the latest tree as the next snapshot, keeping only what the previous one was */
+    /* The timeline is left unchanged if the delta cannot be merged */
+    pub fn merge(&mut self, delta: ParsleyTreeDelta) -> Result<TreeDelta, MergeError> {
+        let (tree_delta, base) = self.latest.tree.merge(delta)?;
+
+        let timestamp: u64 = std::mem::replace(&mut self.latest.timestamp, now_millis());
+        let refs_returned: Option<Vec<(i32, String)>> = self.latest.refs_returned.take();
+        self.keep(Snapshot { tree: SnapshotTree::Merged(base), timestamp, refs_returned });
+
+        Ok(tree_delta)
+    }
+
+    /* Add a snapshot before the latest, dropping the oldest if there are too many */
+    fn keep(&mut self, snapshot: Snapshot<SnapshotTree>) {
+        self.earlier.push_back(snapshot);
+
+        while self.earlier.len() >= self.max_snapshots {
+            self.earlier.pop_front();
+            self.dropped += 1;
+        }
+    }
+
+    /* Most recently posted tree, there is always one */
+    pub fn latest(&self) -> &DebugTree {
+        &self.latest.tree
+    }
+
+    /* Index of the most recently posted tree */
+    pub fn latest_index(&self) -> usize {
+        self.dropped + self.earlier.len()
+    }
+
+    /* Consume the timeline, returning the most recently posted tree */
+    pub fn into_latest(self) -> DebugTree {
+        self.latest.tree
+    }
+
+    /* Tree posted at snapshot index if it is kept whole, without rebuilding it */
+    pub fn whole(&self, index: usize) -> Option<&DebugTree> {
+        let position: usize = index.checked_sub(self.dropped)?;
+
+        match self.earlier.get(position) {
+            Some(Snapshot { tree: SnapshotTree::Whole(tree), .. }) => Some(tree),
+            Some(Snapshot { tree: SnapshotTree::Merged(_), .. }) => None,
+            None => (position == self.earlier.len()).then_some(&self.latest.tree),
+        }
+    }
+
+    /* Tree posted at snapshot index, if it has not been dropped, rebuilding it if it was reached by a delta */
+    pub fn tree(&self, index: usize) -> Option<Cow<'_, DebugTree>> {
+        let position: usize = index.checked_sub(self.dropped)?;
+
+        match self.earlier.get(position) {
+            Some(Snapshot { tree: SnapshotTree::Merged(base), .. }) => Some(Cow::Owned(self.merged_into(position).before_merge(base))),
+            _ => self.whole(index).map(Cow::Borrowed),
+        }
+    }
+
+    /* First tree kept whole after position, which every delta merged since the snapshot there was appended to */
+    fn merged_into(&self, position: usize) -> &DebugTree {
+        self.earlier.iter()
+            .skip(position + 1)
+            .find_map(|snapshot| match &snapshot.tree {
+                SnapshotTree::Whole(tree) => Some(tree.as_ref()),
+                SnapshotTree::Merged(_) => None,
+            })
+            .unwrap_or(&self.latest.tree)
+    }
+
+    /* Record the references returned when the latest breakpoint was continued */
+    pub fn set_refs_returned(&mut self, refs: Vec<(i32, String)>) {
+        self.latest.refs_returned = Some(refs);
+    }
+
+    pub fn summaries(&self) -> Vec<SnapshotSummary> {
+        let earlier = self.earlier.iter()
+            .map(|snapshot| (snapshot.timestamp, snapshot.tree.num_nodes(), snapshot.tree.refs(), &snapshot.refs_returned));
+        let latest = (self.latest.timestamp, self.latest.tree.nodes().len(), self.latest.tree.refs(), &self.latest.refs_returned);
+
+        earlier.chain(std::iter::once(latest))
+            .enumerate()
+            .map(|(position, (timestamp, num_nodes, refs_sent, refs_returned))| SnapshotSummary {
+                index: self.dropped + position,
+                timestamp,
+                num_nodes,
+                refs_sent,
+                refs_returned: refs_returned.clone(),
+            })
+            .collect()
+    }
+
+    /* Compare snapshot index against the one before it, the first snapshot is compared against nothing */
+    /* A snapshot whose previous one was dropped is not compared, rather than shown as entirely added */
+    pub fn diff(&self, index: usize) -> Option<SnapshotDiff> {
+        let current: Cow<DebugTree> = self.tree(index)?;
+
+        let previous: Option<Cow<DebugTree>> = match index.checked_sub(1) {
+            Some(previous) if previous < self.dropped => {
+                return Some(SnapshotDiff { index, previous_dropped: true, nodes: TreeDiff::default(), refs: Vec::new() });
+            },
+            Some(previous) => self.tree(previous),
+            None => None,
+        };
+
+        let refs: Vec<RefChange> = ref_changes(&previous.as_deref().map(DebugTree::refs).unwrap_or_default(), &current.refs());
+
+        Some(SnapshotDiff { index, previous_dropped: false, nodes: current.diff(previous.as_deref()), refs })
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+
+    /* Timeline unit testing */
+
+    use super::{RefChange, Timeline, DEFAULT_MAX_SNAPSHOTS};
+    use crate::trees::{debug_tree, delta_tree, DebugTree};
+
+    /* Timeline of the nested tree followed by the tree with a delta merged in */
+    fn timeline() -> Timeline {
+        let mut timeline = Timeline::new(debug_tree::test::nested_tree(), DEFAULT_MAX_SNAPSHOTS);
+        timeline.set_refs_returned(vec![(0, String::from("b"))]);
+        timeline.merge(delta_tree::test::delta()).expect("Merge should succeed");
+        timeline
+    }
+
+    #[test]
+    fn snapshots_are_recorded_in_order() {
+        let timeline: Timeline = timeline();
+        let summaries = timeline.summaries();
+
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[0].num_nodes, 5);
+        assert_eq!(summaries[1].num_nodes, 8);
+        assert!(summaries[0].timestamp <= summaries[1].timestamp);
+
+        assert_eq!(summaries[0].refs_returned, Some(vec![(0, String::from("b"))]));
+        assert_eq!(summaries[1].refs_returned, None);
+        assert_eq!(Some(timeline.latest()), timeline.tree(1).as_deref());
+    }
+
+    #[test]
+    fn merged_snapshots_are_rebuilt_from_later_trees() {
+        let mut timeline: Timeline = timeline();
+        let merged: DebugTree = timeline.latest().clone();
+        timeline.push(debug_tree::test::tree());
+
+        /* Only the tree replaced whole is kept whole, the one before it is rebuilt from it */
+        assert!(timeline.whole(0).is_none());
+        assert_eq!(timeline.whole(1), Some(&merged));
+        assert_eq!(timeline.tree(0).as_deref(), Some(&debug_tree::test::nested_tree()));
+        assert_eq!(timeline.tree(2).as_deref(), Some(&debug_tree::test::tree()));
+    }
+
+    #[test]
+    fn snapshot_cap_is_configurable() {
+        let mut timeline = Timeline::new(debug_tree::test::nested_tree(), 2);
+        timeline.merge(delta_tree::test::delta()).expect("Merge should succeed");
+        timeline.push(debug_tree::test::tree());
+
+        let indices: Vec<usize> = timeline.summaries().iter().map(|summary| summary.index).collect();
+        assert_eq!(indices, vec![1, 2]);
+        assert!(timeline.tree(0).is_none());
+
+        /* A timeline always keeps its latest tree */
+        let mut timeline = Timeline::new(debug_tree::test::nested_tree(), 0);
+        timeline.push(debug_tree::test::tree());
+        assert_eq!(timeline.summaries().len(), 1);
+        assert_eq!(timeline.tree(1).as_deref(), Some(&debug_tree::test::tree()));
+    }
+
+    #[test]
+    fn snapshot_diff_compares_previous() {
+        let timeline: Timeline = timeline();
+
+        let diff = timeline.diff(1).expect("Snapshot 1 should exist");
+        assert_eq!(diff.nodes.added.len(), 3);
+        assert_eq!(diff.refs, vec![RefChange { address: 0, previous: None, current: Some(String::from("a")) }]);
+
+        /* The first snapshot is compared against an empty tree */
+        let first = timeline.diff(0).expect("Snapshot 0 should exist");
+        assert!(!first.previous_dropped);
+        assert_eq!(first.nodes.added.len(), 5);
+        assert!(timeline.diff(2).is_none());
+    }
+
+    #[test]
+    fn oldest_snapshots_are_dropped_keeping_indices() {
+        let mut timeline: Timeline = timeline();
+        for _ in 0..DEFAULT_MAX_SNAPSHOTS {
+            timeline.push(debug_tree::test::nested_tree());
+        }
+
+        let summaries = timeline.summaries();
+        assert_eq!(summaries.len(), DEFAULT_MAX_SNAPSHOTS);
+        assert_eq!(summaries[0].index, 2);
+        assert_eq!(summaries[DEFAULT_MAX_SNAPSHOTS - 1].index, DEFAULT_MAX_SNAPSHOTS + 1);
+
+        assert!(timeline.tree(1).is_none());
+        assert!(timeline.tree(DEFAULT_MAX_SNAPSHOTS + 1).is_some());
+
+        /* The oldest kept snapshot is not compared against the dropped one */
+        let oldest = timeline.diff(2).expect("Snapshot 2 should be kept");
+        assert!(oldest.previous_dropped);
+        assert!(oldest.nodes.added.is_empty() && oldest.refs.is_empty());
+        assert!(!timeline.diff(3).expect("Snapshot 3 should be kept").previous_dropped);
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::BTreeSet;
+
+use super::timeline::{ref_changes, RefChange};
+use crate::trees::SessionId;
+
+/* Addresses of the state references being watched, across every session */
+pub struct Watchpoints {
+    addresses: BTreeSet<i32>,
+}
+
+/* Watched references that changed since a session's previous breakpoint */
+#[derive(Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchpointHit {
+    session_id: SessionId,
+    changes: Vec<RefChange>,
+}
+
+impl WatchpointHit {
+    pub fn new(session_id: SessionId, changes: Vec<RefChange>) -> Self {
+        WatchpointHit { session_id, changes }
+    }
+}
+
+impl Watchpoints {
+    pub fn new() -> Self {
+        Watchpoints { addresses: BTreeSet::new() }
+    }
+
+    /* Watch a reference, returning false if it was already watched */
+    pub fn add(&mut self, address: i32) -> bool {
+        self.addresses.insert(address)
+    }
+
+    /* Stop watching a reference, returning false if it was not watched */
+    pub fn remove(&mut self, address: i32) -> bool {
+        self.addresses.remove(&address)
+    }
+
+    pub fn addresses(&self) -> Vec<i32> {
+        self.addresses.iter().copied().collect()
+    }
+
+    /* Changes to watched references, or None if nothing is watched */
+    /* Without previous references every watched reference is treated as changed */
+    pub fn changes(&self, previous: Option<&[(i32, String)]>, refs: &[(i32, String)]) -> Option<Vec<RefChange>> {
+        if self.addresses.is_empty() {
+            return None;
+        }
+
+        Some(ref_changes(previous.unwrap_or_default(), refs)
+            .into_iter()
+            .filter(|change| self.addresses.contains(&change.address))
+            .collect())
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+
+    /* Watchpoint unit testing */
+
+    use super::Watchpoints;
+
+    fn refs(values: &[&str]) -> Vec<(i32, String)> {
+        values.iter().enumerate().map(|(address, value)| (address as i32, value.to_string())).collect()
+    }
+
+    #[test]
+    fn unwatched_refs_have_no_opinion() {
+        let watchpoints = Watchpoints::new();
+
+        assert_eq!(watchpoints.changes(Some(&refs(&["a"])), &refs(&["b"])), None);
+    }
+
+    #[test]
+    fn only_watched_changes_are_reported() {
+        let mut watchpoints = Watchpoints::new();
+        assert!(watchpoints.add(1));
+        assert!(!watchpoints.add(1));
+
+        /* Address 0 changed but is not watched */
+        let changes = watchpoints.changes(Some(&refs(&["a", "x"])), &refs(&["b", "x"]));
+        assert_eq!(changes, Some(Vec::new()));
+
+        let changes = watchpoints.changes(Some(&refs(&["a", "x"])), &refs(&["a", "y"])).expect("Refs are watched");
+        assert_eq!(changes.len(), 1);
+        assert_eq!((changes[0].address, changes[0].previous.as_deref(), changes[0].current.as_deref()), (1, Some("x"), Some("y")));
+
+        /* The first breakpoint of a session has nothing to compare with */
+        assert_eq!(watchpoints.changes(None, &refs(&["a", "x"])).map(|changes| changes.len()), Some(1));
+
+        assert!(watchpoints.remove(1));
+        assert_eq!(watchpoints.addresses(), Vec::<i32>::new());
+    }
+}
@@ -1,9 +1,20 @@
 pub mod debug_tree;
 pub mod parsley_tree;
 pub mod saved_tree;
+pub mod delta_tree;
+pub mod tree_diff;
+pub mod ref_metadata;
+pub mod session_metadata;
+pub mod session_id;
+pub mod deep_json;
 
 pub use debug_tree::{DebugNode, DebugTree};
 pub use saved_tree::SavedTree;
+pub use delta_tree::{ParsleyTreeDelta, TreeDelta};
+pub use tree_diff::TreeDiff;
+pub use ref_metadata::RefMetadata;
+pub use session_metadata::SessionMetadata;
+pub use session_id::{ClientSessionId, SessionId};
 
 #[allow(unused_imports)]
 pub use parsley_tree::{ParsleyNode, ParsleyTree};
@@ -1,25 +1,86 @@
 use std::collections::HashMap;
 
-/* Placeholder ParserInfo structures for state management */
-#[derive(Clone, Debug, PartialEq, serde::Serialize)]
-#[serde(rename_all = "camelCase")]
+use super::{RefMetadata, SessionId, SessionMetadata};
+
+/* Tree of nodes held in an arena, where each node's id is its index */
+#[derive(Clone, Debug, PartialEq)]
 pub struct DebugTree {
     input: String,
-    root: DebugNode,
+    nodes: Vec<DebugNode>,      /* Arena of nodes, the root is always at index 0 */
     parser_info: HashMap<String, Vec<(i32, i32)>>,
     is_debuggable: bool,
     refs: Vec<(i32, String)>,
-    session_id: i32,
+    ref_metadata: Vec<RefMetadata>,     /* Descriptions of refs, empty for older clients */
+    session_id: SessionId,
     session_name: String,
+    metadata: Option<SessionMetadata>,  /* Sent by the session's client when it connected */
 }
 
 impl DebugTree {
-    pub fn new(input: String, root: DebugNode, parser_info: HashMap<String, Vec<(i32, i32)>>, is_debuggable: bool, refs: Vec<(i32, String)>,  session_id: i32, session_name: String) -> Self {
-        DebugTree { input, root, parser_info, is_debuggable, refs, session_id, session_name }
+    /* Id of the root node in every tree */
+    pub const ROOT_ID: u32 = 0;
+
+    pub fn new(input: String, root: DebugNode, parser_info: HashMap<String, Vec<(i32, i32)>>, is_debuggable: bool, refs: Vec<(i32, String)>,  session_id: SessionId, session_name: String) -> Self {
+        let mut tree = DebugTree { input, nodes: Vec::new(), parser_info, is_debuggable, refs, ref_metadata: Vec::new(), session_id, session_name, metadata: None };
+        tree.push_node(None, root);
+        tree
+    }
+
+    /* Add a node to the arena as the last child of parent, returning its new id */
+    /* Panics if the parent has not already been pushed */
+    pub fn push_node(&mut self, parent: Option<u32>, mut node: DebugNode) -> u32 {
+        let node_id: u32 = self.nodes.len() as u32;
+        node.node_id = node_id;
+        node.parent = parent;
+
+        if let Some(parent_id) = parent {
+            let parent: &mut DebugNode = &mut self.nodes[parent_id as usize];
+            parent.children.push(node_id);
+            parent.is_leaf = false;
+        }
+
+        self.nodes.push(node);
+        node_id
     }
 
     pub fn get_root(&self) -> &DebugNode {
-        &self.root
+        &self.nodes[Self::ROOT_ID as usize]
+    }
+
+    /* Get node by id in constant time */
+    pub fn get_node(&self, node_id: u32) -> Option<&DebugNode> {
+        self.nodes.get(node_id as usize)
+    }
+
+    /* All nodes of the tree, indexed by node id */
+    pub fn nodes(&self) -> &[DebugNode] {
+        &self.nodes
+    }
+
+    /* Copy of this tree around another arena of nodes */
+    pub(super) fn with_nodes(&self, nodes: Vec<DebugNode>) -> DebugTree {
+        DebugTree {
+            input: self.input.clone(),
+            nodes,
+            parser_info: self.parser_info.clone(),
+            is_debuggable: self.is_debuggable,
+            refs: self.refs.clone(),
+            ref_metadata: self.ref_metadata.clone(),
+            session_id: self.session_id,
+            session_name: self.session_name.clone(),
+            metadata: self.metadata.clone(),
+        }
+    }
+
+    /* Mutable access to nodes, which must not change their ids or links */
+    pub(super) fn nodes_mut(&mut self) -> &mut [DebugNode] {
+        &mut self.nodes
+    }
+
+    /* Get the children of a node, in order */
+    pub fn get_children(&self, node_id: u32) -> Option<Vec<&DebugNode>> {
+        self.get_node(node_id)
+            .map(|node| node.children.iter().map(|&child| &self.nodes[child as usize]).collect())
     }
 
     pub fn get_input(&self) -> &String {
@@ -38,7 +99,19 @@ impl DebugTree {
         self.refs.clone()
     }
 
-    pub fn get_session_id(&self) -> i32 {
+    pub fn set_refs(&mut self, refs: Vec<(i32, String)>) {
+        self.refs = refs
+    }
+
+    pub fn ref_metadata(&self) -> &[RefMetadata] {
+        &self.ref_metadata
+    }
+
+    pub fn set_ref_metadata(&mut self, ref_metadata: Vec<RefMetadata>) {
+        self.ref_metadata = ref_metadata
+    }
+
+    pub fn get_session_id(&self) -> SessionId {
         self.session_id
     }
 
@@ -46,47 +119,89 @@ impl DebugTree {
         self.is_debuggable = is_debug
     }
 
-    pub fn set_session_id(&mut self, session_id: i32) {
+    pub fn set_session_id(&mut self, session_id: SessionId) {
         self.session_id = session_id
     }
 
     pub fn get_session_name(&self) -> String {
         self.session_name.clone()
     }
+
+    pub fn metadata(&self) -> Option<&SessionMetadata> {
+        self.metadata.as_ref()
+    }
+
+    pub fn set_metadata(&mut self, metadata: Option<SessionMetadata>) {
+        self.metadata = metadata
+    }
+}
+
+/* Only the root is sent to the frontend, which fetches children on demand */
+impl serde::Serialize for DebugTree {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct DebugTreeView<'a> {
+            input: &'a String,
+            root: &'a DebugNode,
+            parser_info: &'a HashMap<String, Vec<(i32, i32)>>,
+            is_debuggable: bool,
+            refs: &'a Vec<(i32, String)>,
+            #[serde(skip_serializing_if = "Vec::is_empty")] ref_metadata: &'a Vec<RefMetadata>,
+            session_id: SessionId,
+            session_name: &'a String,
+            #[serde(skip_serializing_if = "Option::is_none")] metadata: Option<&'a SessionMetadata>,
+        }
+
+        DebugTreeView {
+            input: &self.input,
+            root: self.get_root(),
+            parser_info: &self.parser_info,
+            is_debuggable: self.is_debuggable,
+            refs: &self.refs,
+            ref_metadata: &self.ref_metadata,
+            session_id: self.session_id,
+            session_name: &self.session_name,
+            metadata: self.metadata(),
+        }.serialize(serializer)
+    }
 }
 
 /* Defines tree structure used in backend that will be passed to frontend */
 #[derive(Debug, Clone, PartialEq, serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DebugNode {
-    pub node_id: u32,          /* The unique number of this node */
+    pub node_id: u32,          /* The unique number of this node, and its index in the tree's arena */
     pub name: String,          /* The internal (default) or user-defined name of the parser */
     pub internal: String,      /* The internal name of the parser */
     pub success: bool,         /* Whether the parser was successful */
     pub child_id: Option<u32>, /* The unique child number of this node */
     pub input_start: u32,      /* Index of start of consumed input */
     pub input_end: u32,        /* Index of end of consumed input (exclusive) */
-    #[serde(skip_serializing)] pub children: Vec<DebugNode>, /* The children of this node */
+    #[serde(skip_serializing)] pub parent: Option<u32>, /* The node id of the parent of this node */
+    #[serde(skip_serializing)] pub children: Vec<u32>,  /* The node ids of the children of this node */
     pub is_leaf: bool,         /* Whether this node is a leaf node */
     pub is_iterative: bool,    /* Whether this node needs bubbling (iterative and transparent) */
     pub newly_generated: bool, /* Whether this node was generated since the previous breakpoint */
 }
 
 impl DebugNode {
-    pub fn new(node_id: u32, name: String, internal: String, success: bool,
-            child_id: Option<u32>, input_start: u32, input_end: u32, children: Vec<DebugNode>,
-            is_iterative: bool, newly_generated: bool) -> Self {
+    /* Create a detached leaf node, its id and parent are set when pushed into a tree */
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(name: String, internal: String, success: bool, child_id: Option<u32>,
+            input_start: u32, input_end: u32, is_iterative: bool, newly_generated: bool) -> Self {
 
         DebugNode {
-            node_id,
+            node_id: DebugTree::ROOT_ID,
             name,
             internal,
             success,
             child_id,
             input_start,
             input_end,
-            is_leaf: children.is_empty(),
-            children,
+            parent: None,
+            children: Vec::new(),
+            is_leaf: true,
             is_iterative,
             newly_generated
         }
@@ -102,8 +217,9 @@ pub mod test {
     use std::collections::HashMap;
 
     use super::{DebugNode, DebugTree};
+    use crate::trees::{session_id, SessionId};
 
-    const DEFAULT_SESSION_ID: i32 = -1;
+    const DEFAULT_SESSION_ID: SessionId = session_id::test::id(0);
     const DEFAULT_SESSION_NAME: &str = "tree";
 
     pub fn json() -> String {
@@ -124,7 +240,7 @@ pub mod test {
             "parserInfo" : {{}},
             "isDebuggable": false,
             "refs": [],
-            "sessionId": {session_id},
+            "sessionId": "{session_id}",
             "sessionName": "{session_name}"
         }}"#, session_id = DEFAULT_SESSION_ID, session_name = DEFAULT_SESSION_NAME)
         .split_whitespace()
@@ -149,24 +265,35 @@ pub mod test {
             "parserInfo" : {{}},
             "isDebuggable": false,
             "refs": [],
-            "sessionId": {session_id},
+            "sessionId": "{session_id}",
             "sessionName": "{session_name}"
         }}"#, session_id = DEFAULT_SESSION_ID, session_name = DEFAULT_SESSION_NAME)
         .split_whitespace()
         .collect()
     }
 
+    /* Build a node of the test trees, whose name and offsets are derived from its id */
+    fn node(id: u32) -> DebugNode {
+        DebugNode::new(
+            id.to_string(),
+            id.to_string(),
+            true,
+            Some(id),
+            id, id + 1,
+            false,
+            false
+        )
+    }
+
     pub fn tree() -> DebugTree {
         DebugTree::new(
             String::from("Test"),
             DebugNode::new(
-                0,
                 String::from("Test"),
                 String::from("Test"),
                 true,
                 Some(0),
                 0, 4,
-                Vec::new(),
                 false,
                 false
             ),
@@ -179,72 +306,23 @@ pub mod test {
     }
 
     pub fn nested_tree() -> DebugTree {
-        DebugTree::new(
+        let mut tree = DebugTree::new(
             String::from("01234"),
-            DebugNode::new(
-                0,
-                String::from("0"),
-                String::from("0"),
-                true,
-                Some(0),
-                0, 1,
-                vec![
-                    DebugNode::new(
-                        1,
-                        String::from("1"),
-                        String::from("1"),
-                        true,
-                        Some(1),
-                        1, 2, 
-                        vec![
-                            DebugNode::new(
-                                2,
-                                String::from("2"),
-                                String::from("2"),
-                                true,
-                                Some(2),
-                                2, 3,
-                                Vec::new(),
-                                false,
-                                false
-                            )
-                        ],
-                        false,
-                        false
-                    ),
-                    DebugNode::new(
-                        3,
-                        String::from("3"),
-                        String::from("3"),
-                        true,
-                        Some(3),
-                        3, 4,
-                        vec![
-                            DebugNode::new(
-                                4,
-                                String::from("4"),
-                                String::from("4"),
-                                true,
-                                Some(4),
-                                4, 5,
-                                Vec::new(),
-                                false,
-                                false
-                            )
-                        ],
-                        false,
-                        false
-                    )
-                ],
-                false,
-                false
-            ),
+            node(0),
             HashMap::new(),
             false,
             Vec::new(),
             DEFAULT_SESSION_ID,
             String::from(DEFAULT_SESSION_NAME),
-        )
+        );
+
+        /* Children are pushed in pre-order, so ids match the nested saved and parsley trees */
+        let one: u32 = tree.push_node(Some(DebugTree::ROOT_ID), node(1));
+        tree.push_node(Some(one), node(2));
+        let three: u32 = tree.push_node(Some(DebugTree::ROOT_ID), node(3));
+        tree.push_node(Some(three), node(4));
+
+        tree
     }
 
 
@@ -268,4 +346,34 @@ pub mod test {
         assert_eq!(json, nested_json());
     }
 
+    #[test]
+    fn pushed_nodes_are_linked_by_id() {
+        let tree: DebugTree = nested_tree();
+
+        assert_eq!(tree.get_root().children, vec![1, 3]);
+        assert!(!tree.get_root().is_leaf);
+
+        let four: &DebugNode = tree.get_node(4).expect("Node 4 should exist");
+        assert_eq!(four.node_id, 4);
+        assert_eq!(four.parent, Some(3));
+        assert!(four.is_leaf);
+
+        assert!(tree.get_node(5).is_none());
+    }
+
+    #[test]
+    fn children_are_fetched_in_order() {
+        let tree: DebugTree = nested_tree();
+
+        let names: Vec<&str> = tree.get_children(DebugTree::ROOT_ID)
+            .expect("Root should exist")
+            .into_iter()
+            .map(|child| child.name.as_str())
+            .collect();
+
+        assert_eq!(names, vec!["1", "3"]);
+        assert_eq!(tree.get_children(2).map(|children| children.len()), Some(0));
+        assert!(tree.get_children(5).is_none());
+    }
+
 }
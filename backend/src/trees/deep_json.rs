@@ -0,0 +1,65 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/* JSON (de)serialisation for nested trees of arbitrary depth.
+   serde's derived implementations recurse once per level of nesting, so the stack
+   is grown onto the heap as needed and serde_json's recursion limit is lifted. */
+
+/* Deserialise a value from a JSON string of any nesting depth */
+pub fn from_str<T: DeserializeOwned>(json: &str) -> serde_json::Result<T> {
+    let mut deserializer = serde_json::Deserializer::from_str(json);
+    deserializer.disable_recursion_limit();
+
+    let value: T = T::deserialize(serde_stacker::Deserializer::new(&mut deserializer))?;
+
+    /* Reject trailing characters, as serde_json::from_str does */
+    deserializer.end()?;
+    Ok(value)
+}
+
+/* Serialise a value of any nesting depth to a compact JSON string */
+/* Pretty-printing is avoided, as indentation grows quadratically with depth */
+pub fn to_string<T: Serialize>(value: &T) -> serde_json::Result<String> {
+    let mut json: Vec<u8> = Vec::new();
+    let mut serializer = serde_json::Serializer::new(&mut json);
+
+    value.serialize(serde_stacker::Serializer::new(&mut serializer))?;
+
+    /* serde_json only ever writes valid UTF-8 */
+    Ok(String::from_utf8(json).expect("serde_json produced invalid UTF-8"))
+}
+
+
+#[cfg(test)]
+mod test {
+
+    /* Deep JSON unit testing */
+
+    use super::{from_str, to_string};
+
+    /* Nested singleton arrays, deeper than serde_json allows by default */
+    const DEPTH: usize = 10_000;
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Nested(Vec<Nested>);
+
+    #[test]
+    fn deep_json_round_trips() {
+        let json: String = format!("{}{}", "[".repeat(DEPTH), "]".repeat(DEPTH));
+        assert!(serde_json::from_str::<serde_json::Value>(&json).is_err());
+
+        let nested: Nested = from_str(&json).expect("Could not deserialise deep JSON");
+        assert_eq!(to_string(&nested).expect("Could not serialise deep JSON"), json);
+
+        /* Unwind without recursing through every level on drop */
+        let mut stack: Vec<Nested> = vec![nested];
+        while let Some(Nested(children)) = stack.pop() {
+            stack.extend(children);
+        }
+    }
+
+    #[test]
+    fn trailing_characters_rejected() {
+        assert!(from_str::<Vec<i32>>("[1, 2] 3").is_err());
+    }
+}
@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+
+use super::{ClientSessionId, DebugNode, DebugTree, ParsleyNode, ParsleyTree, SessionId};
+
+/* Nodes generated since the previous breakpoint of a session, sent by RemoteView instead of a whole tree */
+#[derive(Debug, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParsleyTreeDelta {
+    session_id: ClientSessionId,    /* Session whose stored tree the nodes are merged into */
+    nodes: Vec<ParsleyDeltaNode>,   /* New subtrees, each appended below an already sent node */
+
+    /* If this delta was produced by a currently-running parser */
+    #[serde(default)] is_debuggable: bool,
+
+    /* State references to be modified */
+    #[serde(default)] refs: Vec<(i32, String)>,
+
+    /* Version of the RemoteView protocol spoken by the client */
+    #[serde(default = "ParsleyTree::default_protocol_version")] protocol_version: u32,
+}
+
+/* Subtree of new nodes, attached to a node sent in a previous tree or delta */
+#[derive(Debug, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParsleyDeltaNode {
+    parent_child_id: u32,   /* The unique child number of the node this subtree is appended to */
+    node: ParsleyNode,      /* Root of the new subtree */
+}
+
+impl ParsleyTreeDelta {
+    pub fn session_id(&self) -> ClientSessionId {
+        self.session_id
+    }
+
+    pub fn is_debuggable(&self) -> bool {
+        self.is_debuggable
+    }
+
+    pub fn refs(&self) -> Vec<(i32, String)> {
+        self.refs.clone()
+    }
+
+    pub fn protocol_version(&self) -> u32 {
+        self.protocol_version
+    }
+}
+
+
+/* Region of a stored tree changed by merging a delta, sent to the frontend */
+#[derive(Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TreeDelta {
+    session_id: SessionId,
+    is_debuggable: bool,
+    refs: Vec<(i32, String)>,
+    nodes: Vec<DeltaNode>,  /* Every added node, in pre-order */
+}
+
+/* Node added by a delta, along with the node id of its parent */
+#[derive(Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeltaNode {
+    parent_id: u32,
+    #[serde(flatten)] node: DebugNode,
+}
+
+impl TreeDelta {
+    /* Number of nodes added to the tree */
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+}
+
+
+#[derive(Debug, PartialEq)]
+pub enum MergeError {
+    ParentNotFound(u32),    /* No node in the tree has this child id */
+}
+
+/* What a tree was before a delta was merged into it, from which it can be rebuilt as merging only appends nodes */
+#[derive(Debug, PartialEq)]
+pub struct MergeBase {
+    num_nodes: usize,               /* Nodes were appended after these */
+    newly_generated: Vec<u32>,      /* Nodes that were generated since the breakpoint before */
+    is_debuggable: bool,
+    refs: Vec<(i32, String)>,
+}
+
+impl MergeBase {
+    pub fn num_nodes(&self) -> usize {
+        self.num_nodes
+    }
+
+    pub fn refs(&self) -> Vec<(i32, String)> {
+        self.refs.clone()
+    }
+}
+
+impl DebugTree {
+    /* Append the subtrees of a delta to this tree, returning the added region and what the tree was before */
+    /* The tree is left unchanged if the parent of any subtree cannot be found */
+    pub fn merge(&mut self, delta: ParsleyTreeDelta) -> Result<(TreeDelta, MergeBase), MergeError> {
+        let ParsleyTreeDelta { nodes, is_debuggable, refs, .. } = delta;
+
+        /* Resolve every parent before the tree is modified */
+        let node_ids: HashMap<u32, u32> = self.nodes()
+            .iter()
+            .filter_map(|node| node.child_id.map(|child_id| (child_id, node.node_id)))
+            .collect();
+
+        let parent_ids: Vec<u32> = nodes.iter()
+            .map(|delta_node| node_ids.get(&delta_node.parent_child_id)
+                .copied()
+                .ok_or(MergeError::ParentNotFound(delta_node.parent_child_id)))
+            .collect::<Result<_, _>>()?;
+
+        let base = MergeBase {
+            num_nodes: self.nodes().len(),
+            newly_generated: self.nodes().iter().filter(|node| node.newly_generated).map(|node| node.node_id).collect(),
+            is_debuggable: self.is_debuggable(),
+            refs: self.refs(),
+        };
+
+        /* Only the nodes of this delta were generated since the previous breakpoint */
+        self.nodes_mut().iter_mut().for_each(|node| node.newly_generated = false);
+        let first_added: usize = self.nodes().len();
+
+        for (parent_id, ParsleyDeltaNode { mut node, .. }) in parent_ids.into_iter().zip(nodes) {
+            let node_id: u32 = self.push_node(Some(parent_id), node.take_debug_node());
+            node.push_children(self, node_id);
+        }
+
+        self.nodes_mut()[first_added..].iter_mut().for_each(|node| node.newly_generated = true);
+        self.set_is_debugging(is_debuggable);
+        self.set_refs(refs.clone());
+
+        let tree_delta = TreeDelta {
+            session_id: self.get_session_id(),
+            is_debuggable,
+            refs,
+            nodes: self.nodes()[first_added..]
+                .iter()
+                .map(|node| DeltaNode {
+                    /* Added nodes are never the root, so always have a parent */
+                    parent_id: node.parent.unwrap_or(DebugTree::ROOT_ID),
+                    node: node.clone(),
+                })
+                .collect(),
+        };
+
+        Ok((tree_delta, base))
+    }
+
+    /* Rebuild this tree as it was before later deltas were merged into it */
+    pub fn before_merge(&self, base: &MergeBase) -> DebugTree {
+        let num_nodes: u32 = base.num_nodes as u32;
+
+        let mut nodes: Vec<DebugNode> = self.nodes()[..base.num_nodes].to_vec();
+        /* Nodes only stop being leaves when children are pushed below them */
+        for node in &mut nodes {
+            node.children.retain(|&child| child < num_nodes);
+            node.is_leaf = node.children.is_empty();
+            node.newly_generated = false;
+        }
+        base.newly_generated.iter().for_each(|&node_id| nodes[node_id as usize].newly_generated = true);
+
+        let mut tree: DebugTree = self.with_nodes(nodes);
+        tree.set_is_debugging(base.is_debuggable);
+        tree.set_refs(base.refs.clone());
+        tree
+    }
+}
+
+
+#[cfg(test)]
+pub mod test {
+
+    /* Delta tree unit testing */
+
+    use super::{MergeError, ParsleyTreeDelta};
+    use crate::trees::{debug_tree, deep_json, session_id, DebugTree};
+
+    /* Delta adding a node below node "1" and a subtree of two nodes below node "4" */
+    pub fn json() -> String {
+        r#"{
+            "sessionId": "00000000-0000-0000-0000-000000000000",
+            "nodes": [
+                {
+                    "parentChildId": 1,
+                    "node": {
+                        "name": "5",
+                        "internal": "5",
+                        "success": true,
+                        "childId": 5,
+                        "fromOffset": 2,
+                        "toOffset": 3,
+                        "children": []
+                    }
+                },
+                {
+                    "parentChildId": 4,
+                    "node": {
+                        "name": "6",
+                        "internal": "6",
+                        "success": false,
+                        "childId": 6,
+                        "fromOffset": 4,
+                        "toOffset": 5,
+                        "children": [
+                            {
+                                "name": "7",
+                                "internal": "7",
+                                "success": false,
+                                "childId": 7,
+                                "fromOffset": 4,
+                                "toOffset": 5,
+                                "children": []
+                            }
+                        ]
+                    }
+                }
+            ],
+            "isDebuggable": true,
+            "refs": [[0, "a"]]
+        }"#
+        .split_whitespace()
+        .collect()
+    }
+
+    pub fn delta() -> ParsleyTreeDelta {
+        deep_json::from_str(&json()).expect("Could not deserialise ParsleyTreeDelta")
+    }
+
+    /* Delta adding leaves below node "7" of the first delta, and below node "2" */
+    fn delta_after_merge() -> ParsleyTreeDelta {
+        let node = |child_id: u32| format!(
+            r#"{{"name":"{child_id}","internal":"{child_id}","success":true,"childId":{child_id},"fromOffset":4,"toOffset":5,"children":[]}}"#
+        );
+        let json: String = format!(
+            r#"{{"sessionId":"{}","nodes":[{{"parentChildId":7,"node":{}}},{{"parentChildId":2,"node":{}}}],"isDebuggable":false,"refs":[[0,"b"]]}}"#,
+            session_id::test::id(0), node(8), node(9)
+        );
+
+        deep_json::from_str(&json).expect("Could not deserialise ParsleyTreeDelta")
+    }
+
+
+    #[test]
+    fn tree_is_rebuilt_as_before_merge() {
+        let original: DebugTree = debug_tree::test::nested_tree();
+        let mut tree: DebugTree = original.clone();
+
+        let (_, base) = tree.merge(delta()).expect("Merge should succeed");
+        assert_eq!(base.num_nodes(), 5);
+        assert_eq!(tree.before_merge(&base), original);
+
+        /* A tree merged into again still rebuilds each earlier tree */
+        let merged: DebugTree = tree.clone();
+        let (_, second) = tree.merge(delta_after_merge()).expect("Merge should succeed");
+        assert_eq!(tree.before_merge(&second), merged);
+        assert_eq!(tree.before_merge(&base), original);
+    }
+
+    #[test]
+    fn merged_nodes_are_appended_below_parents() {
+        let mut tree: DebugTree = debug_tree::test::nested_tree();
+
+        let (delta, _) = tree.merge(delta()).expect("Merge should succeed");
+        assert_eq!(delta.num_nodes(), 3);
+
+        /* New ids follow the existing nodes, allocated in pre-order */
+        let names = |id: u32| -> Vec<String> {
+            tree.get_children(id)
+                .expect("Node should exist")
+                .into_iter()
+                .map(|child| child.name.clone())
+                .collect()
+        };
+
+        assert_eq!(names(1), vec!["2", "5"]);
+        assert_eq!(names(4), vec!["6"]);
+        assert_eq!(names(6), vec!["7"]);
+
+        assert!(!tree.get_node(4).expect("Node 4 should exist").is_leaf);
+        assert_eq!(tree.get_node(7).map(|node| node.parent), Some(Some(6)));
+        assert!(tree.is_debuggable());
+        assert_eq!(tree.refs(), vec![(0, String::from("a"))]);
+    }
+
+    #[test]
+    fn only_merged_nodes_are_newly_generated() {
+        let mut tree: DebugTree = debug_tree::test::nested_tree();
+        tree.merge(delta()).expect("Merge should succeed");
+
+        let newly_generated: Vec<u32> = tree.nodes()
+            .iter()
+            .filter(|node| node.newly_generated)
+            .map(|node| node.node_id)
+            .collect();
+
+        assert_eq!(newly_generated, vec![5, 6, 7]);
+    }
+
+    #[test]
+    fn unknown_parent_leaves_tree_unchanged() {
+        let mut tree: DebugTree = debug_tree::test::tree();
+
+        /* The single node tree only has the node with child id 0 */
+        assert_eq!(tree.merge(delta()).map(|(delta, _)| delta), Err(MergeError::ParentNotFound(1)));
+        assert_eq!(tree, debug_tree::test::tree());
+    }
+
+    #[test]
+    fn tree_delta_serialises_added_region() {
+        let mut tree: DebugTree = debug_tree::test::nested_tree();
+        let (delta, _) = tree.merge(delta()).expect("Merge should succeed");
+
+        let json: serde_json::Value = serde_json::to_value(&delta).expect("Could not serialise TreeDelta");
+
+        assert_eq!(json["sessionId"], session_id::test::id(0).to_string());
+        assert_eq!(json["nodes"][0]["parentId"], 1);
+        assert_eq!(json["nodes"][0]["nodeId"], 5);
+        assert_eq!(json["nodes"][2]["parentId"], 6);
+        assert_eq!(json["nodes"][2]["newlyGenerated"], true);
+    }
+}
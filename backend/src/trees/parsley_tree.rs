@@ -1,9 +1,10 @@
 use std::collections::HashMap;
+use std::mem;
 
-use super::{DebugNode, DebugTree};
+use super::{ClientSessionId, DebugNode, DebugTree, RefMetadata, SessionId};
 
 /* Represents tree received from parsley-debug-views' Remote View*/
-#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[derive(Debug, PartialEq, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ParsleyNode {
     name: String,               /* The user-defined name */
@@ -35,68 +36,113 @@ pub struct ParsleyTree {
     /* State references to be modified */
     #[serde(default = "Vec::new")] refs: Vec<(i32, String)>,
 
-    /* If this tree was produced by a currently-running parser */
-    #[serde(default = "ParsleyTree::default_session_id")] session_id: i32,
+    /* Labels, declared types and source locations of refs, not sent by older clients */
+    #[serde(default)] ref_metadata: Vec<RefMetadata>,
+
+    /* Session the tree belongs to, which older clients send as an integer, or -1 if none was allocated */
+    #[serde(default)] session_id: Option<ClientSessionId>,
 
     /* The name for this session, should it be provided */
     #[serde(default)] session_name: Option<String>,
+
+    /* Version of the RemoteView protocol spoken by the client */
+    #[serde(default = "ParsleyTree::default_protocol_version")] protocol_version: u32,
 }
 
 impl ParsleyTree {
     /* Function used by serde to parse default boolean values as false */
     fn default_bool() -> bool { false }
 
-    pub fn session_not_set(&self) -> bool { self.session_id == Self::default_session_id() }
+    /* Session the client named, or None if it has not been allocated one */
+    pub fn session_id(&self) -> Option<ClientSessionId> {
+        self.session_id.filter(|session_id| !session_id.is_unset())
+    }
+
+    pub fn session_not_set(&self) -> bool { self.session_id().is_none() }
+
+    /* Clients that do not send a version predate versioning */
+    pub const fn default_protocol_version() -> u32 { 1 }
+
+    pub fn protocol_version(&self) -> u32 {
+        self.protocol_version
+    }
 
-    const fn default_session_id() -> i32 { -1 }
     fn default_session_name() -> String { String::from("tree") }
 
-    pub fn set_session_id(&mut self, session_id: i32) {
-        self.session_id = session_id
+    pub fn set_session_id(&mut self, session_id: ClientSessionId) {
+        self.session_id = Some(session_id)
+    }
+
+    /* Whether the client names sessions with integers, so must be answered with one */
+    /* Clients that have not been allocated a session are answered in the form their protocol version expects */
+    pub fn speaks_legacy_ids(&self) -> bool {
+        match self.session_id() {
+            Some(session_id) => session_id.is_legacy(),
+            None => self.protocol_version < Self::OPAQUE_SESSION_ID_VERSION,
+        }
+    }
+
+    /* First protocol version whose clients understand opaque session ids */
+    pub const OPAQUE_SESSION_ID_VERSION: u32 = 3;
+
+    /* Convert into a DebugTree of the session the client's id was resolved to */
+    pub fn into_debug_tree(self, session_id: SessionId) -> DebugTree {
+        let ParsleyTree { input, mut root, parser_info, is_debuggable, refs, ref_metadata, session_name, .. } = self;
+
+        let session_name = session_name.unwrap_or(ParsleyTree::default_session_name());
+        let mut debug_tree = DebugTree::new(input, root.take_debug_node(), parser_info, is_debuggable, refs, session_id, session_name);
+        debug_tree.set_ref_metadata(ref_metadata);
+
+        root.push_children(&mut debug_tree, DebugTree::ROOT_ID);
+
+        debug_tree
     }
 }
 
-/* Convert from ParsleyTree to DebugTree */
-impl From<ParsleyTree> for DebugTree {
-    fn from(tree: ParsleyTree) -> Self {
-        /* Helper function used to convert a ParsleyNode given the total input string */
-        fn convert_node(node: ParsleyNode, input: &str, current_id: &mut u32) -> DebugNode {
-            let node_id = *current_id; /* Holding the current node id to be passed on*/
-            *current_id += 1; /* Incrementing the current node id for the next pass */
+impl ParsleyNode {
+    /* Move the fields of this node, without its children, into a detached DebugNode */
+    pub(super) fn take_debug_node(&mut self) -> DebugNode {
+        DebugNode::new(
+            mem::take(&mut self.name),
+            mem::take(&mut self.internal),
+            self.success,
+            self.child_id.try_into().ok(), /* Convert child_id, handling -1 case */
+            u32::try_from(self.from_offset).unwrap_or(0),
+            u32::try_from(self.to_offset).unwrap_or(0),
+            self.is_iterative,
+            self.newly_generated,
+        )
+    }
 
-            /* Convert child_id, handling -1 case */
-            let child_id: Option<u32> = node.child_id.try_into().ok();
+    /* Move the descendants of this node into tree, below the node with id parent_id */
+    /* An explicit stack of (parent id, node) is used, as deep trees would overflow the call stack */
+    pub(super) fn push_children(&mut self, tree: &mut DebugTree, parent_id: u32) {
+        /* Children are pushed in reverse so that node ids are allocated in pre-order */
+        let mut stack: Vec<(u32, ParsleyNode)> = mem::take(&mut self.children)
+            .into_iter()
+            .rev()
+            .map(|child| (parent_id, child))
+            .collect();
 
+        while let Some((parent_id, mut node)) = stack.pop() {
+            let node_id: u32 = tree.push_node(Some(parent_id), node.take_debug_node());
 
-            /* Recursively convert children into DebugNodes */
-            let children: Vec<DebugNode> = node
-                .children
+            stack.extend(mem::take(&mut node.children)
                 .into_iter()
-                .map(|child| convert_node(child, input, current_id))
-                .collect();
-
-            /* Instantiate DebugNode */
-            DebugNode::new(
-                node_id,
-                node.name,
-                node.internal,
-                node.success,
-                child_id,
-                u32::try_from(node.from_offset).unwrap_or(0),
-                u32::try_from(node.to_offset).unwrap_or(0),
-                children,
-                node.is_iterative,
-                node.newly_generated,
-            )
+                .rev()
+                .map(|child| (node_id, child)));
         }
+    }
+}
 
-        /* BFS traversal for node_id */
-        let mut current_id: u32 = 0;
+/* Drop children iteratively, as deep trees would overflow the stack */
+impl Drop for ParsleyNode {
+    fn drop(&mut self) {
+        let mut stack: Vec<ParsleyNode> = mem::take(&mut self.children);
 
-        /* Convert the root node and return DebugTree */
-        let node: DebugNode = convert_node(tree.root, &tree.input, &mut current_id);
-        let session_name = tree.session_name.unwrap_or(ParsleyTree::default_session_name());
-        DebugTree::new(tree.input, node, tree.parser_info, tree.is_debuggable, tree.refs, tree.session_id, session_name)
+        while let Some(mut node) = stack.pop() {
+            stack.append(&mut node.children);
+        }
     }
 }
 
@@ -109,9 +155,12 @@ pub mod test {
     use std::collections::HashMap;
 
     use super::{ParsleyNode, ParsleyTree};
-    use crate::trees::{debug_tree, DebugTree};
+    use crate::trees::{debug_tree, deep_json, ref_metadata, session_id, ClientSessionId, DebugTree, SessionId};
 
-    const DEFAULT_SESSION_ID: i32 = ParsleyTree::default_session_id();
+    /* Depth far beyond what recursive conversion or serde_json's limit allows */
+    pub const DEEP_TREE_DEPTH: usize = 100_000;
+
+    const DEFAULT_SESSION_ID: SessionId = session_id::test::id(0);
 
     pub fn json() -> String {
         r#"{
@@ -199,6 +248,19 @@ pub mod test {
         .collect()
     }
 
+    /* Tree whose nodes form a single chain of the given depth below the root */
+    pub fn deep_tree_json(depth: usize) -> String {
+        const NODE: &str = r#"{"name":"deep","internal":"deep","success":true,"childId":0,"fromOffset":0,"toOffset":1,"children":["#;
+
+        format!(
+            r#"{{"input":"deep","root":{}{}{}{},"parserInfo":{{}},"isDebuggable":false,"refs":[]}}"#,
+            NODE.repeat(depth),
+            NODE,
+            "]}".repeat(depth),
+            "]}",
+        )
+    }
+
     pub fn tree() -> ParsleyTree {
         ParsleyTree {
             input: String::from("Test"),
@@ -216,8 +278,10 @@ pub mod test {
             parser_info: HashMap::new(),
             is_debuggable: false,
             refs: Vec::new(),
-            session_id: DEFAULT_SESSION_ID,
+            ref_metadata: Vec::new(),
+            session_id: None,
             session_name: None,
+            protocol_version: ParsleyTree::default_protocol_version(),
         }
     }
 
@@ -285,8 +349,10 @@ pub mod test {
             parser_info: HashMap::new(),
             is_debuggable: false,
             refs: Vec::new(),
-            session_id: DEFAULT_SESSION_ID,
+            ref_metadata: Vec::new(),
+            session_id: None,
             session_name: Some(String::from("tree")),
+            protocol_version: ParsleyTree::default_protocol_version(),
         }
     }
 
@@ -299,6 +365,34 @@ pub mod test {
         assert_eq!(tree, self::tree());
     }
 
+    #[test]
+    fn ref_metadata_deserialises() {
+        let json: String = json().replace(r#""refs":[]"#, &format!(r#""refs":[[0,"1"]],"refMetadata":{}"#, ref_metadata::test::json()));
+        let tree: DebugTree = serde_json::from_str::<ParsleyTree>(&json)
+            .expect("Could not deserialise ParsleyTree with ref metadata")
+            .into_debug_tree(DEFAULT_SESSION_ID);
+
+        assert_eq!(tree.ref_metadata(), ref_metadata::test::metadata());
+    }
+
+    #[test]
+    fn session_id_may_be_legacy_integer() {
+        let tree: ParsleyTree = serde_json::from_str(&json().replace(r#""input""#, r#""sessionId":-1,"input""#))
+            .expect("Could not deserialise ParsleyTree with unset session");
+        assert!(tree.session_not_set());
+        assert!(tree.speaks_legacy_ids());
+
+        let tree: ParsleyTree = serde_json::from_str(&json().replace(r#""input""#, r#""sessionId":2,"input""#))
+            .expect("Could not deserialise ParsleyTree with integer session");
+        assert_eq!(tree.session_id(), Some(ClientSessionId::Legacy(2)));
+
+        let opaque: String = format!(r#""sessionId":"{DEFAULT_SESSION_ID}","protocolVersion":3,"input""#);
+        let tree: ParsleyTree = serde_json::from_str(&json().replace(r#""input""#, &opaque))
+            .expect("Could not deserialise ParsleyTree with opaque session");
+        assert_eq!(tree.session_id(), Some(ClientSessionId::Opaque(DEFAULT_SESSION_ID)));
+        assert!(!tree.speaks_legacy_ids());
+    }
+
     #[test]
     fn nested_parsley_tree_deserialises() {
         let tree: ParsleyTree = serde_json::from_str(&nested_json())
@@ -312,7 +406,7 @@ pub mod test {
         let parsley_tree: ParsleyTree = tree();
         let debug_tree: DebugTree = debug_tree::test::tree();
 
-        assert_eq!(debug_tree, parsley_tree.into());
+        assert_eq!(debug_tree, parsley_tree.into_debug_tree(DEFAULT_SESSION_ID));
     }
 
     #[test]
@@ -320,7 +414,41 @@ pub mod test {
         let parsley_tree: ParsleyTree = nested_tree();
         let debug_tree: DebugTree = debug_tree::test::nested_tree();
 
-        assert_eq!(debug_tree, parsley_tree.into());
+        assert_eq!(debug_tree, parsley_tree.into_debug_tree(DEFAULT_SESSION_ID));
+    }
+
+    #[test]
+    fn deep_parsley_tree_deserialises() {
+        let json: String = deep_tree_json(DEEP_TREE_DEPTH);
+        assert!(serde_json::from_str::<ParsleyTree>(&json).is_err());
+
+        let tree: ParsleyTree = deep_json::from_str(&json)
+            .expect("Could not deserialise deep ParsleyTree");
+
+        let mut depth: usize = 0;
+        let mut node: &ParsleyNode = &tree.root;
+        while let Some(child) = node.children.first() {
+            depth += 1;
+            node = child;
+        }
+
+        assert_eq!(depth, DEEP_TREE_DEPTH);
+    }
+
+    #[test]
+    fn deep_parsley_tree_converts_into_debug_tree() {
+        let tree: ParsleyTree = deep_json::from_str(&deep_tree_json(DEEP_TREE_DEPTH))
+            .expect("Could not deserialise deep ParsleyTree");
+
+        let debug_tree: DebugTree = tree.into_debug_tree(DEFAULT_SESSION_ID);
+
+        /* Nodes form a single chain with ids allocated in pre-order */
+        let deepest: u32 = DEEP_TREE_DEPTH as u32;
+        let leaf = debug_tree.get_node(deepest).expect("Deepest node should exist");
+
+        assert!(leaf.is_leaf);
+        assert_eq!(leaf.parent, Some(deepest - 1));
+        assert!(debug_tree.get_node(deepest + 1).is_none());
     }
 
 }
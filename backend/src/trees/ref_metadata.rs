@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+
+/* Declared type of a state reference, which edits from the frontend are checked against */
+/* Sent as the type's name, where any name other than the built-in types is a custom type */
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum RefType {
+    Int,
+    Bool,
+    String,
+    Char,
+    Custom(String),     /* Any other Scala type, named as declared */
+}
+
+impl From<String> for RefType {
+    fn from(name: String) -> Self {
+        match name.as_str() {
+            "int" => RefType::Int,
+            "bool" => RefType::Bool,
+            "string" => RefType::String,
+            "char" => RefType::Char,
+            _ => RefType::Custom(name),
+        }
+    }
+}
+
+impl From<RefType> for String {
+    fn from(ref_type: RefType) -> Self {
+        match ref_type {
+            RefType::Int => String::from("int"),
+            RefType::Bool => String::from("bool"),
+            RefType::String => String::from("string"),
+            RefType::Char => String::from("char"),
+            RefType::Custom(name) => name,
+        }
+    }
+}
+
+/* Place in the parser's source where a reference was declared */
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceLocation {
+    file: String,
+    line: u32,
+    #[serde(default)] column: Option<u32>,
+}
+
+/* Optional description of a state reference, sent by RemoteView alongside its refs */
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefMetadata {
+    address: i32,                                   /* Address of the reference being described */
+    #[serde(default)] label: Option<String>,        /* Name given to the reference by the user */
+    #[serde(rename = "type")] ref_type: RefType,
+    #[serde(default)] location: Option<SourceLocation>,
+}
+
+/* Edit to a reference rejected because its value does not have the declared type */
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefFieldError {
+    address: i32,
+    label: Option<String>,
+    expected: RefType,
+    value: String,
+}
+
+impl RefType {
+    /* Whether a reference of this type could hold the printed value */
+    /* Custom types cannot be checked, so accept anything */
+    pub fn accepts(&self, value: &str) -> bool {
+        match self {
+            RefType::Int => value.parse::<i32>().is_ok(),
+            RefType::Bool => value == "true" || value == "false",
+            RefType::Char => value.chars().count() == 1,
+            RefType::String | RefType::Custom(_) => true,
+        }
+    }
+}
+
+/* Check new reference values against their declared types, references without metadata are unchecked */
+pub fn validate_refs(metadata: &[RefMetadata], refs: &[(i32, String)]) -> Result<(), Vec<RefFieldError>> {
+    let by_address: HashMap<i32, &RefMetadata> = metadata.iter().map(|meta| (meta.address, meta)).collect();
+
+    let errors: Vec<RefFieldError> = refs.iter()
+        .filter_map(|(address, value)| by_address.get(address)
+            .filter(|meta| !meta.ref_type.accepts(value))
+            .map(|meta| RefFieldError {
+                address: *address,
+                label: meta.label.clone(),
+                expected: meta.ref_type.clone(),
+                value: value.clone(),
+            }))
+        .collect();
+
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(errors),
+    }
+}
+
+
+#[cfg(test)]
+pub mod test {
+
+    /* Reference metadata unit testing */
+
+    use super::{validate_refs, RefMetadata, RefType};
+
+    /* Metadata for an Int counter at address 0 and a custom type at address 2 */
+    pub fn json() -> String {
+        r#"[
+            {"address": 0, "label": "counter", "type": "int", "location": {"file": "Parser.scala", "line": 12}},
+            {"address": 2, "type": "List[Int]"}
+        ]"#
+        .split_whitespace()
+        .collect()
+    }
+
+    pub fn metadata() -> Vec<RefMetadata> {
+        serde_json::from_str(&json()).expect("Could not deserialise RefMetadata")
+    }
+
+    #[test]
+    fn ref_types_accept_printed_values() {
+        assert!(RefType::Int.accepts("-12"));
+        assert!(!RefType::Int.accepts("1.5"));
+        assert!(RefType::Bool.accepts("false"));
+        assert!(!RefType::Bool.accepts("yes"));
+        assert!(RefType::Char.accepts("é"));
+        assert!(!RefType::Char.accepts("ab"));
+        assert!(RefType::Custom(String::from("Foo")).accepts("anything"));
+    }
+
+    #[test]
+    fn invalid_refs_are_reported_per_field() {
+        let metadata: Vec<RefMetadata> = metadata();
+        assert_eq!(metadata[1].ref_type, RefType::Custom(String::from("List[Int]")));
+
+        /* Address 1 has no metadata, so is not checked */
+        let refs = vec![(0, String::from("3")), (1, String::from("x")), (2, String::from("Nil"))];
+        assert_eq!(validate_refs(&metadata, &refs), Ok(()));
+
+        let errors = validate_refs(&metadata, &[(0, String::from("three"))]).expect_err("Value is not an Int");
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].address, errors[0].label.as_deref(), &errors[0].expected), (0, Some("counter"), &RefType::Int));
+    }
+}
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
+use std::mem;
 
-use super::{DebugNode, DebugTree};
+use super::{ClientSessionId, DebugNode, DebugTree, RefMetadata, SessionId, SessionMetadata};
 
 /* Struct identical to DebugTree that allows serialized saving */
 #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
@@ -10,26 +11,30 @@ pub struct SavedTree {
     parser_info: HashMap<String, Vec<(i32, i32)>>,
     is_debuggable: bool,
     refs: Vec<(i32, String)>,
-    session_id: i32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")] ref_metadata: Vec<RefMetadata>,
+    session_id: ClientSessionId,    /* Trees saved by older versions of Dill have integer ids */
     #[serde(default = "SavedTree::default_session_name")] session_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")] metadata: Option<SessionMetadata>,
 }
 impl SavedTree {
-    pub fn new(input: String, root: SavedNode, parser_info: HashMap<String, Vec<(i32, i32)>>, is_debuggable: bool, refs: Vec<(i32, String)>, session_id: i32, session_name: String) -> Self {
+    pub fn new(input: String, root: SavedNode, parser_info: HashMap<String, Vec<(i32, i32)>>, is_debuggable: bool, refs: Vec<(i32, String)>, session_id: ClientSessionId, session_name: String) -> Self {
         SavedTree {
             input,
             root,
             parser_info,
             is_debuggable,
             refs,
+            ref_metadata: Vec::new(),
             session_id,
             session_name,
+            metadata: None,
         }
     }
 
     fn default_session_name() -> String { String::from("tree") }
 }
 
-#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct SavedNode {
     node_id: u32,               /* The user-defined name */
     name: String,               /* The internal name of the parser */
@@ -43,6 +48,7 @@ pub struct SavedNode {
     newly_generated: bool,      /* Whether this node was generated since the previous breakpoint */
 }
 impl SavedNode {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(node_id: u32, name: String, internal: String, success: bool,
             child_id: Option<u32>, input_start: u32, input_end: u32, children: Vec<SavedNode>,
             is_iterative: bool, newly_generated: bool) -> Self {
@@ -62,21 +68,42 @@ impl SavedNode {
     }
 }
 
+/* Drop children iteratively, as deep trees would overflow the stack */
+impl Drop for SavedNode {
+    fn drop(&mut self) {
+        let mut stack: Vec<SavedNode> = mem::take(&mut self.children);
+
+        while let Some(mut node) = stack.pop() {
+            stack.append(&mut node.children);
+        }
+    }
+}
+
 impl From<DebugTree> for SavedTree {
     fn from(debug_tree: DebugTree) -> Self {
+        SavedTree::from(&debug_tree)
+    }
+}
 
-        fn convert_node(node: DebugNode) -> SavedNode {
-            /* Recursively convert children into SavedNodes */
+impl From<&DebugTree> for SavedTree {
+    fn from(debug_tree: &DebugTree) -> Self {
+        /* Build SavedNodes bottom-up: a parent is always pushed into the arena before its
+           children, so visiting ids in reverse converts every child before its parent */
+        let nodes: &[DebugNode] = debug_tree.nodes();
+        let mut converted: Vec<Option<SavedNode>> = Vec::with_capacity(nodes.len());
+        converted.resize_with(nodes.len(), || None);
+
+        for node in nodes.iter().rev() {
             let children: Vec<SavedNode> = node.children
-                .into_iter()
-                .map(convert_node)
+                .iter()
+                .map(|&child| converted[child as usize].take().expect("Child should be converted before its parent"))
                 .collect();
 
             /* Instantiate SavedNode */
-            SavedNode::new(
+            converted[node.node_id as usize] = Some(SavedNode::new(
                 node.node_id,
-                node.name,
-                node.internal,
+                node.name.clone(),
+                node.internal.clone(),
                 node.success,
                 node.child_id,
                 node.input_start,
@@ -84,41 +111,66 @@ impl From<DebugTree> for SavedTree {
                 children,
                 node.is_iterative,
                 node.newly_generated,
-            )
+            ));
         }
 
-        let node: SavedNode = convert_node(debug_tree.get_root().clone());
+        let node: SavedNode = converted[DebugTree::ROOT_ID as usize].take().expect("Root should be converted last");
 
-        SavedTree::new(debug_tree.get_input().clone(), node, debug_tree.get_parser_info().clone(), debug_tree.is_debuggable(), debug_tree.refs(), debug_tree.get_session_id(), String::from("tree"))
+        let mut saved_tree = SavedTree::new(debug_tree.get_input().clone(), node, debug_tree.get_parser_info().clone(), debug_tree.is_debuggable(), debug_tree.refs(), debug_tree.get_session_id().into(), String::from("tree"));
+        saved_tree.ref_metadata = debug_tree.ref_metadata().to_vec();
+        saved_tree.metadata = debug_tree.metadata().cloned();
+        saved_tree
+    }
+}
+
+impl SavedNode {
+    /* Move the fields of this node, without its children, into a detached DebugNode */
+    fn take_debug_node(&mut self) -> DebugNode {
+        DebugNode::new(
+            mem::take(&mut self.name),
+            mem::take(&mut self.internal),
+            self.success,
+            self.child_id,
+            self.input_start,
+            self.input_end,
+            self.is_iterative,
+            self.newly_generated
+        )
     }
 }
 
 impl From<SavedTree> for DebugTree {
     fn from(saved_tree: SavedTree) -> Self {
-        /* Recursively convert children into SavedNodes */
-        fn convert_node(node: SavedNode) -> DebugNode {
-            let children: Vec<DebugNode> = node.children
+        let SavedTree { input, mut root, parser_info, is_debuggable, refs, ref_metadata, session_id, session_name, metadata } = saved_tree;
+
+        /* Integer ids may collide with sessions of this run, so are replaced */
+        let session_id: SessionId = match session_id {
+            ClientSessionId::Opaque(session_id) => session_id,
+            ClientSessionId::Legacy(_) => SessionId::new(),
+        };
+
+        let mut debug_tree = DebugTree::new(input, root.take_debug_node(), parser_info, is_debuggable, refs, session_id, session_name);
+        debug_tree.set_ref_metadata(ref_metadata);
+        debug_tree.set_metadata(metadata);
+
+        /* Push the rest of the tree using an explicit stack of (parent id, node) */
+        /* Children are pushed in reverse so that node ids are re-allocated in pre-order */
+        let mut stack: Vec<(u32, SavedNode)> = mem::take(&mut root.children)
+            .into_iter()
+            .rev()
+            .map(|child| (DebugTree::ROOT_ID, child))
+            .collect();
+
+        while let Some((parent_id, mut node)) = stack.pop() {
+            let node_id: u32 = debug_tree.push_node(Some(parent_id), node.take_debug_node());
+
+            stack.extend(mem::take(&mut node.children)
                 .into_iter()
-                .map(convert_node)
-                .collect();
-
-            /* Instantiate SavedNode */
-            DebugNode::new(
-                node.node_id,
-                node.name,
-                node.internal,
-                node.success,
-                node.child_id,
-                node.input_start,
-                node.input_end,
-                children,
-                node.is_iterative,
-                node.newly_generated
-            )
+                .rev()
+                .map(|child| (node_id, child)));
         }
 
-        let node: DebugNode = convert_node(saved_tree.root.clone());
-        DebugTree::new(saved_tree.input.clone(), node, saved_tree.parser_info.clone(), saved_tree.is_debuggable, saved_tree.refs, saved_tree.session_id, saved_tree.session_name.clone())
+        debug_tree
     }
 }
 
@@ -133,9 +185,9 @@ pub mod test {
     use std::fs::{self, File};
 
     use super::{SavedTree, SavedNode};
-    use crate::trees::{debug_tree, DebugTree};
+    use crate::trees::{debug_tree, deep_json, parsley_tree, session_id, DebugTree, ParsleyTree, SessionId, SessionMetadata};
 
-    const DEFAULT_SESSION_ID: i32 = -1;
+    const DEFAULT_SESSION_ID: SessionId = session_id::test::id(0);
     const DEFAULT_SESSION_NAME: &str = "tree";
 
     pub fn json() -> String {
@@ -156,7 +208,7 @@ pub mod test {
             "parser_info" : {{}},
             "is_debuggable": false,
             "refs": [],
-            "session_id": {session_id},
+            "session_id": "{session_id}",
             "session_name": "{session_name}"
         }}"#, session_id = DEFAULT_SESSION_ID, session_name = DEFAULT_SESSION_NAME)
         .split_whitespace()
@@ -232,7 +284,7 @@ pub mod test {
             "parser_info" : {{}},
             "is_debuggable": false,
             "refs": [],
-            "session_id": {session_id},
+            "session_id": "{session_id}",
             "session_name": "{session_name}"
         }}"#, session_id = DEFAULT_SESSION_ID, session_name = DEFAULT_SESSION_NAME)
         .split_whitespace()
@@ -256,7 +308,7 @@ pub mod test {
             HashMap::new(),
             false,
             Vec::new(),
-            DEFAULT_SESSION_ID,
+            DEFAULT_SESSION_ID.into(),
             String::from(DEFAULT_SESSION_NAME),
         )
     }
@@ -325,7 +377,7 @@ pub mod test {
             HashMap::new(),
             false,
             Vec::new(),
-            DEFAULT_SESSION_ID,
+            DEFAULT_SESSION_ID.into(),
             String::from(DEFAULT_SESSION_NAME),
         )
     }
@@ -397,6 +449,28 @@ pub mod test {
     }
 
 
+    #[test]
+    fn legacy_saved_tree_is_given_new_session() {
+        let json: String = json().replace(&format!(r#""{DEFAULT_SESSION_ID}""#), "3");
+        let saved_tree: SavedTree = serde_json::from_str(&json)
+            .expect("Tree with an integer session id should deserialize");
+
+        assert_ne!(DebugTree::from(saved_tree).get_session_id(), DEFAULT_SESSION_ID);
+    }
+
+    #[test]
+    fn saved_tree_keeps_session_metadata() {
+        let mut debug_tree: DebugTree = debug_tree::test::tree();
+        debug_tree.set_metadata(Some(SessionMetadata { program: Some(String::from("ExprSpec")), ..SessionMetadata::default() }));
+
+        let json: String = serde_json::to_string(&SavedTree::from(debug_tree.clone()))
+            .expect("Tree should be able to serialize");
+        let saved_tree: SavedTree = serde_json::from_str(&json)
+            .expect("Tree should be able to deserialize");
+
+        assert_eq!(DebugTree::from(saved_tree), debug_tree);
+    }
+
     #[test]
     fn saved_tree_saved_to_json() {
         const FILE_PATH: &str = "test_save.json";
@@ -438,4 +512,19 @@ pub mod test {
             .expect("File could not be deleted");
     }
 
+    #[test]
+    fn deep_tree_saves_and_loads() {
+        let parsley_tree: ParsleyTree = deep_json::from_str(&parsley_tree::test::deep_tree_json(parsley_tree::test::DEEP_TREE_DEPTH))
+            .expect("Could not deserialise deep ParsleyTree");
+        let debug_tree: DebugTree = parsley_tree.into_debug_tree(DEFAULT_SESSION_ID);
+
+        /* Round trip through the nested JSON save format */
+        let json: String = deep_json::to_string(&SavedTree::from(debug_tree.clone()))
+            .expect("Could not serialise deep SavedTree");
+        let saved_tree: SavedTree = deep_json::from_str(&json)
+            .expect("Could not deserialise deep SavedTree");
+
+        assert_eq!(DebugTree::from(saved_tree), debug_tree);
+    }
+
 }
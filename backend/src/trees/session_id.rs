@@ -0,0 +1,128 @@
+use std::fmt;
+use std::str::FromStr;
+
+/* Opaque id of a debugging session, unique across runs of Dill so saved trees never collide */
+/* Ids are UUIDv7s, so sort in the order sessions were created */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct SessionId(uuid::Uuid);
+
+impl SessionId {
+    /* Allocate a new id */
+    pub fn new() -> Self {
+        SessionId(uuid::Uuid::now_v7())
+    }
+}
+
+impl Default for SessionId {
+    fn default() -> Self {
+        SessionId::new()
+    }
+}
+
+impl fmt::Display for SessionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.hyphenated().fmt(f)
+    }
+}
+
+impl FromStr for SessionId {
+    type Err = uuid::Error;
+
+    fn from_str(id: &str) -> Result<Self, Self::Err> {
+        uuid::Uuid::parse_str(id).map(SessionId)
+    }
+}
+
+
+/* Session id as sent by a client or read from a saved tree, which older versions wrote as an integer */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum ClientSessionId {
+    Opaque(SessionId),
+    Legacy(i32),    /* Integer id, where -1 meant no session had been allocated */
+}
+
+impl ClientSessionId {
+    /* Integer older clients sent before they were allocated a session */
+    const LEGACY_UNSET: i32 = -1;
+
+    /* Whether the client has not been allocated a session */
+    pub fn is_unset(&self) -> bool {
+        *self == ClientSessionId::Legacy(Self::LEGACY_UNSET)
+    }
+
+    pub fn is_legacy(&self) -> bool {
+        matches!(self, ClientSessionId::Legacy(_))
+    }
+}
+
+impl fmt::Display for ClientSessionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientSessionId::Opaque(session_id) => session_id.fmt(f),
+            ClientSessionId::Legacy(session_id) => session_id.fmt(f),
+        }
+    }
+}
+
+impl FromStr for ClientSessionId {
+    type Err = uuid::Error;
+
+    fn from_str(id: &str) -> Result<Self, Self::Err> {
+        match id.parse::<i32>() {
+            Ok(legacy) => Ok(ClientSessionId::Legacy(legacy)),
+            Err(_) => id.parse().map(ClientSessionId::Opaque),
+        }
+    }
+}
+
+impl From<SessionId> for ClientSessionId {
+    fn from(session_id: SessionId) -> Self {
+        ClientSessionId::Opaque(session_id)
+    }
+}
+
+
+#[cfg(test)]
+pub mod test {
+
+    /* Session id unit testing */
+
+    use super::{ClientSessionId, SessionId};
+
+    /* Fixed id for test fixtures, distinct for each n */
+    pub const fn id(n: u128) -> SessionId {
+        SessionId(uuid::Uuid::from_u128(n))
+    }
+
+    #[test]
+    fn ids_are_unique_and_ordered() {
+        let first: SessionId = SessionId::new();
+        let second: SessionId = SessionId::new();
+
+        assert_ne!(first, second);
+        assert!(first < second);
+    }
+
+    #[test]
+    fn id_round_trips_through_string() {
+        let session_id: SessionId = SessionId::new();
+
+        assert_eq!(session_id.to_string().parse::<SessionId>().ok(), Some(session_id));
+        assert_eq!(serde_json::to_value(session_id).expect("Could not serialise id"), session_id.to_string());
+    }
+
+    #[test]
+    fn client_id_accepts_integers() {
+        let legacy: ClientSessionId = serde_json::from_str("3").expect("Could not deserialise integer id");
+        assert_eq!(legacy, ClientSessionId::Legacy(3));
+        assert!(serde_json::from_str::<ClientSessionId>("-1").is_ok_and(|id| id.is_unset()));
+
+        let opaque: ClientSessionId = serde_json::from_value(serde_json::json!(id(7).to_string())).expect("Could not deserialise opaque id");
+        assert_eq!(opaque, ClientSessionId::Opaque(id(7)));
+
+        assert_eq!("4".parse::<ClientSessionId>().ok(), Some(ClientSessionId::Legacy(4)));
+        assert!("session".parse::<ClientSessionId>().is_err());
+    }
+}
@@ -0,0 +1,36 @@
+use std::path::PathBuf;
+
+/* Description of a debugging session, sent by its client in the newSession handshake */
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")] pub parsley_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")] pub remote_view_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")] pub protocol_version: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")] pub program: Option<String>,            /* Program or test being debugged */
+    #[serde(default, skip_serializing_if = "Option::is_none")] pub working_directory: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")] pub source_roots: Vec<PathBuf>,           /* Directories the parser's sources are found in */
+}
+
+
+#[cfg(test)]
+mod test {
+
+    /* Session metadata unit testing */
+
+    use std::path::PathBuf;
+
+    use super::SessionMetadata;
+
+    #[test]
+    fn metadata_fields_are_optional() {
+        let metadata: SessionMetadata = serde_json::from_str(r#"{"program":"ExprSpec","sourceRoots":["src/main/scala"]}"#)
+            .expect("Could not deserialise metadata");
+
+        assert_eq!(metadata.program.as_deref(), Some("ExprSpec"));
+        assert_eq!(metadata.source_roots, vec![PathBuf::from("src/main/scala")]);
+        assert_eq!(metadata.parsley_version, None);
+
+        assert_eq!(serde_json::to_string(&SessionMetadata::default()).expect("Could not serialise metadata"), "{}");
+    }
+}
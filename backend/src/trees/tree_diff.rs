@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+
+use super::{DebugNode, DebugTree};
+
+/* Differences between the nodes of two trees, matched by their unique child numbers */
+#[derive(Debug, Default, PartialEq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TreeDiff {
+    pub added: Vec<DebugNode>,      /* Nodes only in the newer tree */
+    pub removed: Vec<DebugNode>,    /* Nodes only in the older tree */
+    pub changed: Vec<DebugNode>,    /* Newer versions of nodes whose parse attempt differs */
+}
+
+/* Whether two versions of a node record a different parse attempt */
+fn attempt_differs(old: &DebugNode, new: &DebugNode) -> bool {
+    (&old.name, &old.internal, old.success, old.input_start, old.input_end, old.is_iterative)
+        != (&new.name, &new.internal, new.success, new.input_start, new.input_end, new.is_iterative)
+}
+
+impl DebugTree {
+    /* Compare this tree against an older tree of the same parse, or against nothing */
+    /* Node ids are not stable between posted trees, so nodes without a child number are ignored */
+    pub fn diff(&self, previous: Option<&DebugTree>) -> TreeDiff {
+        let by_child_id = |nodes: &[DebugNode]| -> HashMap<u32, usize> {
+            nodes.iter()
+                .enumerate()
+                .filter_map(|(index, node)| node.child_id.map(|child_id| (child_id, index)))
+                .collect()
+        };
+
+        let old_nodes: &[DebugNode] = previous.map_or(&[], |tree| tree.nodes());
+        let old_ids: HashMap<u32, usize> = by_child_id(old_nodes);
+        let new_ids: HashMap<u32, usize> = by_child_id(self.nodes());
+
+        let mut diff = TreeDiff::default();
+
+        /* Walk nodes in id order, so the diff lists them in pre-order */
+        for node in self.nodes().iter().filter(|node| node.child_id.is_some()) {
+            match node.child_id.and_then(|child_id| old_ids.get(&child_id)) {
+                None => diff.added.push(node.clone()),
+                Some(&old) if attempt_differs(&old_nodes[old], node) => diff.changed.push(node.clone()),
+                Some(_) => (),
+            }
+        }
+
+        diff.removed = old_nodes.iter()
+            .filter(|node| node.child_id.is_some_and(|child_id| !new_ids.contains_key(&child_id)))
+            .cloned()
+            .collect();
+
+        diff
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+
+    /* Tree diff unit testing */
+
+    use super::TreeDiff;
+    use crate::trees::{debug_tree, delta_tree, DebugTree};
+
+    fn names(nodes: &[crate::trees::DebugNode]) -> Vec<&str> {
+        nodes.iter().map(|node| node.name.as_str()).collect()
+    }
+
+    #[test]
+    fn diff_against_nothing_adds_every_node() {
+        let tree: DebugTree = debug_tree::test::nested_tree();
+        let diff: TreeDiff = tree.diff(None);
+
+        assert_eq!(names(&diff.added), vec!["0", "1", "2", "3", "4"]);
+        assert!(diff.removed.is_empty() && diff.changed.is_empty());
+    }
+
+    #[test]
+    fn diff_finds_added_nodes() {
+        let old: DebugTree = debug_tree::test::nested_tree();
+        let mut new: DebugTree = old.clone();
+        new.merge(delta_tree::test::delta()).expect("Merge should succeed");
+
+        let diff: TreeDiff = new.diff(Some(&old));
+
+        assert_eq!(names(&diff.added), vec!["5", "6", "7"]);
+        assert!(diff.removed.is_empty() && diff.changed.is_empty());
+
+        /* The reverse comparison removes the same nodes */
+        assert_eq!(names(&old.diff(Some(&new)).removed), vec!["5", "6", "7"]);
+    }
+
+    #[test]
+    fn diff_finds_changed_nodes() {
+        let old: DebugTree = debug_tree::test::nested_tree();
+        let mut new: DebugTree = old.clone();
+        new.nodes_mut()[2].success = false;
+
+        assert_eq!(names(&new.diff(Some(&old)).changed), vec!["2"]);
+    }
+}
@@ -5,7 +5,9 @@ import scala.scalajs.js.internal.UnitOps.unitOrOps
 
 import com.raquo.laminar.api.L.*
 
+import model.CommandError
 import model.errors.*
+import model.json.Reader
 import controller.ToastController
 
 /** 
@@ -41,7 +43,10 @@ object ErrorController {
     /* Maps an error passed by the backend to a frontend DillException object */
     def mapException(error: Throwable): DillException = {
         error match {
-            /* Backend errors */
+            /* Command errors, serialised as { code, message, context } */
+            case js.JavaScriptException(jsErr: js.Object) => parseCommandError(js.JSON.stringify(jsErr))
+
+            /* Errors named by a string */
             case js.JavaScriptException(jsErr) => parseException(jsErr.toString.stripPrefix(": "))
 
             /* Unknown error if not from backend or frontend */
@@ -70,5 +75,13 @@ object ErrorController {
             case "DeserialiseFailed" => DeserialiseFailed
             case _ => new UnknownError(s"Unknown backend error: ${errorName}")
     }
+
+    /* Read a command error, keeping the per-reference detail of rejected reference edits */
+    def parseCommandError(json: String): DillException = {
+        Reader[CommandError].read(json) match
+            case Right(CommandError("invalidRefs", _, context)) if context.invalidRefs.isDefined => InvalidRefs(context.invalidRefs.get)
+            case Right(error) => CommandFailed(error)
+            case Left(_) => new UnknownError(s"Unknown backend error: ${json}")
+    }
     
 }
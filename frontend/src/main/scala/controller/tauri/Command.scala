@@ -7,7 +7,7 @@ import com.raquo.laminar.api.L.*
 import org.scalablytyped.runtime.StringDictionary
 import typings.tauriAppsApi.coreMod.{invoke => tauriInvoke}
 
-import model.{DebugNode, DebugTree}
+import model.{BreakpointAction, BreakpointRule, DebugNode, DebugTree, RuleCondition, ServerStatus, SessionId, SnapshotDiff, SnapshotSummary, QueuedBreakpoint, TimeoutPolicy}
 import model.errors.DillException
 import model.json.Reader
 import controller.errors.ErrorController
@@ -58,17 +58,20 @@ object Command {
 
     /* Fetch commands */
     case object FetchDebugTree extends Command("fetch_debug_tree") {
-        type In = Unit
-        given args: Args[In] = Args.noArgs
+        type In = SessionId
+        given args: Args[In] {
+            extension (sessionId: SessionId)
+                def namedArgs: Map[String, Any] = Map("sessionId" -> sessionId)
+        }
 
         type Out = DebugTree
     }
 
     case object FetchNodeChildren extends Command("fetch_node_children") {
-        type In = Int
+        type In = (SessionId, Int)
         given args: Args[In] {
-            extension (id: Int)
-                def namedArgs: Map[String, Any] = Map("nodeId" -> id)
+            extension (args: (SessionId, Int))
+                def namedArgs: Map[String, Any] = Map("sessionId" -> args._1, "nodeId" -> args._2)
         }
 
         type Out = List[DebugNode]
@@ -103,6 +106,13 @@ object Command {
         type Out = Unit
     }
 
+    case object OpenStartupFiles extends Command("open_startup_files") {
+        type In = Unit
+        given args: Args[Unit] = Args.noArgs
+
+        type Out = Unit
+    }
+
     case object DownloadTree extends Command("download_tree") {
         type In = Int
         given args: Args[In] {
@@ -124,9 +134,9 @@ object Command {
     }
 
     case object GetRefs extends Command("get_refs") {
-        type In = Int
+        type In = SessionId
         given args: Args[In] {
-            extension (sessionId: Int)
+            extension (sessionId: SessionId)
                 def namedArgs: Map[String, Any] = Map("sessionId" -> sessionId)
         }
 
@@ -151,16 +161,16 @@ object Command {
     }
 
     case object SkipBreakpoints extends Command("skip_breakpoints") {
-        type In = (Int, Int)
+        type In = (SessionId, Int)
         given args: Args[In] {
-            extension (args: (Int, Int))
+            extension (args: (SessionId, Int))
                 def namedArgs: Map[String, Any] = Map("sessionId" -> args._1, "skips" -> args._2)
         }
         type Out = Unit
     }
 
     case object SkipAllBreakpoints extends Command("skip_all_breakpoints") {
-        type In = Int
+        type In = SessionId
         given args: Args[In] {
             extension (sessionId: In)
                 def namedArgs: Map[String, Any] = Map("sessionId" -> sessionId)
@@ -169,7 +179,25 @@ object Command {
     }
 
     case object TerminateDebugging extends Command("terminate_debugging") {
-        type In = Int
+        type In = SessionId
+        given args: Args[In] {
+            extension (sessionId: In)
+                def namedArgs: Map[String, Any] = Map("sessionId" -> sessionId)
+        }
+        type Out = Unit
+    }
+
+    case object ContinueUntilParser extends Command("continue_until_parser") {
+        type In = (SessionId, String)
+        given args: Args[In] {
+            extension (args: (SessionId, String))
+                def namedArgs: Map[String, Any] = Map("sessionId" -> args._1, "name" -> args._2)
+        }
+        type Out = Unit
+    }
+
+    case object ContinueUntilFailure extends Command("continue_until_failure") {
+        type In = SessionId
         given args: Args[In] {
             extension (sessionId: In)
                 def namedArgs: Map[String, Any] = Map("sessionId" -> sessionId)
@@ -177,6 +205,28 @@ object Command {
         type Out = Unit
     }
 
+    case object ContinueUntilOffset extends Command("continue_until_offset") {
+        type In = (SessionId, Int)
+        given args: Args[In] {
+            extension (args: (SessionId, Int))
+                def namedArgs: Map[String, Any] = Map("sessionId" -> args._1, "offset" -> args._2)
+        }
+        type Out = Unit
+    }
+
+    case object ResumeWithRefs extends Command("resume_with_refs") {
+        type In = (SessionId, BreakpointAction, Seq[(Int, String)])
+        given args: Args[In] {
+            extension (args: (SessionId, BreakpointAction, Seq[(Int, String)]))
+                def namedArgs: Map[String, Any] = Map(
+                    "sessionId" -> args._1,
+                    "action" -> args._2.toJs,
+                    "refs" -> js.Array(args._3.map(js.Tuple2(_, _))*)
+                )
+        }
+        type Out = Unit
+    }
+
     case object RequestSourceFile extends Command("request_source_file") {
         type In = String
         given args: Args[In] {
@@ -185,4 +235,166 @@ object Command {
         }
         type Out = Unit
     }
+
+
+    /* Timeline commands */
+    case object ListTimeline extends Command("list_timeline") {
+        type In = SessionId
+        given args: Args[In] {
+            extension (sessionId: In)
+                def namedArgs: Map[String, Any] = Map("sessionId" -> sessionId)
+        }
+        type Out = List[SnapshotSummary]
+    }
+
+    case object LoadSnapshot extends Command("load_snapshot") {
+        type In = (SessionId, Int)
+        given args: Args[In] {
+            extension (args: (SessionId, Int))
+                def namedArgs: Map[String, Any] = Map("sessionId" -> args._1, "index" -> args._2)
+        }
+        type Out = Unit
+    }
+
+    case object DiffSnapshot extends Command("diff_snapshot") {
+        type In = (SessionId, Int)
+        given args: Args[In] {
+            extension (args: (SessionId, Int))
+                def namedArgs: Map[String, Any] = Map("sessionId" -> args._1, "index" -> args._2)
+        }
+        type Out = SnapshotDiff
+    }
+
+
+    /* Auto-continue rule commands */
+    case object CreateRule extends Command("create_rule") {
+        type In = RuleCondition
+        given args: Args[In] {
+            extension (condition: RuleCondition)
+                def namedArgs: Map[String, Any] = Map("condition" -> condition.toJs)
+        }
+        type Out = BreakpointRule
+    }
+
+    case object ListRules extends Command("list_rules") {
+        type In = Unit
+        given args: Args[In] = Args.noArgs
+
+        type Out = List[BreakpointRule]
+    }
+
+    case object EnableRule extends Command("enable_rule") {
+        type In = (Int, Boolean)
+        given args: Args[In] {
+            extension (args: (Int, Boolean))
+                def namedArgs: Map[String, Any] = Map("ruleId" -> args._1, "enabled" -> args._2)
+        }
+        type Out = Unit
+    }
+
+    case object DeleteRule extends Command("delete_rule") {
+        type In = Int
+        given args: Args[In] {
+            extension (ruleId: In)
+                def namedArgs: Map[String, Any] = Map("ruleId" -> ruleId)
+        }
+        type Out = Unit
+    }
+
+
+    /* Breakpoint timeout commands, for a session or every session if None */
+    case object SetBreakpointTimeout extends Command("set_breakpoint_timeout") {
+        type In = (Option[SessionId], TimeoutPolicy)
+        given args: Args[In] {
+            extension (args: (Option[SessionId], TimeoutPolicy))
+                def namedArgs: Map[String, Any] = Map("sessionId" -> args._1.orNull, "policy" -> args._2.toJs)
+        }
+        type Out = Unit
+    }
+
+    case object GetBreakpointTimeout extends Command("get_breakpoint_timeout") {
+        type In = Option[SessionId]
+        given args: Args[In] {
+            extension (sessionId: In)
+                def namedArgs: Map[String, Any] = Map("sessionId" -> sessionId.orNull)
+        }
+        type Out = TimeoutPolicy
+    }
+
+
+    /* Breakpoint queue commands */
+    case object ListBreakpointQueue extends Command("list_breakpoint_queue") {
+        type In = Unit
+        given args: Args[In] = Args.noArgs
+
+        type Out = List[QueuedBreakpoint]
+    }
+
+    case object SetFocusPolicy extends Command("set_focus_policy") {
+        type In = String
+        given args: Args[In] {
+            extension (policy: In)
+                def namedArgs: Map[String, Any] = Map("policy" -> policy)
+        }
+        type Out = Unit
+    }
+
+    case object GetFocusPolicy extends Command("get_focus_policy") {
+        type In = Unit
+        given args: Args[In] = Args.noArgs
+
+        type Out = String
+    }
+
+
+    /* Watchpoint commands */
+    case object AddWatchpoint extends Command("add_watchpoint") {
+        type In = Int
+        given args: Args[In] {
+            extension (address: In)
+                def namedArgs: Map[String, Any] = Map("address" -> address)
+        }
+        type Out = Unit
+    }
+
+    case object ListWatchpoints extends Command("list_watchpoints") {
+        type In = Unit
+        given args: Args[In] = Args.noArgs
+
+        type Out = List[Int]
+    }
+
+    case object RemoveWatchpoint extends Command("remove_watchpoint") {
+        type In = Int
+        given args: Args[In] {
+            extension (address: In)
+                def namedArgs: Map[String, Any] = Map("address" -> address)
+        }
+        type Out = Unit
+    }
+
+
+    /* Server commands */
+    case object GetServerStatus extends Command("server_status") {
+        type In = Unit
+        given args: Args[In] = Args.noArgs
+
+        type Out = ServerStatus
+    }
+
+    case object GetServerToken extends Command("server_token") {
+        type In = Unit
+        given args: Args[In] = Args.noArgs
+
+        type Out = Option[String]
+    }
+
+    case object RestartServer extends Command("restart_server") {
+        type In = Option[Int]
+        given args: Args[In] {
+            extension (port: Option[Int])
+                def namedArgs: Map[String, Any] = Map("port" -> port.orNull)
+        }
+        type Out = Unit
+    }
 }
@@ -63,9 +63,41 @@ object Event {
         override val isUnit = true
     }
 
+    case object TreeDelta extends Event("tree-delta") {
+        type Out = model.TreeDelta
+    }
+
+    case object WatchpointHit extends Event("watchpoint-hit") {
+        type Out = model.WatchpointHit
+    }
+
     case object UploadCodeFile extends Event("upload-code-file") {
         type Out = String
     }
+
+    case object ServerStatus extends Event("server-status") {
+        type Out = model.ServerStatus
+    }
+
+    case object SessionPaused extends Event("session-paused") {
+        type Out = model.SessionInfo
+    }
+
+    case object SessionResumed extends Event("session-resumed") {
+        type Out = model.SessionInfo
+    }
+
+    case object SessionClosed extends Event("session-closed") {
+        type Out = model.SessionInfo
+    }
+
+    case object BreakpointTimeoutWarning extends Event("breakpoint-timeout-warning") {
+        type Out = model.TimeoutWarning
+    }
+
+    case object BreakpointQueued extends Event("breakpoint-queued") {
+        type Out = model.QueuedBreakpoint
+    }
 }
 
 
@@ -5,7 +5,10 @@ import scala.scalajs.js.internal.UnitOps.unitOrOps
 
 import com.raquo.laminar.api.L.*
 
+import model.{ServerStatus, TimeoutPolicy}
+import model.errors.DillException
 import model.settings.*
+import controller.tauri.{Command, Tauri}
 
 
 /**
@@ -32,6 +35,11 @@ object SettingsViewController {
     private val numSkipBreakpoints: Var[Int] = Var(numSkipBreakpointsDefault)
     private val colorBlindMode: Var[Boolean] = Var(colorBlindModeDefault)
 
+    /**
+     * Most recent status of the server receiving trees, unknown until the backend reports it.
+     */
+    private val serverStatus: Var[Option[ServerStatus]] = Var(None)
+
     /**
      * Retrieves a signal indicating whether the settings panel is open.
      * 
@@ -107,4 +115,69 @@ object SettingsViewController {
             newColorBlindMode = newColorBlindMode
         )
     }
+
+    /**
+     * Retrieves a signal of the server's most recent status.
+     *
+     * @return A Signal of the status, or None if it has not been reported yet.
+     */
+    def getServerStatus: Signal[Option[ServerStatus]] = serverStatus.signal
+
+    /**
+     * Records a status reported by the server.
+     */
+    val setServerStatus: Observer[ServerStatus] = serverStatus.writer.contramap(Some(_))
+
+    /**
+     * Fetches the server's status, which may have been reported before the frontend was listening.
+     *
+     * @return An EventStream of the status, or a DillException.
+     */
+    def fetchServerStatus: EventStream[Either[DillException, ServerStatus]] = Tauri.invoke(Command.GetServerStatus, ())
+
+    /**
+     * Fetches the token remote clients must send, to configure the parsley client with.
+     *
+     * @return An EventStream of the token, None if authentication is disabled, or a DillException.
+     */
+    def fetchServerToken: EventStream[Either[DillException, Option[String]]] = Tauri.invoke(Command.GetServerToken, ())
+
+    /**
+     * Stops the server and launches it again.
+     *
+     * @param port The port to launch on, or None to keep the current port.
+     * @return An EventStream that emits once the restart has begun, or a DillException.
+     */
+    def restartServer(port: Option[Int]): EventStream[Either[DillException, Unit]] = Tauri.invoke(Command.RestartServer, port)
+
+    /**
+     * Fetches the timeout policy of sessions without their own, which Dill may have been launched with.
+     *
+     * @return An EventStream of the policy, or a DillException.
+     */
+    def fetchBreakpointTimeout: EventStream[Either[DillException, TimeoutPolicy]] = Tauri.invoke(Command.GetBreakpointTimeout, None)
+
+    /**
+     * Sets how long every session's breakpoints are waited at before the policy's action is applied.
+     *
+     * @param policy The new policy, waiting forever if it has no seconds.
+     * @return An EventStream that emits once the policy is set, or a DillException.
+     */
+    def setBreakpointTimeout(policy: TimeoutPolicy): EventStream[Either[DillException, Unit]] =
+        Tauri.invoke(Command.SetBreakpointTimeout, (None, policy))
+
+    /**
+     * Fetches which of several paused sessions is shown.
+     *
+     * @return An EventStream of `mostRecent`, `firstCome` or `never`, or a DillException.
+     */
+    def fetchFocusPolicy: EventStream[Either[DillException, String]] = Tauri.invoke(Command.GetFocusPolicy, ())
+
+    /**
+     * Chooses which of several paused sessions is shown.
+     *
+     * @param policy `mostRecent`, `firstCome` or `never`.
+     * @return An EventStream that emits once the policy is set, or a DillException.
+     */
+    def setFocusPolicy(policy: String): EventStream[Either[DillException, Unit]] = Tauri.invoke(Command.SetFocusPolicy, policy)
 }
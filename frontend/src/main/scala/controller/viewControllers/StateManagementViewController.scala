@@ -5,7 +5,7 @@ import scala.scalajs.js.internal.UnitOps.unitOrOps
 
 import com.raquo.laminar.api.L._
 
-import model.DebugTree
+import model.{DebugTree, RefMetadata}
 
 /**
 * Provides state management functions and signals for controlling
@@ -46,6 +46,11 @@ object StateManagementViewController {
     */
     private var origRefs: Seq[(Int, String)] = Nil
 
+    /**
+    * A private map from reference address to the metadata `Parsley` sent for it, if any.
+    */
+    private var refMetadata: Map[Int, RefMetadata] = Map.empty
+
     /**
     * A signal that emits `true` when the sequence of references is empty,
     * and `false` otherwise. Useful for conditionally displaying error messages or content.
@@ -151,6 +156,23 @@ object StateManagementViewController {
         }
     }
 
+    /**
+    * Replaces the metadata of the current references.
+    *
+    * @param metadata Metadata sent for some or all of the references.
+    */
+    def setRefMetadata(metadata: Seq[RefMetadata]): Unit = {
+        refMetadata = metadata.map(meta => meta.address -> meta).toMap
+    }
+
+    /**
+    * Looks up the metadata of a reference, for showing its label and declared type.
+    *
+    * @param refAddr The reference address to look up.
+    * @return The reference's metadata, or None if `Parsley` did not send any.
+    */
+    def getRefMetadata(refAddr: Int): Option[RefMetadata] = refMetadata.get(refAddr)
+
     /**
     * Retrieves the local (non-reactive) sequence of reference pairs. This sequence
     * is separate from the reactive `refs` and must be updated manually to remain in sync.
@@ -10,6 +10,7 @@ import controller.tauri.Tauri
 import controller.tauri.Command
 import controller.viewControllers.SettingsViewController
 import model.DebugNode
+import model.{BreakpointAction, BreakpointRule, QueuedBreakpoint, RuleCondition, SessionId, SessionInfo, SnapshotDiff, SnapshotSummary}
 
 
 
@@ -50,12 +51,15 @@ object TreeViewController {
     /** Return true signal if tree is loaded into frontend */
     def treeExists: Signal[Boolean] = getTree.map(_.isDefined)
 
-    /** Get sessionId from loaded tree, -1 for non-debuggable */
-    def getSessionId: Signal[Int] = getTree.foldOption(-1)(_.sessionId)
+    /** Get sessionId from loaded tree, empty if no tree is loaded */
+    def getSessionId: Signal[SessionId] = getTree.foldOption("")(_.sessionId)
 
     /** Get sessionName from loaded tree, "tree" for a plain name */
     def getSessionName: Signal[String] = getTree.foldOption("tree")(_.sessionName)
 
+    /** Get a description of the client that sent the displayed tree, empty if it gave none */
+    def getSessionDescription: Signal[String] = getTree.map(_.flatMap(_.metadata).fold("")(_.summary))
+
     /** Get debug tree element or warning if no tree found */
     def getTreeElem: Signal[HtmlElement] = getTree.map(_ match
         /* Default tree view when no tree is loaded */
@@ -78,45 +82,125 @@ object TreeViewController {
     /* Imports JSON in path from users device */
     def importTree(contents: String): EventStream[Either[DillException, Unit]] = Tauri.invoke(Command.ImportTree, contents)
 
-    /** Fetch the debug tree root from the backend, return in EventStream */
-    def reloadTree: EventStream[Either[DillException, DebugTree]] = Tauri.invoke(Command.FetchDebugTree, ())
+    /** Fetch the debug tree root of a session from the backend, return in EventStream */
+    def reloadTree(sessionId: SessionId): EventStream[Either[DillException, DebugTree]] = Tauri.invoke(Command.FetchDebugTree, sessionId)
+
+    /** Resumes the current breakpoint with an action and the applied references, in one command
+      *
+      * @param sessionIdAndAction The sessionId of the current debugging session, and the action to take
+      */
+    def resumeWithRefs(sessionIdAndAction: EventStream[(SessionId, BreakpointAction)]): EventStream[Either[DillException, Unit]] = {
+        sessionIdAndAction
+            .withCurrentValueOf(StateManagementViewController.getRefs)
+            .flatMapSwitch(Tauri.invoke(Command.ResumeWithRefs, _))
+    }
 
     /** Skips the current breakpoint 'skips' times
       *
       * @param skips The amount of times to skip a breakpoint
       * @param sessionId The sessionId of the current debugging session
       */
-    def skipBreakpoints(sessionId: EventStream[Int]): EventStream[Either[DillException, Unit]] = {
+    def skipBreakpoints(sessionId: EventStream[SessionId]): EventStream[Either[DillException, Unit]] = {
         sessionId
             .withCurrentValueOf(SettingsViewController.getNumSkipBreakpoints.signal)
-            .map((sessionId, skips) => (sessionId, skips - 1))
-            .flatMapSwitch(Tauri.invoke(Command.SkipBreakpoints, _))
+            .map((sessionId, skips) => (sessionId, BreakpointAction.Skip(skips - 1)))
+            .compose(resumeWithRefs)
     }
 
     /** Skips over all breakpoints, to receive the final parse tree
       *
       * @param sessionId The sessionId of the current debugging session
       */
-    def skipAllBreakpoints(sessionId: EventStream[Int]): EventStream[Either[DillException, Unit]] = {
-        sessionId.flatMapSwitch(Tauri.invoke(Command.SkipAllBreakpoints, _))
+    def skipAllBreakpoints(sessionId: EventStream[SessionId]): EventStream[Either[DillException, Unit]] = {
+        sessionId.map((_, BreakpointAction.SkipAll)).compose(resumeWithRefs)
     }
 
     /** Terminates debugging. Skips all breakpoints and will *not* receive the final parse tree.
       *
       * @param sessionId The sessionId of the current debugging session
       */
-    def terminateDebugging(sessionId: EventStream[Int]): EventStream[Either[DillException, Unit]] = {
+    def terminateDebugging(sessionId: EventStream[SessionId]): EventStream[Either[DillException, Unit]] = {
         sessionId.flatMapSwitch(Tauri.invoke(Command.TerminateDebugging, _))
     }
 
-    val isDebuggingSession: Signal[Boolean] = tree.signal.map(_.exists(_.isDebuggable))
+    /** Continues until a parser with the given name is entered
+      *
+      * @param sessionIdAndName The sessionId of the current debugging session, and the parser name
+      */
+    def continueUntilParser(sessionIdAndName: EventStream[(SessionId, String)]): EventStream[Either[DillException, Unit]] = {
+        sessionIdAndName.flatMapSwitch(Tauri.invoke(Command.ContinueUntilParser, _))
+    }
+
+    /** Continues until the next parser failure
+      *
+      * @param sessionId The sessionId of the current debugging session
+      */
+    def continueUntilFailure(sessionId: EventStream[SessionId]): EventStream[Either[DillException, Unit]] = {
+        sessionId.flatMapSwitch(Tauri.invoke(Command.ContinueUntilFailure, _))
+    }
+
+    /** Continues until the parse reaches the given input offset
+      *
+      * @param sessionIdAndOffset The sessionId of the current debugging session, and the input offset
+      */
+    def continueUntilOffset(sessionIdAndOffset: EventStream[(SessionId, Int)]): EventStream[Either[DillException, Unit]] = {
+        sessionIdAndOffset.flatMapSwitch(Tauri.invoke(Command.ContinueUntilOffset, _))
+    }
+
+    /* Latest status the server sent for each session that paused at a breakpoint */
+    private val sessionStatuses: Var[Map[SessionId, SessionInfo]] = Var(Map.empty)
+
+    /** Record a session pausing, resuming or closing */
+    val setSessionStatus: Observer[SessionInfo] = sessionStatuses.updater((statuses, session) => statuses + (session.sessionId -> session))
+
+    /* Debuggable trees can only be continued while their session is paused, or if no status was sent */
+    val isDebuggingSession: Signal[Boolean] = tree.signal.combineWith(sessionStatuses.signal).map {
+        case (tree, statuses) => tree.exists(tree => tree.isDebuggable && statuses.get(tree.sessionId).forall(_.isPaused))
+    }
 
     /** Get the references of a debugging tree */
-    def getRefs(sessionId: Int): EventStream[Either[DillException, Seq[(Int, String)]]] = Tauri.invoke(Command.GetRefs, sessionId)
+    def getRefs(sessionId: SessionId): EventStream[Either[DillException, Seq[(Int, String)]]] = Tauri.invoke(Command.GetRefs, sessionId)
 
     /** Get the references of a debugging tree */
     def setRefs(newRefs: Seq[(Int, String)]): EventStream[Either[DillException, Unit]] = Tauri.invoke(Command.SetRefs, newRefs)
 
+    /** Add a rule, so only breakpoints matching an enabled rule are shown */
+    def createRule(condition: RuleCondition): EventStream[Either[DillException, BreakpointRule]] = Tauri.invoke(Command.CreateRule, condition)
+
+    /** List every auto-continue rule */
+    def listRules(): EventStream[Either[DillException, List[BreakpointRule]]] = Tauri.invoke(Command.ListRules, ())
+
+    /** Enable or disable an auto-continue rule */
+    def enableRule(ruleId: Int, enabled: Boolean): EventStream[Either[DillException, Unit]] = Tauri.invoke(Command.EnableRule, (ruleId, enabled))
+
+    /** Delete an auto-continue rule */
+    def deleteRule(ruleId: Int): EventStream[Either[DillException, Unit]] = Tauri.invoke(Command.DeleteRule, ruleId)
+
+    /** Stop at every breakpoint where the reference at address changed */
+    def addWatchpoint(address: Int): EventStream[Either[DillException, Unit]] = Tauri.invoke(Command.AddWatchpoint, address)
+
+    /** List the addresses of every watched reference */
+    def listWatchpoints(): EventStream[Either[DillException, List[Int]]] = Tauri.invoke(Command.ListWatchpoints, ())
+
+    /** Stop watching the reference at address */
+    def removeWatchpoint(address: Int): EventStream[Either[DillException, Unit]] = Tauri.invoke(Command.RemoveWatchpoint, address)
+
+    /** List the sessions waiting at a breakpoint, oldest first */
+    def listBreakpointQueue(): EventStream[Either[DillException, List[QueuedBreakpoint]]] = Tauri.invoke(Command.ListBreakpointQueue, ())
+
+    /** List every breakpoint snapshot recorded for a session */
+    def listTimeline(sessionId: SessionId): EventStream[Either[DillException, List[SnapshotSummary]]] = Tauri.invoke(Command.ListTimeline, sessionId)
+
+    /** Step back to a breakpoint snapshot of a session, which is sent as a TreeReady event
+      *
+      * @param sessionId The sessionId of the debugging session
+      * @param index The position of the snapshot in the session's timeline
+      */
+    def loadSnapshot(sessionId: SessionId, index: Int): EventStream[Either[DillException, Unit]] = Tauri.invoke(Command.LoadSnapshot, (sessionId, index))
+
+    /** Compare a breakpoint snapshot of a session against the snapshot before it */
+    def diffSnapshot(sessionId: SessionId, index: Int): EventStream[Either[DillException, SnapshotDiff]] = Tauri.invoke(Command.DiffSnapshot, (sessionId, index))
+
     /** Resets the references of a debugging tree */
     def resetRefs(): EventStream[Either[DillException, Seq[(Int, String)]]] = Tauri.invoke(Command.ResetRefs, ())
 }
@@ -0,0 +1,26 @@
+package model
+
+import scala.scalajs.js
+
+
+/**
+  * Enum representing an action taken at a breakpoint, sent to the backend alongside the edited references.
+  */
+enum BreakpointAction {
+    case Skip(skips: Int)
+    case SkipAll
+    case Terminate
+    case UntilParser(name: String)
+    case UntilFailure
+    case UntilOffset(offset: Int)
+
+    /* Convert to a JS object for passing as a command argument */
+    def toJs: js.Dictionary[Any] = this match {
+        case Skip(skips) => js.Dictionary("kind" -> "skip", "skips" -> skips)
+        case SkipAll => js.Dictionary("kind" -> "skipAll")
+        case Terminate => js.Dictionary("kind" -> "terminate")
+        case UntilParser(name) => js.Dictionary("kind" -> "untilParser", "name" -> name)
+        case UntilFailure => js.Dictionary("kind" -> "untilFailure")
+        case UntilOffset(offset) => js.Dictionary("kind" -> "untilOffset", "offset" -> offset)
+    }
+}
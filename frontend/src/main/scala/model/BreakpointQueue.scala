@@ -0,0 +1,19 @@
+package model
+
+import model.json.Reader
+
+
+/**
+  * Case class representing a session waiting at a breakpoint.
+  *
+  * @param sessionId session waiting at the breakpoint
+  * @param pausedAt milliseconds since the epoch the breakpoint was hit
+  * @param sessionName name of the session, once its tree is stored
+  * @param parser parser most recently added to the session's tree
+  */
+case class QueuedBreakpoint(
+    sessionId: SessionId,
+    pausedAt: Long,
+    sessionName: Option[String] = None,
+    parser: Option[String] = None
+) derives Reader.upickle
@@ -0,0 +1,54 @@
+package model
+
+import scala.scalajs.js
+
+import model.json.Reader
+
+
+/**
+  * Case class representing a user-defined rule deciding which breakpoints are shown.
+  * Breakpoints that match no enabled rule are continued by the backend without being shown.
+  *
+  * @param id unique identifier of the rule
+  * @param enabled whether the rule is checked against incoming breakpoints
+  * @param condition the condition a breakpoint must meet to be shown
+  */
+case class BreakpointRule(id: Int, enabled: Boolean, condition: RuleCondition) derives Reader.upickle
+
+
+/**
+  * Case class representing a rule condition, with only the fields used by its kind set.
+  *
+  * @param kind one of `parserName`, `failure`, `refValue` or `offsetRange`
+  * @param name name of the parser that must have run, for `parserName`
+  * @param address address of the reference to check, for `refValue`
+  * @param value value the reference must hold, for `refValue`
+  * @param start first input offset of the range, for `offsetRange`
+  * @param end input offset the range stops before, for `offsetRange`
+  */
+case class RuleCondition(
+        kind: String,
+        name: Option[String] = None,
+        address: Option[Int] = None,
+        value: Option[String] = None,
+        start: Option[Int] = None,
+        end: Option[Int] = None
+    ) derives Reader.upickle {
+
+    /* Convert to a JS object for passing as a command argument */
+    def toJs: js.Dictionary[Any] = js.Dictionary((
+        Seq("kind" -> kind)
+            ++ name.map("name" -> _)
+            ++ address.map("address" -> _)
+            ++ value.map("value" -> _)
+            ++ start.map("start" -> _)
+            ++ end.map("end" -> _)
+    )*)
+}
+
+object RuleCondition {
+    def parserName(name: String): RuleCondition = RuleCondition("parserName", name = Some(name))
+    val failure: RuleCondition = RuleCondition("failure")
+    def refValue(address: Int, value: String): RuleCondition = RuleCondition("refValue", address = Some(address), value = Some(value))
+    def offsetRange(start: Int, end: Int): RuleCondition = RuleCondition("offsetRange", start = Some(start), end = Some(end))
+}
@@ -0,0 +1,30 @@
+package model
+
+import scala.scalajs.js
+
+import model.json.Reader
+
+
+/**
+  * Case class representing how long breakpoints are waited at before an action is applied.
+  *
+  * @param seconds time waited before the action, or None to wait forever
+  * @param action `continue`, `skipAll` or `terminate`
+  */
+case class TimeoutPolicy(seconds: Option[Int] = None, action: String = "continue") derives Reader.upickle {
+
+    /* Convert to a JS object for passing as a command argument */
+    def toJs: js.Dictionary[Any] = js.Dictionary((
+        Seq("action" -> action)
+            ++ seconds.map("seconds" -> _)
+    )*)
+}
+
+/**
+  * Case class representing a warning that a session's breakpoint is about to time out.
+  *
+  * @param sessionId session waiting at the breakpoint
+  * @param secondsLeft time left before the action is applied
+  * @param action action that will be applied
+  */
+case class TimeoutWarning(sessionId: SessionId, secondsLeft: Int, action: String) derives Reader.upickle
@@ -0,0 +1,34 @@
+package model
+
+import model.json.Reader
+
+
+/**
+  * Case class representing what a failed backend command was acting on.
+  *
+  * @param sessionId session the command was acting on, if any
+  * @param nodeId node that could not be found, if any
+  * @param index index of the tab or snapshot the command was given, if any
+  * @param ruleId breakpoint rule the command was given, if any
+  * @param address address of the watched reference, if any
+  * @param path file that could not be read or written, if any
+  * @param invalidRefs every edited reference whose value does not match its declared type
+  */
+case class ErrorContext(
+        sessionId: Option[SessionId] = None,
+        nodeId: Option[Int] = None,
+        index: Option[Int] = None,
+        ruleId: Option[Int] = None,
+        address: Option[Int] = None,
+        path: Option[String] = None,
+        invalidRefs: Option[Seq[RefFieldError]] = None,
+    ) derives Reader.upickle
+
+/**
+  * Case class representing the error every backend command fails with.
+  *
+  * @param code stable camelCase code naming why the command failed, e.g. `treeNotFound`
+  * @param message human-readable description of the failure
+  * @param context what the command was acting on
+  */
+case class CommandError(code: String, message: String, context: ErrorContext = ErrorContext()) derives Reader.upickle
@@ -9,19 +9,22 @@ import model.json.Reader
   * @param input the input string the tree node has to parse
   * @param root the debug tree root node
   * @param isDebuggable Used for if the tree is being actively used for debugging with breakpoints
-  * @param sessionId Id of a debugging session, This will be -1 for trees that cannot be debugged, whereas
-  *                  it will be some id for trees that are actively being debugged or have been debugged
+  * @param sessionId Id of the session the tree was posted or imported in
   * @param sessionName The name of the session, which should alter the tab title.
   * @param refs A list of pairs: `Address` and `Reference Value` from `Parsley`'s State
+  * @param refMetadata Labels, declared types and source locations of refs, empty for older clients
+  * @param metadata How the session's client described itself when it connected, if it did
   */
 case class DebugTree(
         input: String,
         root: DebugNode,
         parserInfo: Map[String, List[(Int, Int)]],
         isDebuggable: Boolean,
-        sessionId: Int,
+        sessionId: SessionId,
         sessionName: String,
-        refs: Seq[(Int, String)] = Nil
+        refs: Seq[(Int, String)] = Nil,
+        refMetadata: Seq[RefMetadata] = Nil,
+        metadata: Option[SessionMetadata] = None
     ) derives Reader.upickle
 
 
@@ -45,4 +48,29 @@ case class DebugNode(nodeId: Int, name: String, internal: String, success: Boole
 }
 
 
+/**
+  * Case class used to represent the nodes merged into a stored tree from a delta sent by RemoteView.
+  *
+  * @param sessionId Id of the debugging session the nodes were merged into
+  * @param isDebuggable Used for if the tree is being actively used for debugging with breakpoints
+  * @param refs A list of pairs: `Address` and `Reference Value` from `Parsley`'s State
+  * @param nodes the added nodes, in pre-order
+  */
+case class TreeDelta(
+        sessionId: SessionId,
+        isDebuggable: Boolean,
+        refs: Seq[(Int, String)],
+        nodes: Seq[DeltaNode]
+    ) derives Reader.upickle
+
+
+/**
+  * Case class identifying a node added by a tree delta.
+  *
+  * @param parentId the node id of the node it was appended to
+  * @param nodeId an unique identifier number for the tree node
+  */
+case class DeltaNode(parentId: Int, nodeId: Int) derives Reader.upickle
+
+
 // TODO: derive uPickle for reading Range from Rust-style start..end range 
\ No newline at end of file
@@ -189,6 +189,32 @@ case object MalformedJSON extends Error {
         "Please report this bug through a GitHub Issue if the problem persists"
 }
 
+case class InvalidRefs(errors: Seq[model.RefFieldError]) extends Warning {
+    override def name: String = "Invalid Reference Values"
+    override def message: String = errors
+        .map(error => s"${error.label.getOrElse(s"Reference ${error.address}")} must be of type ${error.expected}, not '${error.value}'")
+        .mkString("; ")
+}
+
+/**
+  * Failure of a backend command, showing the backend's own message. Failures leaving Dill's state
+  * unusable cannot be closed, any other failure only affected the command
+  */
+case class CommandFailed(error: model.CommandError) extends DillException {
+    override def name: String = error.code.split("(?=[A-Z])").map(_.capitalize).mkString(" ")
+    override def message: String = error.context.sessionId.fold(error.message)(id => s"${error.message} (session $id)")
+
+    private def fatal: Boolean = CommandFailed.fatalCodes.contains(error.code)
+    override def closable: Boolean = !fatal
+    override def style: String = if fatal then "error" else "warning"
+    override def icon: String = if fatal then "bi bi-exclamation-triangle-fill" else "bi bi-exclamation-circle-fill"
+}
+
+object CommandFailed {
+    /* Codes meaning the backend can no longer be relied on */
+    val fatalCodes: Set[String] = Set("lockFailed", "eventEmitFailed", "serialiseFailed")
+}
+
 /* Used when an unexpected error occurs */
 case class UnknownError(msg: String) extends Error {
     override def name: String = "Unknown Error"
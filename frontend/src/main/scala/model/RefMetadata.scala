@@ -0,0 +1,29 @@
+package model
+
+import model.json.Reader
+
+
+/**
+  * Case class representing the description `Parsley` gives of one of its state references.
+  *
+  * @param address address of the reference being described
+  * @param label name given to the reference by the user, if any
+  * @param type declared type of the reference: `int`, `bool`, `string`, `char` or a custom type name
+  * @param location place in the parser's source where the reference was declared, if known
+  */
+case class RefMetadata(
+        address: Int,
+        label: Option[String] = None,
+        `type`: String,
+        location: Option[SourceLocation] = None
+    ) derives Reader.upickle
+
+/**
+  * Case class representing a position in a source file.
+  */
+case class SourceLocation(file: String, line: Int, column: Option[Int] = None) derives Reader.upickle
+
+/**
+  * Case class representing an edited reference rejected because its value does not have the declared type.
+  */
+case class RefFieldError(address: Int, label: Option[String], expected: String, value: String) derives Reader.upickle
@@ -0,0 +1,29 @@
+package model
+
+import model.json.Reader
+
+
+/**
+  * Case class representing the lifecycle of the server receiving trees from RemoteView.
+  *
+  * @param status one of `starting`, `listening` or `failed`
+  * @param address address the server is listening on, when `listening`
+  * @param port port the server is listening on, when `listening`
+  * @param socket Unix domain socket the server is also listening on, when `listening`
+  * @param reason why the server could not be launched, when `failed`
+  */
+case class ServerStatus(
+        status: String,
+        address: Option[String] = None,
+        port: Option[Int] = None,
+        socket: Option[String] = None,
+        reason: Option[String] = None
+    ) derives Reader.upickle {
+
+    /* Short description shown in the settings panel */
+    def describe: String = status match {
+        case "listening" => s"Listening on ${address.getOrElse("")}:${port.getOrElse("")}" + socket.fold("")(path => s" and $path")
+        case "failed" => s"Failed: ${reason.getOrElse("unknown reason")}"
+        case _ => "Starting"
+    }
+}
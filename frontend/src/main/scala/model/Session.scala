@@ -0,0 +1,31 @@
+package model
+
+import model.json.Reader
+
+
+/**
+  * Opaque id of a debugging session, unique across runs of Dill.
+  */
+type SessionId = String
+
+/**
+  * Case class representing who sent a session's requests to the server.
+  *
+  * @param address IP address of the client, if known
+  * @param userAgent `User-Agent` the client sent, if any
+  */
+case class ClientInfo(address: Option[String] = None, userAgent: Option[String] = None) derives Reader.upickle
+
+/**
+  * Case class representing what the server last saw of a session.
+  *
+  * @param sessionId session being described
+  * @param status `connected`, `paused`, `running`, `finished` or `disconnected`
+  * @param lastSeen milliseconds since the epoch the session was last seen
+  * @param client who sent the session's requests, if known
+  * @param metadata how the session's client described itself when it connected, if it did
+  */
+case class SessionInfo(sessionId: SessionId, status: String, lastSeen: Long, client: Option[ClientInfo] = None, metadata: Option[SessionMetadata] = None) derives Reader.upickle {
+    def isPaused: Boolean = status == "paused"
+    def isDisconnected: Boolean = status == "disconnected"
+}
@@ -0,0 +1,34 @@
+package model
+
+import model.json.Reader
+
+
+/**
+  * Case class representing how a debugging session's client described itself when it connected.
+  *
+  * @param parsleyVersion version of `Parsley` the parser was built with
+  * @param remoteViewVersion version of `parsley-debug`'s RemoteView that sent the session
+  * @param protocolVersion version of the remote protocol the client speaks
+  * @param program name of the program or test being debugged
+  * @param workingDirectory directory the program was run from
+  * @param sourceRoots directories the parser's sources are found in
+  */
+case class SessionMetadata(
+        parsleyVersion: Option[String] = None,
+        remoteViewVersion: Option[String] = None,
+        protocolVersion: Option[Int] = None,
+        program: Option[String] = None,
+        workingDirectory: Option[String] = None,
+        sourceRoots: Seq[String] = Nil
+    ) derives Reader.upickle {
+
+    /** One line per known field, for showing in a tooltip */
+    def summary: String = Seq(
+        program.map(name => s"Program: $name"),
+        parsleyVersion.map(version => s"Parsley $version"),
+        remoteViewVersion.map(version => s"RemoteView $version"),
+        protocolVersion.map(version => s"Protocol version $version"),
+        workingDirectory.map(dir => s"Working directory: $dir"),
+        Option.when(sourceRoots.nonEmpty)(s"Source roots: ${sourceRoots.mkString(", ")}"),
+    ).flatten.mkString("\n")
+}
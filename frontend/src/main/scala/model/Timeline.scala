@@ -0,0 +1,42 @@
+package model
+
+import model.json.Reader
+
+
+/**
+  * Case class representing one breakpoint snapshot recorded for a debugging session.
+  *
+  * @param index position of the snapshot in the session's timeline
+  * @param timestamp milliseconds since the Unix epoch when the snapshot was received
+  * @param numNodes number of nodes in the snapshot's tree
+  * @param refsSent references sent by `Parsley` with the snapshot
+  * @param refsReturned references sent back when the breakpoint was continued, if it has been
+  */
+case class SnapshotSummary(
+        index: Int,
+        timestamp: Long,
+        numNodes: Int,
+        refsSent: Seq[(Int, String)],
+        refsReturned: Option[Seq[(Int, String)]]
+    ) derives Reader.upickle
+
+
+/**
+  * Case class representing the differences between a snapshot and the snapshot before it.
+  *
+  * @param index position of the newer snapshot in the session's timeline
+  * @param previousDropped whether the previous snapshot was dropped, in which case nothing was compared
+  * @param nodes nodes added, removed and changed since the previous snapshot
+  * @param refs references whose values changed since the previous snapshot
+  */
+case class SnapshotDiff(index: Int, previousDropped: Boolean, nodes: TreeDiff, refs: Seq[RefChange]) derives Reader.upickle
+
+/**
+  * Case class representing the nodes that differ between two trees, matched by their child ids.
+  */
+case class TreeDiff(added: List[DebugNode], removed: List[DebugNode], changed: List[DebugNode]) derives Reader.upickle
+
+/**
+  * Case class representing a reference whose value differs between two snapshots.
+  */
+case class RefChange(address: Int, previous: Option[String], current: Option[String]) derives Reader.upickle
@@ -94,3 +94,39 @@ case object TreeDownloadFailed extends ErrorToast {
     override def message: String = "Tree download failed, this is likely caused by an issue in your file system"
 }
 
+case class WatchpointTriggered(hit: model.WatchpointHit) extends InfoToast {
+    override def name: String = "Watchpoint"
+    override def message: String = hit.changes
+        .map(change => s"Reference ${change.address}: ${change.previous.getOrElse("unset")} → ${change.current.getOrElse("unset")}")
+        .mkString(", ")
+}
+
+case class SessionDisconnected(session: model.SessionInfo) extends ErrorToast {
+    override def name: String = "Parser Disconnected"
+    override def message: String = s"Session ${session.sessionId} stopped responding while paused at a breakpoint"
+}
+
+case class BreakpointTimingOut(warning: model.TimeoutWarning) extends InfoToast {
+    override def name: String = "Breakpoint Timeout"
+    override def message: String = {
+        val action: String = warning.action match {
+            case "skipAll" => "skip every remaining breakpoint"
+            case "terminate" => "stop the parse"
+            case _ => "continue"
+        }
+        s"Session ${warning.sessionId} will $action in ${warning.secondsLeft}s unless you continue it"
+    }
+}
+
+case class BreakpointWaiting(queued: model.QueuedBreakpoint) extends InfoToast {
+    override def name: String = "Breakpoint Waiting"
+    override def message: String = {
+        val session: String = queued.sessionName.fold(s"Session ${queued.sessionId}")(name => s"$name (session ${queued.sessionId})")
+        queued.parser.fold(s"$session is paused at a breakpoint")(parser => s"$session is paused at $parser")
+    }
+}
+
+case class ServerFailed(reason: String) extends ErrorToast {
+    override def name: String = "Server Failed"
+    override def message: String = reason
+}
@@ -0,0 +1,12 @@
+package model
+
+import model.json.Reader
+
+
+/**
+  * Case class representing a breakpoint stopped at because watched references changed.
+  *
+  * @param sessionId session whose breakpoint was stopped at
+  * @param changes watched references whose values differ from the session's previous breakpoint
+  */
+case class WatchpointHit(sessionId: SessionId, changes: Seq[RefChange]) derives Reader.upickle
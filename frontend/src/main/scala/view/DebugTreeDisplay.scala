@@ -57,6 +57,7 @@ object DebugTreeDisplay {
         StateManagementViewController.setRefs(tree.refs)
         StateManagementViewController.setLocalRefs(tree.refs)
         StateManagementViewController.setOrigRefs(tree.refs)
+        StateManagementViewController.setRefMetadata(tree.refMetadata)
 
         div(
             className := "zoom-container debug-tree-container",
@@ -364,18 +365,18 @@ private object ReactiveNodeDisplay {
                     onClick(_
                         .filterNot(_.ctrlKey) /* Ctrl + Click allow focus node without toggle expansion */
                         .filter(_ => !node.debugNode.isLeaf)
-                        .sample(expansionState)
+                        .sample(expansionState, TreeViewController.getSessionId)
                         .flatMapSwitch {
-                            case ExpansionState.AllChildren =>
+                            case (ExpansionState.AllChildren, _) =>
                                 EventStream.fromValue(Nil)
-                            case ExpansionState.OneIterativeChild =>
+                            case (ExpansionState.OneIterativeChild, sessionId) =>
                                 expandAllChildren.set(true)
-                                Tauri.invoke(Command.FetchNodeChildren, node.debugNode.nodeId).collectRight
-                            case ExpansionState.NoChildren =>
+                                Tauri.invoke(Command.FetchNodeChildren, (sessionId, node.debugNode.nodeId)).collectRight
+                            case (ExpansionState.NoChildren, sessionId) =>
                                 if (node.debugNode.isIterative) {
                                     expandAllChildren.set(false)
                                 }
-                                Tauri.invoke(Command.FetchNodeChildren, node.debugNode.nodeId).collectRight
+                                Tauri.invoke(Command.FetchNodeChildren, (sessionId, node.debugNode.nodeId)).collectRight
                         }
                     ) --> node.children.writer
                 ),
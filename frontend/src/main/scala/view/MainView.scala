@@ -9,15 +9,16 @@ import com.raquo.laminar.api.L.*
 import org.scalajs.dom
 
 import model.errors.DillException
+import model.toast.{BreakpointTimingOut, BreakpointWaiting, ServerFailed, SessionDisconnected, WatchpointTriggered}
 import controller.AppStateController
 import controller.errors.ErrorController
 import controller.viewControllers.CodeViewController
 import controller.viewControllers.MainViewController.View
 import controller.ToastController
 import controller.tauri.{Tauri, Event}
-import controller.viewControllers.{MainViewController, TreeViewController, InputViewController, TabViewController, StateManagementViewController}
+import controller.viewControllers.{MainViewController, TreeViewController, InputViewController, TabViewController, StateManagementViewController, SettingsViewController}
 
-import model.{CodeFileInformation, DebugTree}
+import model.{CodeFileInformation, DebugTree, ServerStatus}
 import controller.tauri.Command
 
 object MainView extends DebugViewPage {
@@ -36,8 +37,34 @@ object MainView extends DebugViewPage {
     val treeStream = eitherTreeStream.collectRight
     val (newTreeStream, unlistenNewTree) = Tauri.listen(Event.NewTree)
 
+    /* Listen for nodes merged into the shown session's tree, and reload that tree */
+    /* Other sessions are sent as TreeReady if they take focus */
+    val (deltaStream, unlistenDelta) = Tauri.listen(Event.TreeDelta)
+    val mergedTreeStream = deltaStream.collectRight
+        .withCurrentValueOf(TreeViewController.getSessionId)
+        .collect { case (delta, sessionId) if delta.sessionId == sessionId => delta.sessionId }
+        .flatMapSwitch(TreeViewController.reloadTree)
+
+    /* Listen for breakpoints stopped at because a watched reference changed */
+    val (watchpointStream, unlistenWatchpoint) = Tauri.listen(Event.WatchpointHit)
+
     val (codeStream, unlistenCode) = Tauri.listen(Event.UploadCodeFile)
 
+    /* Listen for the server starting, listening or failing to launch */
+    val (serverStatusStream, unlistenServerStatus) = Tauri.listen(Event.ServerStatus)
+
+    /* Listen for sessions pausing at a breakpoint, resuming, finishing or disconnecting */
+    val (sessionPausedStream, unlistenSessionPaused) = Tauri.listen(Event.SessionPaused)
+    val (sessionResumedStream, unlistenSessionResumed) = Tauri.listen(Event.SessionResumed)
+    val (sessionClosedStream, unlistenSessionClosed) = Tauri.listen(Event.SessionClosed)
+    val sessionStream = EventStream.merge(sessionPausedStream, sessionResumedStream, sessionClosedStream)
+
+    /* Listen for breakpoints about to be continued by their timeout policy */
+    val (timeoutWarningStream, unlistenTimeoutWarning) = Tauri.listen(Event.BreakpointTimeoutWarning)
+
+    /* Listen for sessions paused while another is shown */
+    val (queuedStream, unlistenQueued) = Tauri.listen(Event.BreakpointQueued)
+
     /* Render main viewing page */
     def apply(): HtmlElement = {
 
@@ -45,10 +72,12 @@ object MainView extends DebugViewPage {
 
         super.render(Some(
             div(
+                /* Clear trees from a previous run, unless kept in a chosen directory, then import any files Dill was opened with */
                 EventStream.fromValue(())
                     .take(1)
                     .flatMapTo(Tauri.invoke(Command.DeleteSavedTrees, ()))
-                    --> Observer.empty,
+                    .flatMapTo(Tauri.invoke(Command.OpenStartupFiles, ()))
+                    .collectLeft --> ErrorController.setError,
 
                 /* Update DOM theme with theme value */
                 AppStateController.isLightMode --> AppStateController.updateDomTheme(),
@@ -79,6 +108,38 @@ object MainView extends DebugViewPage {
                 /* Notify of any errors caught by newTreeStream */
                 newTreeStream.collectLeft --> ErrorController.setError,
 
+                /* Show tree with nodes merged from a delta */
+                mergedTreeStream.collectRight --> TreeViewController.setTree,
+                mergedTreeStream.collectLeft --> ErrorController.setError,
+                deltaStream.collectLeft --> ErrorController.setError,
+
+                /* Name the watched references that changed */
+                watchpointStream.collectRight.map(WatchpointTriggered(_)) --> ToastController.setToast,
+                watchpointStream.collectLeft --> ErrorController.setError,
+
+                /* Track the server's status, fetching any sent before listening */
+                EventStream.fromValue(())
+                    .flatMapTo(SettingsViewController.fetchServerStatus)
+                    .mergeWith(serverStatusStream)
+                    .collectRight --> SettingsViewController.setServerStatus,
+                serverStatusStream.collectRight
+                    .collect { case ServerStatus("failed", _, _, reason) => ServerFailed(reason.getOrElse("Unknown reason")) }
+                    --> ToastController.setToast,
+                serverStatusStream.collectLeft --> ErrorController.setError,
+
+                /* Only show breakpoint controls while the tree's session is paused */
+                sessionStream.collectRight --> TreeViewController.setSessionStatus,
+                sessionClosedStream.collectRight.filter(_.isDisconnected).map(SessionDisconnected(_)) --> ToastController.setToast,
+                sessionStream.collectLeft --> ErrorController.setError,
+
+                /* Warn that a forgotten breakpoint is about to be continued */
+                timeoutWarningStream.collectRight.map(BreakpointTimingOut(_)) --> ToastController.setToast,
+                timeoutWarningStream.collectLeft --> ErrorController.setError,
+
+                /* Tell the user of breakpoints waiting behind the shown session */
+                queuedStream.collectRight.map(BreakpointWaiting(_)) --> ToastController.setToast,
+                queuedStream.collectLeft --> ErrorController.setError,
+
 
                 /* Load main page */
                 child <-- MainViewController.getViewElem,
@@ -98,7 +159,15 @@ object MainView extends DebugViewPage {
                 /* Unlisten to TreeReady event */
                 onUnmountCallback(_ => unlistenTree.get),
                 onUnmountCallback(_ => unlistenNewTree.get),
+                onUnmountCallback(_ => unlistenDelta.get),
+                onUnmountCallback(_ => unlistenWatchpoint.get),
                 onUnmountCallback(_ => unlistenCode.get),
+                onUnmountCallback(_ => unlistenServerStatus.get),
+                onUnmountCallback(_ => unlistenSessionPaused.get),
+                onUnmountCallback(_ => unlistenSessionResumed.get),
+                onUnmountCallback(_ => unlistenSessionClosed.get),
+                onUnmountCallback(_ => unlistenTimeoutWarning.get),
+                onUnmountCallback(_ => unlistenQueued.get),
             )
         ))
     }
@@ -2,10 +2,12 @@ package view
 
 import com.raquo.laminar.api.L.*
 
+import model.TimeoutPolicy
 import model.settings.*
 import model.toast.SettingsApplied
 import model.toast.DefaultSettingsApplied
 import controller.ToastController
+import controller.errors.ErrorController
 import controller.viewControllers.SettingsViewController
 
 private case class SettingView(setting: UserSetting) {
@@ -63,7 +65,110 @@ private case class SettingView(setting: UserSetting) {
 
 
 object SettingsView {
-    
+
+    /* Which of several sessions paused at once is shown */
+    private def renderFocusPolicy: HtmlElement = {
+        val policy: Var[String] = Var("mostRecent")
+
+        div(
+            className := "sidepanel-item-container",
+
+            div(className := "sidepanel-item-name", "Show paused sessions"),
+
+            /* Start from the policy Dill was launched with */
+            SettingsViewController.fetchFocusPolicy.collectRight --> policy.writer,
+
+            select(
+                option(value := "mostRecent", "Most recent"),
+                option(value := "firstCome", "First come first served"),
+                option(value := "never", "Never switch"),
+                controlled(
+                    value <-- policy.signal,
+                    onChange.mapToValue --> policy.writer,
+                ),
+            ),
+
+            policy.signal.changes
+                .flatMapSwitch(SettingsViewController.setFocusPolicy)
+                .collectLeft --> ErrorController.setError,
+        )
+    }
+
+    /* Time a forgotten breakpoint is waited at, and the action then applied to it */
+    private def renderBreakpointTimeout: HtmlElement = {
+        val policy: Var[TimeoutPolicy] = Var(TimeoutPolicy())
+        val setBus: EventBus[Unit] = EventBus()
+
+        div(
+            className := "sidepanel-item-container",
+
+            div(className := "sidepanel-item-name", "Breakpoint timeout"),
+
+            /* Start from the policy Dill was launched with */
+            SettingsViewController.fetchBreakpointTimeout.collectRight --> policy.writer,
+
+            input(
+                typ := "number",
+                minAttr := "1",
+                placeholder := "Wait forever",
+                value <-- policy.signal.map(_.seconds.fold("")(_.toString)),
+                onInput.mapToValue.map(_.toIntOption) --> policy.updater[Option[Int]]((old, seconds) => old.copy(seconds = seconds)),
+            ),
+
+            select(
+                option(value := "continue", "Continue"),
+                option(value := "skipAll", "Skip all"),
+                option(value := "terminate", "Terminate"),
+                controlled(
+                    value <-- policy.signal.map(_.action),
+                    onChange.mapToValue --> policy.updater[String]((old, action) => old.copy(action = action)),
+                ),
+            ),
+
+            button("Set", onClick.mapToUnit --> setBus.writer),
+
+            setBus.stream
+                .sample(policy.signal)
+                .flatMapSwitch(SettingsViewController.setBreakpointTimeout)
+                .collectLeft --> ErrorController.setError,
+        )
+    }
+
+    /* Status of the server, with a port to restart it on */
+    private def renderServer: HtmlElement = {
+        val port: Var[Option[Int]] = Var(None)
+        val restartBus: EventBus[Unit] = EventBus()
+
+        div(
+            className := "sidepanel-item-container",
+
+            div(
+                className := "sidepanel-item-name",
+                child.text <-- SettingsViewController.getServerStatus.map(_.fold("Server starting")(_.describe)),
+            ),
+
+            input(
+                typ := "number",
+                minAttr := "0",
+                placeholder := "Port",
+                onInput.mapToValue.map(_.toIntOption) --> port.writer,
+            ),
+
+            button("Restart", onClick.mapToUnit --> restartBus.writer),
+
+            /* Token for the parsley client, only shown when authentication is enabled */
+            child.maybe <-- SettingsViewController.fetchServerToken
+                .collectRight
+                .map(_.map(token => div(className := "sidepanel-item-name", s"Token: $token")))
+                .toSignal(None),
+
+            restartBus.stream
+                .sample(port.signal)
+                .flatMapSwitch(SettingsViewController.restartServer)
+                .collectLeft --> ErrorController.setError,
+        )
+    }
+
     def apply(): HtmlElement = {
         div(
             className := "sidepanel-container settings",
@@ -74,6 +179,9 @@ object SettingsView {
             div(
                 className := "sidepanel-items-container",
                 allUserSettings.map(SettingView(_).render),
+                renderFocusPolicy,
+                renderBreakpointTimeout,
+                renderServer,
             ),
 
             div(flexGrow := 1),
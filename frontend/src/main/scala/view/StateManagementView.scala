@@ -2,8 +2,10 @@ package view
 
 import com.raquo.laminar.api.L.*
 
+import model.RefMetadata
 import model.toast.StateApplied
 import controller.ToastController
+import controller.errors.ErrorController
 import controller.viewControllers.StateManagementViewController
 import controller.viewControllers.TreeViewController
 import controller.viewControllers.TreeViewController.treeExists
@@ -15,6 +17,7 @@ object StateManagementView {
     def renderReference(ref: (Int, String)): HtmlElement = {
         val refAddr: Int = ref._1
         val refValue: String = ref._2
+        val metadata: Option[RefMetadata] = StateManagementViewController.getRefMetadata(refAddr)
 
         div(
             className := "sidepanel-item-container",
@@ -23,7 +26,12 @@ object StateManagementView {
                 className := "sidepanel-item-title",
                 text <-- StateManagementViewController.getRefNumber(refAddr)
                     .map(i => s"R${StateRef.subscriptInt(i)}")
+                    .map(name => metadata.flatMap(_.label).fold(name)(label => s"$name: $label"))
             ),
+
+            /* Declared type and where the reference was declared, if Parsley sent them */
+            metadata.map(meta => div("Type: ", span(className := "ref-value", meta.`type`))),
+            metadata.flatMap(_.location).map(location => div(s"Declared at ${location.file}:${location.line}")),
             
             div("Current Value: ", span(className := "ref-value", text <-- StateManagementViewController.getRefValue(refAddr))),
             div("Original Value: ", span(className := "ref-value", StateManagementViewController.getOrigRefValue(refAddr))),
@@ -60,11 +68,13 @@ object StateManagementView {
             div(    
                 className := "sidepanel-footer",
 
-                child(button("Apply", onClick --> (_ => {
-                    StateManagementViewController.getLocalRefs.foreach(StateManagementViewController.updateNewRefValue)
-                    TreeViewController.setRefs(StateManagementViewController.getLocalRefs)
-                    ToastController.setToast(StateApplied)
-                }))) <-- StateManagementViewController.refsEmptySignal.not,
+                /* Only show values as applied once the backend accepts them as their declared types */
+                child(button("Apply", onClick.flatMapTo(TreeViewController.setRefs(StateManagementViewController.getLocalRefs)) --> {
+                    case Right(_) =>
+                        StateManagementViewController.getLocalRefs.foreach(StateManagementViewController.updateNewRefValue)
+                        ToastController.setToast(StateApplied)
+                    case Left(err) => ErrorController.setError.onNext(err)
+                })) <-- StateManagementViewController.refsEmptySignal.not,
 
                 child(button("Restore Originals", onClick --> (_ => {
                     TreeViewController.resetRefs().collectRight --> StateManagementViewController.getRefsVar
@@ -37,6 +37,12 @@ object TabView {
             cls("selected") <-- TabViewController.tabSelected(index),
 
             text <-- TabViewController.getFileName(index),
+
+            /* Describe the client that sent the selected tab's tree */
+            title <-- TabViewController.tabSelected(index)
+                .combineWith(TreeViewController.getSessionDescription)
+                .map((selected, description) => if selected then description else ""),
+
             closeTabButton(index),
 
             /* Sets selected tab signal to newly selected tab */
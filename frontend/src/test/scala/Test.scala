@@ -29,7 +29,7 @@ class Test extends AnyFlatSpec with should.Matchers {
         "parserInfo": {},
         "isDebuggable": false,
         "refs": [],
-        "sessionId": -1,
+        "sessionId": "0190a6c4-7e2a-7c3b-9d1e-5f2a8b6c4d10",
         "sessionName": "tree"
     }"""
 
@@ -43,7 +43,7 @@ class Test extends AnyFlatSpec with should.Matchers {
 
         /* Check that the root tree has been deserialised correctly */
         tree.input should be ("Test")
-        tree.sessionId should be (-1)
+        tree.sessionId should be ("0190a6c4-7e2a-7c3b-9d1e-5f2a8b6c4d10")
         tree.isDebuggable should be (false)
         tree.refs should be (Nil)
 